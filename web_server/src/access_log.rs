@@ -0,0 +1,227 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::http_date::format_log_date;
+use crate::Method;
+use crate::Request;
+use crate::Result;
+use crate::Version;
+
+/// Receives one entry per answered request. Implementations must be cheap or
+/// hand the entry off, since they run on the connection's worker thread.
+pub trait LogSink: Send + Sync {
+    fn log(&self, entry: &AccessLogEntry);
+}
+
+/// Line formats understood by the bundled sinks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// `host ident authuser [date] "request" status bytes`
+    Common,
+    /// Common Log Format followed by `"referer" "user-agent"`.
+    #[default]
+    Combined,
+}
+
+/// Time spent in each phase of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct RequestTiming {
+    /// Reading and parsing the request, from its first byte on.
+    pub parse: Duration,
+    /// Routing and running the handler.
+    pub handle: Duration,
+    /// Serializing the response and writing it to the socket.
+    pub write: Duration,
+}
+
+impl RequestTiming {
+    pub fn total(&self) -> Duration {
+        self.parse + self.handle + self.write
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AccessLogEntry {
+    pub peer: Option<SocketAddr>,
+    /// When the first byte of the request arrived.
+    pub time: SystemTime,
+    /// `None` if the request could not be parsed.
+    pub method: Option<Method>,
+    /// The request target, including the query string.
+    pub target: Option<String>,
+    pub version: Option<Version>,
+    pub status: u16,
+    /// Body bytes sent, not counting the head.
+    pub response_size: u64,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub timing: RequestTiming,
+    /// Whether `format` appends the timing breakdown.
+    pub detailed_timing: bool,
+}
+
+impl AccessLogEntry {
+    pub(crate) fn new(
+        peer: Option<SocketAddr>,
+        time: SystemTime,
+        request: Option<&Request>,
+        status: u16,
+        response_size: u64,
+    ) -> AccessLogEntry {
+        let header = |name| request.and_then(|request| request.header(name).map(String::from));
+        AccessLogEntry {
+            peer,
+            time,
+            method: request.map(Request::method),
+            target: request.map(|request| request.target().to_string()),
+            version: request.map(Request::version),
+            status,
+            response_size,
+            referer: header("Referer"),
+            user_agent: header("User-Agent"),
+            timing: RequestTiming::default(),
+            detailed_timing: false,
+        }
+    }
+
+    /// Formats the entry as one log line without the trailing newline.
+    pub fn format(&self, format: LogFormat) -> String {
+        let host = self
+            .peer
+            .map_or("-".to_string(), |peer| peer.ip().to_string());
+        let request_line = match (&self.method, &self.target) {
+            (Some(method), Some(target)) => format!(
+                "{} {} {}",
+                method.as_str(),
+                target,
+                self.version.unwrap_or(Version::Http11).as_str()
+            ),
+            _ => "-".to_string(),
+        };
+        let size = if self.response_size == 0 {
+            "-".to_string()
+        } else {
+            self.response_size.to_string()
+        };
+
+        let mut line = format!(
+            "{} - - [{}] \"{}\" {} {}",
+            host,
+            format_log_date(self.time),
+            escape(&request_line),
+            self.status,
+            size
+        );
+        if format == LogFormat::Combined {
+            line.push_str(&format!(
+                " \"{}\" \"{}\"",
+                escape(self.referer.as_deref().unwrap_or("-")),
+                escape(self.user_agent.as_deref().unwrap_or("-"))
+            ));
+        }
+        if self.detailed_timing {
+            line.push_str(&format!(
+                " parse={}us handle={}us write={}us total={}us",
+                self.timing.parse.as_micros(),
+                self.timing.handle.as_micros(),
+                self.timing.write.as_micros(),
+                self.timing.total().as_micros()
+            ));
+        }
+        line
+    }
+}
+
+/// Quotes and non-printable bytes would let a client forge log lines.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Access logging settings of a server.
+#[derive(Clone)]
+pub struct AccessLog {
+    pub(crate) sink: Arc<dyn LogSink>,
+    /// Adds the parse, handle and write durations to every entry.
+    pub detailed_timing: bool,
+}
+
+impl AccessLog {
+    pub fn new(sink: impl LogSink + 'static) -> AccessLog {
+        AccessLog {
+            sink: Arc::new(sink),
+            detailed_timing: false,
+        }
+    }
+
+    pub(crate) fn log(&self, mut entry: AccessLogEntry) {
+        entry.detailed_timing = self.detailed_timing;
+        self.sink.log(&entry);
+    }
+}
+
+impl std::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("detailed_timing", &self.detailed_timing)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Prints entries to standard output.
+pub struct StdoutLogSink {
+    format: LogFormat,
+}
+
+impl StdoutLogSink {
+    pub fn new(format: LogFormat) -> StdoutLogSink {
+        StdoutLogSink { format }
+    }
+}
+
+impl LogSink for StdoutLogSink {
+    fn log(&self, entry: &AccessLogEntry) {
+        println!("{}", entry.format(self.format));
+    }
+}
+
+/// Appends entries to a file.
+pub struct FileLogSink {
+    file: Mutex<File>,
+    format: LogFormat,
+}
+
+impl FileLogSink {
+    pub fn open(path: impl AsRef<Path>, format: LogFormat) -> Result<FileLogSink> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileLogSink {
+            file: Mutex::new(file),
+            format,
+        })
+    }
+}
+
+impl LogSink for FileLogSink {
+    fn log(&self, entry: &AccessLogEntry) {
+        let line = entry.format(self.format) + "\n";
+        let result = match self.file.lock() {
+            Ok(mut file) => file.write_all(line.as_bytes()),
+            Err(_) => return,
+        };
+        if let Err(error) = result {
+            println!("Failed to write access log: {}", error);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config_file;
+use crate::run_server_with_router;
+use crate::AccessLog;
+use crate::CompressionPolicy;
+use crate::FileCache;
+use crate::HttpServer;
+use crate::Middleware;
+use crate::RateLimit;
+use crate::Request;
+use crate::Response;
+use crate::Result;
+use crate::Router;
+use crate::ServerConfig;
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use crate::VirtualHost;
+
+/// Collects server settings and starts the server, e.g.
+/// `HttpServer::builder().content_dir("/srv/www").threads(8).bind("0.0.0.0:8080").start()`.
+#[derive(Default)]
+pub struct HttpServerBuilder {
+    config: ServerConfig,
+    router: Router,
+    middlewares: Vec<Middleware>,
+    virtual_hosts: Vec<(String, VirtualHost)>,
+}
+
+impl HttpServerBuilder {
+    pub fn new() -> HttpServerBuilder {
+        HttpServerBuilder::default()
+    }
+
+    /// Starts from an existing configuration instead of the defaults.
+    pub fn config(mut self, config: ServerConfig) -> HttpServerBuilder {
+        self.config = config;
+        self
+    }
+
+    /// Applies the settings of a TOML file on top of the current ones, so
+    /// later builder calls override the file. Relative paths in it are
+    /// resolved against the directory of the file; unknown keys are errors.
+    ///
+    /// Keys at the top: `bind`, `also_bind`, `threads`, `max_threads`,
+    /// `root`, `index_files`, `directory_listing`, `server_header`,
+    /// `health_check`, `metrics`, `metrics_address`, `log` (a file, or `-`
+    /// for stdout) and `log_format` (`common` or `combined`). Tables:
+    ///
+    /// - `[limits]`: `max_request_size`, `max_body_size`, `max_uri_length`,
+    ///   `max_header_bytes`, `max_header_count`,
+    ///   `max_requests_per_connection`, `max_queued_connections`,
+    ///   `max_websockets`, `max_connections_per_ip`, `requests_per_second`
+    ///   and `burst`.
+    /// - `[timeouts]`, in seconds: `read`, `request`, `write` (`0` waits
+    ///   forever), `keep_alive` and `thread_idle`.
+    /// - `[cache]`: `max_bytes` and `max_file_bytes`, see `FileCache`.
+    /// - `[compression]`: the fields of `CompressionPolicy`.
+    /// - `[tls]`: `cert`, `key` and `redirect_from`; needs the `tls` feature.
+    /// - `[mime_types]`, `[cache_control]` and `[error_pages]`: extension,
+    ///   path pattern or status mapped to a MIME type, `Cache-Control` value
+    ///   or template.
+    /// - `[[vhost]]`, once per virtual host: `host` (a pattern) and `root`.
+    pub fn config_file(mut self, path: impl AsRef<Path>) -> Result<HttpServerBuilder> {
+        config_file::apply(path.as_ref(), &mut self.config, &mut self.virtual_hosts)?;
+        Ok(self)
+    }
+
+    pub fn content_dir(mut self, content_dir: impl Into<PathBuf>) -> HttpServerBuilder {
+        self.config.content_dir = content_dir.into();
+        self
+    }
+
+    /// Serves files ending in `.extension` with the given `Content-Type`.
+    pub fn mime_type(mut self, extension: &str, mime: &str) -> HttpServerBuilder {
+        self.config.mime_types.insert(extension, mime);
+        self
+    }
+
+    pub fn threads(mut self, threads_count: usize) -> HttpServerBuilder {
+        self.config.threads_count = threads_count;
+        self
+    }
+
+    /// Lets the pool grow from `threads` up to `max_threads` workers under
+    /// load. Workers above `threads` exit after `idle_timeout` without work.
+    pub fn max_threads(mut self, max_threads: usize, idle_timeout: Duration) -> HttpServerBuilder {
+        self.config.max_threads = Some(max_threads);
+        self.config.thread_idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Connections that may wait for a worker before new ones get a `503`.
+    pub fn max_queued_connections(mut self, count: usize) -> HttpServerBuilder {
+        self.config.max_queued_connections = Some(count);
+        self
+    }
+
+    /// Answers WebSocket handshakes beyond `count` open connections with
+    /// `503`.
+    pub fn max_websockets(mut self, count: usize) -> HttpServerBuilder {
+        self.config.max_websockets = count;
+        self
+    }
+
+    /// Answers further connections from an IP address that already has
+    /// `count` open with `429 Too Many Requests`.
+    pub fn max_connections_per_ip(mut self, count: usize) -> HttpServerBuilder {
+        self.config
+            .rate_limit
+            .get_or_insert_with(RateLimit::default)
+            .max_connections_per_ip = Some(count);
+        self
+    }
+
+    /// Lets each IP address send `rate` requests per second on average and
+    /// up to `burst` at once; requests beyond that get `429 Too Many
+    /// Requests` with a `Retry-After`.
+    pub fn requests_per_second(mut self, rate: f64, burst: u32) -> HttpServerBuilder {
+        let limit = self
+            .config
+            .rate_limit
+            .get_or_insert_with(RateLimit::default);
+        limit.requests_per_second = Some(rate);
+        limit.burst = burst;
+        self
+    }
+
+    pub fn bind(mut self, address: impl Into<String>) -> HttpServerBuilder {
+        self.config.address = address.into();
+        self
+    }
+
+    /// Listens on `address` as well as on the one given to `bind`.
+    pub fn also_bind(mut self, address: impl Into<String>) -> HttpServerBuilder {
+        self.config.additional_addresses.push(address.into());
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> HttpServerBuilder {
+        self.config.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Total time allowed for receiving one request, see
+    /// `ServerConfig::request_read_timeout`.
+    pub fn request_read_timeout(mut self, timeout: Duration) -> HttpServerBuilder {
+        self.config.request_read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> HttpServerBuilder {
+        self.config.write_timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_headers(mut self, max_bytes: usize, max_count: usize) -> HttpServerBuilder {
+        self.config.max_header_bytes = max_bytes;
+        self.config.max_header_count = max_count;
+        self
+    }
+
+    pub fn max_request_size(mut self, bytes: usize) -> HttpServerBuilder {
+        self.config.max_request_size = bytes;
+        self
+    }
+
+    pub fn max_body_size(mut self, bytes: usize) -> HttpServerBuilder {
+        self.config.max_body_size = bytes;
+        self
+    }
+
+    pub fn max_uri_length(mut self, bytes: usize) -> HttpServerBuilder {
+        self.config.max_uri_length = bytes;
+        self
+    }
+
+    pub fn keep_alive(mut self, idle_timeout: Duration, max_requests: usize) -> HttpServerBuilder {
+        self.config.keep_alive_timeout = idle_timeout;
+        self.config.max_requests_per_connection = max_requests;
+        self
+    }
+
+    /// Sends `Cache-Control: value` with static files whose path matches
+    /// `pattern`. Patterns are checked in the order they were added.
+    pub fn cache_control(mut self, pattern: &str, value: &str) -> HttpServerBuilder {
+        self.config
+            .cache_control
+            .push((pattern.to_string(), value.to_string()));
+        self
+    }
+
+    /// Keeps static files in `cache`, e.g.
+    /// `.file_cache(FileCache::new(64 * 1024 * 1024))`. Keep a clone of it to
+    /// read its hit and miss counts.
+    pub fn file_cache(mut self, cache: FileCache) -> HttpServerBuilder {
+        self.config.file_cache = Some(cache);
+        self
+    }
+
+    /// Compresses eligible responses, e.g.
+    /// `.compression(CompressionPolicy { level: 9, ..CompressionPolicy::default() })`.
+    pub fn compression(mut self, policy: CompressionPolicy) -> HttpServerBuilder {
+        self.config.compression = Some(policy);
+        self
+    }
+
+    /// Logs every request, e.g.
+    /// `.access_log(AccessLog::new(StdoutLogSink::new(LogFormat::Combined)))`.
+    pub fn access_log(mut self, access_log: AccessLog) -> HttpServerBuilder {
+        self.config.access_log = Some(access_log);
+        self
+    }
+
+    /// Lists the content of directories that have no index file.
+    pub fn directory_listing(mut self, enabled: bool) -> HttpServerBuilder {
+        self.config.directory_listing = enabled;
+        self
+    }
+
+    /// Serves the file at `path` under the content directory for error
+    /// responses with `status`, see `ErrorPages::template`.
+    pub fn error_page(mut self, status: u16, path: impl Into<PathBuf>) -> HttpServerBuilder {
+        let error_pages = std::mem::take(&mut self.config.error_pages);
+        self.config.error_pages = error_pages.template(status, path);
+        self
+    }
+
+    /// Builds error responses with `status` with `handler`, see
+    /// `ErrorPages::handler`.
+    pub fn error_handler<F>(mut self, status: u16, handler: F) -> HttpServerBuilder
+    where
+        F: Fn(&Request, u16) -> Response + Send + Sync + 'static,
+    {
+        let error_pages = std::mem::take(&mut self.config.error_pages);
+        self.config.error_pages = error_pages.handler(status, handler);
+        self
+    }
+
+    /// Answers `GET path` with `200 ok` while the server is up, for load
+    /// balancer health checks.
+    pub fn health_check(mut self, path: &str) -> HttpServerBuilder {
+        self.config.health_path = Some(path.to_string());
+        self
+    }
+
+    /// Exposes request statistics at `path`, in the Prometheus text format
+    /// or as JSON for `?format=json`.
+    pub fn metrics(mut self, path: &str) -> HttpServerBuilder {
+        self.config.metrics_path = Some(path.to_string());
+        self
+    }
+
+    /// Serves the health check and metrics endpoints on `address` only,
+    /// instead of next to the other routes.
+    pub fn metrics_address(mut self, address: impl Into<String>) -> HttpServerBuilder {
+        self.config.metrics_address = Some(address.into());
+        self
+    }
+
+    /// Serves HTTPS with the given certificate, e.g.
+    /// `.tls(TlsConfig::from_pem_files("cert.pem", "key.pem")?)`.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> HttpServerBuilder {
+        self.config.tls = Some(tls);
+        self
+    }
+
+    /// Also listens for plain HTTP on `address` and redirects those requests
+    /// to the HTTPS listener.
+    #[cfg(feature = "tls")]
+    pub fn redirect_http_from(mut self, address: impl Into<String>) -> HttpServerBuilder {
+        self.config.http_redirect_address = Some(address.into());
+        self
+    }
+
+    /// Serves requests for hosts matching `pattern` from `host`, see
+    /// `Router::virtual_host`.
+    pub fn virtual_host(mut self, pattern: &str, host: VirtualHost) -> HttpServerBuilder {
+        self.virtual_hosts.push((pattern.to_string(), host));
+        self
+    }
+
+    pub fn router(mut self, router: Router) -> HttpServerBuilder {
+        self.router = router;
+        self
+    }
+
+    /// Wraps request handling in `middleware`, see `Router::middleware`.
+    /// These run outside the middlewares of the router, in the order added.
+    pub fn use_middleware<F>(mut self, middleware: F) -> HttpServerBuilder
+    where
+        F: Fn(&mut Request, &dyn Fn(&mut Request) -> Response) -> Response + Send + Sync + 'static,
+    {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    pub fn start(self) -> Result<Arc<Mutex<HttpServer>>> {
+        let mut router = self.router;
+        router.middlewares.splice(0..0, self.middlewares);
+        for (pattern, host) in self.virtual_hosts {
+            router.virtual_host(&pattern, host);
+        }
+        run_server_with_router(self.config, router)
+    }
+}
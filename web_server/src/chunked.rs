@@ -0,0 +1,181 @@
+use std::io::{BufRead, Read, Write};
+
+use crate::Result;
+use crate::WebServerError;
+
+/// Longest chunk size or trailer line, including its line break.
+const MAX_LINE_LENGTH: u64 = 4096;
+
+/// Decodes a `Transfer-Encoding: chunked` body (RFC 9112, section 7.1).
+/// Chunk extensions and trailer fields are read and dropped. The framing
+/// counts towards `max_size` along with the data, so a client cannot send
+/// an endless stream of empty extensions either. A single framing line
+/// longer than `MAX_LINE_LENGTH` is a bad request.
+pub(crate) fn read_chunked_body(reader: &mut impl BufRead, max_size: u64) -> Result<Vec<u8>> {
+    let mut limited = reader.take(max_size);
+    let too_large = || {
+        WebServerError::PayloadTooLarge(format!("Chunked request body exceeds {} bytes", max_size))
+    };
+
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(&mut limited).map_err(|error| match error {
+            LineError::Limit => too_large(),
+            LineError::Other(error) => error,
+        })?;
+        let size = parse_chunk_size(&line)
+            .ok_or_else(|| WebServerError::BadRequest(format!("Invalid chunk size {:?}", line)))?;
+        if size == 0 {
+            break;
+        }
+        if size > limited.limit() {
+            return Err(too_large());
+        }
+
+        let start = body.len();
+        limited.by_ref().take(size).read_to_end(&mut body)?;
+        if ((body.len() - start) as u64) < size {
+            return Err(WebServerError::BadRequest(
+                "Chunked request body ended inside a chunk".to_string(),
+            ));
+        }
+        match read_line(&mut limited) {
+            Ok(line) if line.is_empty() => {}
+            Ok(_) => {
+                return Err(WebServerError::BadRequest(
+                    "Chunk data is longer than its size".to_string(),
+                ))
+            }
+            Err(LineError::Limit) => return Err(too_large()),
+            Err(LineError::Other(error)) => return Err(error),
+        }
+    }
+
+    // Trailer fields up to the blank line that ends the body.
+    loop {
+        match read_line(&mut limited) {
+            Ok(line) if line.is_empty() => return Ok(body),
+            Ok(_) => {}
+            Err(LineError::Limit) => return Err(too_large()),
+            Err(LineError::Other(error)) => return Err(error),
+        }
+    }
+}
+
+/// Copies the data of a chunked body from `reader` to `writer` as it
+/// arrives, without the framing, for a client that does not know chunks.
+/// Unlike `read_chunked_body` there is no size limit, the body is not kept.
+pub(crate) fn copy_unchunked<R: BufRead>(
+    reader: &mut R,
+    writer: &mut dyn Write,
+) -> std::io::Result<()> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let next_line = |reader: &mut R| {
+        read_line(&mut reader.by_ref().take(u64::MAX)).map_err(|error| match error {
+            LineError::Limit => invalid("Chunked body ended early".to_string()),
+            LineError::Other(error) => invalid(error.to_string()),
+        })
+    };
+
+    loop {
+        let line = next_line(reader)?;
+        let size = parse_chunk_size(&line)
+            .ok_or_else(|| invalid(format!("Invalid chunk size {:?}", line)))?;
+        if size == 0 {
+            break;
+        }
+        let copied = std::io::copy(&mut reader.by_ref().take(size), writer)?;
+        if copied < size {
+            return Err(invalid("Chunked body ended inside a chunk".to_string()));
+        }
+        if !next_line(reader)?.is_empty() {
+            return Err(invalid("Chunk data is longer than its size".to_string()));
+        }
+    }
+    // Trailer fields are dropped along with the framing.
+    while !next_line(reader)?.is_empty() {}
+    Ok(())
+}
+
+/// The size at the start of a chunk line, ignoring any extensions after
+/// it. Only hex digits are allowed, `from_str_radix` would take a sign too.
+fn parse_chunk_size(line: &str) -> Option<u64> {
+    let size = line.split(';').next().unwrap_or("").trim();
+    if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(size, 16).ok()
+}
+
+enum LineError {
+    /// The size limit was reached before the line ended.
+    Limit,
+    Other(WebServerError),
+}
+
+/// One line of chunk framing, without its line break.
+fn read_line<R: BufRead>(reader: &mut std::io::Take<R>) -> std::result::Result<String, LineError> {
+    let mut line = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_LINE_LENGTH)
+        .read_until(b'\n', &mut line)
+        .map_err(|error| LineError::Other(error.into()))?;
+    if line.last() != Some(&b'\n') {
+        return Err(if reader.limit() == 0 {
+            LineError::Limit
+        } else if line.len() as u64 == MAX_LINE_LENGTH {
+            LineError::Other(WebServerError::BadRequest(format!(
+                "Chunk framing line exceeds {} bytes",
+                MAX_LINE_LENGTH
+            )))
+        } else {
+            LineError::Other(WebServerError::BadRequest(
+                "Chunked request body ended early".to_string(),
+            ))
+        });
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| {
+        LineError::Other(WebServerError::BadRequest(
+            "Chunk framing is not valid UTF-8".to_string(),
+        ))
+    })
+}
+
+/// Frames everything written to it as chunks. `finish` writes the last
+/// chunk; without it the body is incomplete.
+pub(crate) struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> ChunkedWriter<W> {
+        ChunkedWriter { inner }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // An empty chunk would end the body.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:x}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
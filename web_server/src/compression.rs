@@ -0,0 +1,210 @@
+use std::io::Write;
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+
+use crate::response::BodyProducer;
+use crate::Request;
+use crate::Response;
+use crate::Result;
+
+/// When and how responses are compressed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    /// zlib compression level, from 0 (none) to 9 (best).
+    pub level: u32,
+    /// Bodies shorter than this are sent as they are; compressing them
+    /// costs more time than it saves bandwidth. A body of unknown length,
+    /// from a reader or a `Response::streaming` producer, is read this far
+    /// before deciding.
+    pub min_size: u64,
+    /// Bodies longer than this are not compressed, because the whole body
+    /// has to be in memory to compress it. Bodies of unknown length are
+    /// compressed as they are written instead, so this does not apply.
+    pub max_size: u64,
+    /// `Content-Type` prefixes eligible for compression. Parameters such as
+    /// `charset` are ignored when matching.
+    pub content_types: Vec<String>,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        CompressionPolicy {
+            level: 6,
+            min_size: 1024,
+            max_size: 16 * 1024 * 1024,
+            content_types: [
+                "text/",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "image/svg+xml",
+            ]
+            .iter()
+            .map(|prefix| prefix.to_string())
+            .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn token(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+impl CompressionPolicy {
+    fn is_eligible(&self, content_type: &str) -> bool {
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        self.content_types.iter().any(|prefix| {
+            media_type.len() >= prefix.len()
+                && media_type[..prefix.len()].eq_ignore_ascii_case(prefix)
+        })
+    }
+
+    /// Compresses the body of a `200` response if its type and size qualify
+    /// and the client accepts gzip or deflate.
+    pub(crate) fn apply(&self, request: &Request, response: &mut Response) -> Result<()> {
+        if response.status() != 200 || response.header("Content-Encoding").is_some() {
+            return Ok(());
+        }
+        match response.header("Content-Type") {
+            Some(content_type) if self.is_eligible(content_type) => {}
+            _ => return Ok(()),
+        }
+
+        // The representation depends on Accept-Encoding from here on, even
+        // when this particular client gets it uncompressed.
+        add_vary(response);
+
+        let encoding = match negotiate(request.header("Accept-Encoding").unwrap_or("")) {
+            Some(encoding) => encoding,
+            None => return Ok(()),
+        };
+        let level = Compression::new(self.level.min(9));
+        match response.body_len() {
+            Some(len) if len >= self.min_size && len <= self.max_size => {
+                let body = response.read_body()?;
+                response.set_body(compress(&body, encoding, level)?);
+            }
+            Some(_) => return Ok(()),
+            None => match response.take_streamed_body(self.min_size)? {
+                Some(producer) => {
+                    response.set_chunked_producer(compress_stream(producer, encoding, level))
+                }
+                None => return Ok(()),
+            },
+        }
+
+        response.set_header("Content-Encoding", encoding.token());
+        // The bytes differ from the identity encoding, so a strong validator
+        // would be wrong; a weak one still allows revalidation with 304.
+        if let Some(etag) = response.header("ETag") {
+            if etag.starts_with('"') {
+                let weak = format!("W/{}", etag);
+                response.set_header("ETag", &weak);
+            }
+        }
+        // Byte ranges would refer to the compressed bytes.
+        response.remove_header("Accept-Ranges");
+        Ok(())
+    }
+}
+
+fn compress(body: &[u8], encoding: Encoding, level: Compression) -> Result<Vec<u8>> {
+    let buffer = Vec::with_capacity(body.len() / 2);
+    Ok(match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(buffer, level);
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(buffer, level);
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+    })
+}
+
+/// Compresses what `producer` writes on its way to the connection. A flush
+/// by the producer flushes the encoder too, so nothing is held back.
+fn compress_stream(producer: BodyProducer, encoding: Encoding, level: Compression) -> BodyProducer {
+    Box::new(move |writer| match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(writer, level);
+            producer(&mut encoder)?;
+            encoder.finish().map(|_| ())
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(writer, level);
+            producer(&mut encoder)?;
+            encoder.finish().map(|_| ())
+        }
+    })
+}
+
+fn add_vary(response: &mut Response) {
+    let vary = match response.header("Vary") {
+        Some(vary)
+            if vary
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("Accept-Encoding")) =>
+        {
+            return
+        }
+        Some(vary) => format!("{}, Accept-Encoding", vary),
+        None => "Accept-Encoding".to_string(),
+    };
+    response.set_header("Vary", &vary);
+}
+
+/// Picks the encoding with the highest quality value in `Accept-Encoding`,
+/// preferring gzip on ties. `*` stands for any encoding not listed.
+fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let mut gzip = None;
+    let mut deflate = None;
+    let mut any = None;
+
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .find_map(|parameter| {
+                let (name, value) = parameter.split_once('=')?;
+                if name.trim().eq_ignore_ascii_case("q") {
+                    value.trim().parse::<f32>().ok()
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(1.0);
+
+        if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            gzip = Some(quality);
+        } else if coding.eq_ignore_ascii_case("deflate") {
+            deflate = Some(quality);
+        } else if coding == "*" {
+            any = Some(quality);
+        }
+    }
+
+    let gzip = gzip.or(any).unwrap_or(0.0);
+    let deflate = deflate.or(any).unwrap_or(0.0);
+    if gzip > 0.0 && gzip >= deflate {
+        Some(Encoding::Gzip)
+    } else if deflate > 0.0 {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,74 @@
+use std::fs::Metadata;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::http_date::parse_http_date;
+use crate::Request;
+
+/// Validators of a static file, compared against the conditional headers
+/// of a request.
+pub(crate) struct Validators {
+    pub etag: String,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// The entity tag is derived from the modification time and size, so it
+    /// changes whenever the file is rewritten without hashing its content.
+    pub fn of(metadata: &Metadata) -> Validators {
+        let last_modified = metadata.modified().ok();
+        let modified_nanos = last_modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since_epoch| since_epoch.as_nanos());
+        Validators {
+            etag: format!("\"{:x}-{:x}\"", modified_nanos, metadata.len()),
+            last_modified,
+        }
+    }
+
+    /// Whether a `GET` or `HEAD` can be answered with `304 Not Modified`.
+    /// `If-None-Match` takes precedence over `If-Modified-Since`.
+    pub fn not_modified(&self, request: &Request) -> bool {
+        if let Some(if_none_match) = request.header("If-None-Match") {
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_tag(tag) == weak_tag(&self.etag));
+        }
+
+        match (request.header("If-Modified-Since"), self.last_modified) {
+            (Some(since), Some(modified)) => match parse_http_date(since) {
+                Some(since) => whole_seconds(modified) <= whole_seconds(since),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a `Range` may be honoured. Without `If-Range` it always may;
+    /// with one, only if the client's copy is still current.
+    pub fn range_allowed(&self, request: &Request) -> bool {
+        let if_range = match request.header("If-Range") {
+            Some(if_range) => if_range.trim(),
+            None => return true,
+        };
+
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            // Ranges require a strong match, so a weak tag never matches.
+            return if_range == self.etag;
+        }
+        match (parse_http_date(if_range), self.last_modified) {
+            (Some(date), Some(modified)) => whole_seconds(date) == whole_seconds(modified),
+            _ => false,
+        }
+    }
+}
+
+fn weak_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+/// HTTP dates have a resolution of one second.
+fn whole_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
@@ -0,0 +1,269 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::request::RequestLimits;
+use crate::AccessLog;
+use crate::CompressionPolicy;
+use crate::ErrorPages;
+use crate::FileCache;
+use crate::IpPreference;
+use crate::MimeTypes;
+use crate::PoolConfig;
+use crate::RateLimit;
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+
+/// Settings for an HTTP server started with `run_server_with_config`.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Worker threads kept running at all times.
+    pub threads_count: usize,
+    /// Upper bound the pool may grow to while connections wait for a
+    /// worker; `None` keeps it at `threads_count`.
+    pub max_threads: Option<usize>,
+    /// How long a worker beyond `threads_count` may stay idle before it
+    /// exits.
+    pub thread_idle_timeout: Duration,
+    /// How many accepted connections may wait for a free worker. Beyond that
+    /// new connections are answered with `503` right away; `None` lets the
+    /// queue grow without bound.
+    pub max_queued_connections: Option<usize>,
+    /// WebSocket connections that may be open at once. Each has a thread of
+    /// its own, outside the pool; further handshakes are answered with `503`.
+    pub max_websockets: usize,
+    /// Per-client limits on connections and request rate; `None` leaves
+    /// clients unlimited.
+    pub rate_limit: Option<RateLimit>,
+    pub address: String,
+    /// More addresses to listen on, each with a listener of its own, e.g.
+    /// `[::]:8080` next to an `address` of `0.0.0.0:8080`.
+    pub additional_addresses: Vec<String>,
+    /// Which address family to try first when `address` resolves to both,
+    /// or `IpPreference::Both` to listen on all of them.
+    pub ip_preference: IpPreference,
+    /// Directory static files are served from. A relative path is resolved
+    /// against the working directory.
+    pub content_dir: PathBuf,
+    /// `Content-Type` of static files by extension.
+    pub mime_types: MimeTypes,
+    /// How long to wait for request data before dropping the connection.
+    /// `None` waits forever.
+    pub read_timeout: Option<Duration>,
+    /// How long receiving one whole request, head and body, may take. Unlike
+    /// `read_timeout` this is not reset by every byte that arrives, so it
+    /// bounds how long a slow client can occupy a worker. `None` disables it.
+    pub request_read_timeout: Option<Duration>,
+    /// How long a write to the client may block before the connection is
+    /// dropped. `None` waits forever.
+    pub write_timeout: Option<Duration>,
+    /// Upper bound for the request head plus body, in bytes.
+    pub max_request_size: usize,
+    /// Upper bound for the request body, in bytes. Larger bodies are answered
+    /// with `413`, announced ones before any of the body is read. Also bounds
+    /// WebSocket messages, which close the connection with 1009 when larger.
+    pub max_body_size: usize,
+    /// Upper bound for the request target, in bytes. Longer targets are
+    /// answered with `414`.
+    pub max_uri_length: usize,
+    /// Upper bound for the request line plus headers, in bytes. Larger heads
+    /// are answered with `431`.
+    pub max_header_bytes: usize,
+    /// Upper bound for the number of request headers, answered with `431`
+    /// when exceeded.
+    pub max_header_count: usize,
+    /// How long an idle persistent connection is kept open waiting for the
+    /// next request. Zero disables keep-alive.
+    pub keep_alive_timeout: Duration,
+    /// Requests served on one connection before it is closed.
+    pub max_requests_per_connection: usize,
+    /// Path of the opt-in request echo endpoint. It answers requests with a
+    /// JSON description of the parsed request; `None` disables it.
+    pub debug_echo_path: Option<String>,
+    /// Path of the health check endpoint, answering `200 ok` for as long as
+    /// the server accepts requests; `None` disables it.
+    pub health_path: Option<String>,
+    /// Path of the metrics endpoint, which reports request counts by status,
+    /// in-flight requests, bytes sent, latencies and the state of the thread
+    /// pool in the Prometheus text format, or as JSON for `?format=json`.
+    /// `None` disables it.
+    pub metrics_path: Option<String>,
+    /// Serves the health check and metrics endpoints on a listener of their
+    /// own at this address instead of next to the regular routes.
+    pub metrics_address: Option<String>,
+    /// `Strict-Transport-Security` policy. Only ever sent over TLS
+    /// connections, as required by RFC 6797.
+    pub hsts: Option<HstsPolicy>,
+    /// Upper bound for the status line plus all response headers. A response
+    /// exceeding it is replaced by a `500` before anything is sent.
+    pub max_response_header_bytes: usize,
+    /// Whether the default 404 page mentions the requested path. The path
+    /// is always HTML-escaped.
+    pub not_found_reflects_path: bool,
+    /// Pages replacing the built-in ones for error statuses. Templates are
+    /// looked up under `content_dir`.
+    pub error_pages: ErrorPages,
+    /// `Retry-After` seconds added to responses with the given status, unless
+    /// the response already has one. Meant for transient errors such as 503
+    /// and 504; a plain 500 is not retryable and gets none by default.
+    pub retry_after: Vec<(u16, u64)>,
+    /// `Cache-Control` values for static files as `(pattern, value)` pairs.
+    /// The first pattern matching the request path wins; `*` in a pattern
+    /// matches any run of characters, e.g. `/assets/*` or `*.css`.
+    pub cache_control: Vec<(String, String)>,
+    /// Keeps static files in memory between requests; `None` reads every
+    /// file from disk. Clones of the config share the cached files.
+    pub file_cache: Option<FileCache>,
+    /// Compression of responses for clients that accept it; `None` disables
+    /// it.
+    pub compression: Option<CompressionPolicy>,
+    /// Value of the `Server` header added to responses that do not set one;
+    /// `None` leaves it out.
+    pub server_header: Option<String>,
+    /// Where a line per answered request goes; `None` disables access
+    /// logging.
+    pub access_log: Option<AccessLog>,
+    /// Files served when a request names a directory, tried in order.
+    pub index_files: Vec<String>,
+    /// Whether a directory without an index file is answered with a
+    /// generated HTML listing instead of a 404.
+    pub directory_listing: bool,
+    /// Serves HTTPS instead of plain HTTP when set.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+    /// Address of an additional plain HTTP listener that redirects every
+    /// request to the HTTPS listener. Only used together with `tls`.
+    #[cfg(feature = "tls")]
+    pub http_redirect_address: Option<String>,
+}
+
+impl ServerConfig {
+    pub(crate) fn request_limits(&self) -> RequestLimits {
+        RequestLimits {
+            max_request_size: self.max_request_size,
+            max_body_size: self.max_body_size,
+            max_header_bytes: self.max_header_bytes,
+            max_header_count: self.max_header_count,
+            max_uri_length: self.max_uri_length,
+        }
+    }
+
+    pub(crate) fn pool_config(&self) -> PoolConfig {
+        PoolConfig {
+            min_threads: self.threads_count,
+            max_threads: self.max_threads.unwrap_or(self.threads_count),
+            idle_timeout: self.thread_idle_timeout,
+            queue_capacity: self.max_queued_connections,
+        }
+    }
+
+    pub fn retry_after_seconds(&self, status: u16) -> Option<u64> {
+        self.retry_after
+            .iter()
+            .find(|(configured, _)| *configured == status)
+            .map(|(_, seconds)| *seconds)
+    }
+
+    pub fn cache_control_for(&self, path: &str) -> Option<&str> {
+        self.cache_control
+            .iter()
+            .find(|(pattern, _)| matches_pattern(pattern, path))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Matches `text` against a pattern in which `*` stands for any run of
+/// characters, including none.
+pub(crate) fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut pieces = pattern.split('*');
+    let first = pieces.next().unwrap_or("");
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let pieces: Vec<&str> = pieces.collect();
+    let (last, middle) = match pieces.split_last() {
+        Some(split) => split,
+        // No `*` at all: the whole text has to match.
+        None => return rest.is_empty(),
+    };
+    for piece in middle {
+        match rest.find(piece) {
+            Some(index) => rest = &rest[index + piece.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HstsPolicy {
+    pub max_age: u64,
+    pub include_subdomains: bool,
+    pub preload: bool,
+}
+
+impl HstsPolicy {
+    pub fn header_value(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age);
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            threads_count: 20,
+            max_threads: None,
+            thread_idle_timeout: Duration::from_secs(60),
+            max_queued_connections: Some(1024),
+            max_websockets: 256,
+            rate_limit: None,
+            address: "127.0.0.1:7878".to_string(),
+            additional_addresses: Vec::new(),
+            ip_preference: IpPreference::default(),
+            content_dir: PathBuf::from("content"),
+            mime_types: MimeTypes::default(),
+            read_timeout: Some(Duration::from_secs(30)),
+            request_read_timeout: Some(Duration::from_secs(60)),
+            write_timeout: Some(Duration::from_secs(30)),
+            max_request_size: 1024 * 1024,
+            max_body_size: 1024 * 1024,
+            max_uri_length: 8 * 1024,
+            max_header_bytes: 16 * 1024,
+            max_header_count: 100,
+            keep_alive_timeout: Duration::from_secs(5),
+            max_requests_per_connection: 100,
+            debug_echo_path: None,
+            health_path: None,
+            metrics_path: None,
+            metrics_address: None,
+            hsts: None,
+            max_response_header_bytes: 64 * 1024,
+            not_found_reflects_path: true,
+            error_pages: ErrorPages::default(),
+            retry_after: vec![(503, 10), (504, 5)],
+            cache_control: Vec::new(),
+            file_cache: None,
+            compression: None,
+            server_header: Some(format!(
+                "{}/{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )),
+            access_log: None,
+            index_files: vec!["index.html".to_string()],
+            directory_listing: false,
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            http_redirect_address: None,
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::toml::{self, Entry, Table, Value};
+use crate::AccessLog;
+use crate::CompressionPolicy;
+use crate::FileCache;
+use crate::FileLogSink;
+use crate::LogFormat;
+use crate::RateLimit;
+use crate::Result;
+use crate::ServerConfig;
+use crate::StdoutLogSink;
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use crate::VirtualHost;
+use crate::WebServerError;
+
+/// Applies the configuration file at `path` to `config` and
+/// `virtual_hosts`, see `HttpServerBuilder::config_file`.
+pub(crate) fn apply(
+    path: &Path,
+    config: &mut ServerConfig,
+    virtual_hosts: &mut Vec<(String, VirtualHost)>,
+) -> Result<()> {
+    // Messages name the file, as in `server.toml, line 3: ...`.
+    let in_file = |error| match error {
+        WebServerError::Config(message) => {
+            WebServerError::Config(format!("{}, {}", path.display(), message))
+        }
+        error => error,
+    };
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| in_file(WebServerError::Config(error.to_string())))?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    for table in &toml::parse(&text).map_err(in_file)? {
+        apply_table(table, base_dir, config, virtual_hosts).map_err(in_file)?;
+    }
+    Ok(())
+}
+
+fn apply_table(
+    table: &Table,
+    base_dir: &Path,
+    config: &mut ServerConfig,
+    virtual_hosts: &mut Vec<(String, VirtualHost)>,
+) -> Result<()> {
+    match (table.name.as_deref(), table.is_array) {
+        (None, _) => apply_server(table, base_dir, config),
+        (Some("limits"), false) => apply_limits(table, config),
+        (Some("timeouts"), false) => apply_timeouts(table, config),
+        (Some("cache"), false) => apply_cache(table, config),
+        (Some("compression"), false) => apply_compression(table, config),
+        (Some("tls"), false) => apply_tls(table, base_dir, config),
+        (Some("mime_types"), false) => {
+            for entry in &table.entries {
+                config.mime_types.insert(&entry.key, string(entry)?);
+            }
+            Ok(())
+        }
+        (Some("cache_control"), false) => {
+            for entry in &table.entries {
+                let value = string(entry)?.to_string();
+                config.cache_control.push((entry.key.clone(), value));
+            }
+            Ok(())
+        }
+        (Some("error_pages"), false) => {
+            for entry in &table.entries {
+                let status = entry
+                    .key
+                    .parse()
+                    .ok()
+                    .filter(|status| (400..600).contains(status))
+                    .ok_or_else(|| {
+                        error(
+                            entry.line,
+                            &format!("`{}` is not an error status", entry.key),
+                        )
+                    })?;
+                let error_pages = std::mem::take(&mut config.error_pages);
+                config.error_pages = error_pages.template(status, string(entry)?);
+            }
+            Ok(())
+        }
+        (Some("vhost"), true) => {
+            let mut host = None;
+            let mut content_dir = None;
+            for entry in &table.entries {
+                match entry.key.as_str() {
+                    "host" => host = Some(string(entry)?.to_string()),
+                    "root" => content_dir = Some(path(entry, base_dir)?),
+                    _ => return Err(unknown_key(entry)),
+                }
+            }
+            let host_pattern =
+                host.ok_or_else(|| error(table.line, "`[[vhost]]` needs a `host`"))?;
+            let mut virtual_host = VirtualHost::new();
+            if let Some(content_dir) = content_dir {
+                virtual_host = virtual_host.content_dir(content_dir);
+            }
+            virtual_hosts.push((host_pattern, virtual_host));
+            Ok(())
+        }
+        (Some(name), true) => Err(error(table.line, &format!("unknown table `[[{}]]`", name))),
+        (Some(name), false) => Err(error(table.line, &format!("unknown table `[{}]`", name))),
+    }
+}
+
+fn apply_server(table: &Table, base_dir: &Path, config: &mut ServerConfig) -> Result<()> {
+    let mut log = None;
+    let mut log_format = LogFormat::default();
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "bind" => config.address = string(entry)?.to_string(),
+            "also_bind" => config.additional_addresses = strings(entry)?,
+            "threads" => config.threads_count = integer(entry)?,
+            "max_threads" => config.max_threads = Some(integer(entry)?),
+            "root" => config.content_dir = path(entry, base_dir)?,
+            "index_files" => config.index_files = strings(entry)?,
+            "directory_listing" => config.directory_listing = boolean(entry)?,
+            "server_header" => config.server_header = Some(string(entry)?.to_string()),
+            "health_check" => config.health_path = Some(string(entry)?.to_string()),
+            "metrics" => config.metrics_path = Some(string(entry)?.to_string()),
+            "metrics_address" => config.metrics_address = Some(string(entry)?.to_string()),
+            "log" => log = Some(entry),
+            "log_format" => {
+                log_format = match string(entry)? {
+                    "common" => LogFormat::Common,
+                    "combined" => LogFormat::Combined,
+                    _ => return Err(invalid(entry, "`common` or `combined`")),
+                }
+            }
+            _ => return Err(unknown_key(entry)),
+        }
+    }
+
+    if let Some(entry) = log {
+        config.access_log = Some(match string(entry)? {
+            "-" => AccessLog::new(StdoutLogSink::new(log_format)),
+            _ => AccessLog::new(FileLogSink::open(path(entry, base_dir)?, log_format)?),
+        });
+    }
+    Ok(())
+}
+
+fn apply_limits(table: &Table, config: &mut ServerConfig) -> Result<()> {
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "max_request_size" => config.max_request_size = integer(entry)?,
+            "max_body_size" => config.max_body_size = integer(entry)?,
+            "max_uri_length" => config.max_uri_length = integer(entry)?,
+            "max_header_bytes" => config.max_header_bytes = integer(entry)?,
+            "max_header_count" => config.max_header_count = integer(entry)?,
+            "max_requests_per_connection" => config.max_requests_per_connection = integer(entry)?,
+            "max_queued_connections" => config.max_queued_connections = Some(integer(entry)?),
+            "max_websockets" => config.max_websockets = integer(entry)?,
+            "max_connections_per_ip" => {
+                rate_limit(config).max_connections_per_ip = Some(integer(entry)?)
+            }
+            "requests_per_second" => rate_limit(config).requests_per_second = Some(number(entry)?),
+            "burst" => {
+                rate_limit(config).burst = integer(entry)?
+                    .try_into()
+                    .map_err(|_| invalid(entry, "a smaller integer"))?
+            }
+            _ => return Err(unknown_key(entry)),
+        }
+    }
+    Ok(())
+}
+
+fn rate_limit(config: &mut ServerConfig) -> &mut RateLimit {
+    config.rate_limit.get_or_insert_with(RateLimit::default)
+}
+
+fn apply_timeouts(table: &Table, config: &mut ServerConfig) -> Result<()> {
+    // `0` waits forever for the timeouts that are optional.
+    let optional = |timeout: Duration| Some(timeout).filter(|timeout| !timeout.is_zero());
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "read" => config.read_timeout = optional(seconds(entry)?),
+            "request" => config.request_read_timeout = optional(seconds(entry)?),
+            "write" => config.write_timeout = optional(seconds(entry)?),
+            "keep_alive" => config.keep_alive_timeout = seconds(entry)?,
+            "thread_idle" => config.thread_idle_timeout = seconds(entry)?,
+            _ => return Err(unknown_key(entry)),
+        }
+    }
+    Ok(())
+}
+
+fn apply_cache(table: &Table, config: &mut ServerConfig) -> Result<()> {
+    let mut max_bytes = None;
+    let mut max_file_bytes = None;
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "max_bytes" => max_bytes = Some(integer(entry)? as u64),
+            "max_file_bytes" => max_file_bytes = Some(integer(entry)? as u64),
+            _ => return Err(unknown_key(entry)),
+        }
+    }
+
+    let max_bytes = max_bytes.ok_or_else(|| error(table.line, "`[cache]` needs `max_bytes`"))?;
+    let mut cache = FileCache::new(max_bytes);
+    if let Some(max_file_bytes) = max_file_bytes {
+        cache = cache.max_file_size(max_file_bytes);
+    }
+    config.file_cache = Some(cache);
+    Ok(())
+}
+
+fn apply_compression(table: &Table, config: &mut ServerConfig) -> Result<()> {
+    let policy = config
+        .compression
+        .get_or_insert_with(CompressionPolicy::default);
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "level" => {
+                policy.level = Some(integer(entry)?)
+                    .filter(|level| *level <= 9)
+                    .ok_or_else(|| invalid(entry, "an integer from 0 to 9"))?
+                    as u32
+            }
+            "min_size" => policy.min_size = integer(entry)? as u64,
+            "max_size" => policy.max_size = integer(entry)? as u64,
+            "content_types" => policy.content_types = strings(entry)?,
+            _ => return Err(unknown_key(entry)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+fn apply_tls(table: &Table, base_dir: &Path, config: &mut ServerConfig) -> Result<()> {
+    let mut cert = None;
+    let mut key = None;
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "cert" => cert = Some(path(entry, base_dir)?),
+            "key" => key = Some(path(entry, base_dir)?),
+            "redirect_from" => config.http_redirect_address = Some(string(entry)?.to_string()),
+            _ => return Err(unknown_key(entry)),
+        }
+    }
+
+    match (cert, key) {
+        (Some(cert), Some(key)) => {
+            config.tls = Some(TlsConfig::from_pem_files(cert, key)?);
+            Ok(())
+        }
+        _ => Err(error(table.line, "`[tls]` needs a `cert` and a `key`")),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn apply_tls(table: &Table, _base_dir: &Path, _config: &mut ServerConfig) -> Result<()> {
+    Err(error(
+        table.line,
+        "`[tls]` needs the server to be built with the `tls` feature",
+    ))
+}
+
+fn error(line: usize, message: &str) -> WebServerError {
+    WebServerError::Config(format!("line {}: {}", line, message))
+}
+
+fn invalid(entry: &Entry, expected: &str) -> WebServerError {
+    error(entry.line, &format!("`{}` must be {}", entry.key, expected))
+}
+
+fn unknown_key(entry: &Entry) -> WebServerError {
+    error(entry.line, &format!("unknown key `{}`", entry.key))
+}
+
+fn mismatch(entry: &Entry, expected: &str) -> WebServerError {
+    error(
+        entry.line,
+        &format!(
+            "`{}` must be {}, not {}",
+            entry.key,
+            expected,
+            entry.value.type_name()
+        ),
+    )
+}
+
+fn string(entry: &Entry) -> Result<&str> {
+    match &entry.value {
+        Value::String(string) => Ok(string),
+        _ => Err(mismatch(entry, "a string")),
+    }
+}
+
+fn strings(entry: &Entry) -> Result<Vec<String>> {
+    match &entry.value {
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(string) => Ok(string.clone()),
+                _ => Err(mismatch(entry, "an array of strings")),
+            })
+            .collect(),
+        _ => Err(mismatch(entry, "an array of strings")),
+    }
+}
+
+fn integer(entry: &Entry) -> Result<usize> {
+    match entry.value {
+        Value::Integer(value) => value
+            .try_into()
+            .map_err(|_| invalid(entry, "a non-negative integer")),
+        _ => Err(mismatch(entry, "an integer")),
+    }
+}
+
+fn number(entry: &Entry) -> Result<f64> {
+    match entry.value {
+        Value::Integer(value) if value >= 0 => Ok(value as f64),
+        Value::Float(value) if value >= 0.0 && value.is_finite() => Ok(value),
+        Value::Integer(_) | Value::Float(_) => Err(invalid(entry, "a non-negative number")),
+        _ => Err(mismatch(entry, "a number")),
+    }
+}
+
+fn seconds(entry: &Entry) -> Result<Duration> {
+    number(entry).map(Duration::from_secs_f64)
+}
+
+fn boolean(entry: &Entry) -> Result<bool> {
+    match entry.value {
+        Value::Boolean(value) => Ok(value),
+        _ => Err(mismatch(entry, "a boolean")),
+    }
+}
+
+/// A path relative to the directory of the configuration file.
+fn path(entry: &Entry, base_dir: &Path) -> Result<PathBuf> {
+    Ok(base_dir.join(string(entry)?))
+}
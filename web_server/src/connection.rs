@@ -0,0 +1,142 @@
+use std::io::{BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+/// The socket of a connection, optionally inside a TLS session. Every read
+/// waits at most `read_timeout`, and fails once `deadline` has passed, so a
+/// client trickling one byte at a time cannot keep a read going forever.
+pub(crate) struct ConnectionStream {
+    stream: TcpStream,
+    #[cfg(feature = "tls")]
+    tls: Option<Box<rustls::ServerConnection>>,
+    read_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    /// What the socket is currently set to, to skip redundant system calls.
+    applied_timeout: Option<Duration>,
+}
+
+/// Wraps the socket. Requests are always read through it, so bytes that were
+/// buffered while reading the head are not lost when reading the body.
+pub(crate) type ConnectionReader = BufReader<ConnectionStream>;
+
+impl ConnectionStream {
+    pub fn new(stream: TcpStream) -> ConnectionStream {
+        ConnectionStream {
+            stream,
+            #[cfg(feature = "tls")]
+            tls: None,
+            read_timeout: None,
+            deadline: None,
+            applied_timeout: None,
+        }
+    }
+
+    /// Serves the connection over TLS. The handshake happens on the first
+    /// read, so it is bound by the same timeouts as reading a request.
+    #[cfg(feature = "tls")]
+    pub fn tls(stream: TcpStream, config: &crate::TlsConfig) -> Result<ConnectionStream> {
+        let session = rustls::ServerConnection::new(std::sync::Arc::clone(&config.server_config))
+            .map_err(|error| crate::WebServerError::Internal(error.to_string()))?;
+        let mut connection = ConnectionStream::new(stream);
+        connection.tls = Some(Box::new(session));
+        Ok(connection)
+    }
+
+    /// Wraps an accepted socket the way `config` asks for.
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+    pub fn accept(stream: TcpStream, config: &crate::ServerConfig) -> Result<ConnectionStream> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &config.tls {
+            return ConnectionStream::tls(stream, tls);
+        }
+        Ok(ConnectionStream::new(stream))
+    }
+
+    pub fn is_secure(&self) -> bool {
+        #[cfg(feature = "tls")]
+        {
+            self.tls.is_some()
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            false
+        }
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+}
+
+impl Read for ConnectionStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Deadline for reading the request passed",
+                    ));
+                }
+                Some(
+                    self.read_timeout
+                        .map_or(remaining, |timeout| timeout.min(remaining)),
+                )
+            }
+            None => self.read_timeout,
+        };
+        if timeout != self.applied_timeout {
+            self.stream.set_read_timeout(timeout)?;
+            self.applied_timeout = timeout;
+        }
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &mut self.tls {
+            return rustls::Stream::new(tls.as_mut(), &mut self.stream).read(buf);
+        }
+        self.stream.read(buf)
+    }
+}
+
+impl Write for ConnectionStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Encrypted data is sent right away rather than on `flush`, so
+        // writers do not have to remember flushing.
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &mut self.tls {
+            let written = tls.writer().write(buf)?;
+            while tls.wants_write() {
+                tls.write_tls(&mut self.stream)?;
+            }
+            return Ok(written);
+        }
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Drop for ConnectionStream {
+    fn drop(&mut self) {
+        // Tell the client the response is complete, so that it can tell a
+        // finished connection from a truncated one.
+        if let Some(tls) = &mut self.tls {
+            tls.send_close_notify();
+            while tls.wants_write() {
+                if tls.write_tls(&mut self.stream).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+
+use crate::Request;
+use crate::Response;
+
+/// Builds the response of the debug echo endpoint: a JSON object describing
+/// the request exactly as it was parsed.
+pub fn echo_response(request: &Request, peer: Option<SocketAddr>, request_id: u64) -> Response {
+    let query_params = request
+        .query()
+        .iter()
+        .map(|(name, value)| json_pair(name, value))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let headers = request
+        .headers()
+        .iter()
+        .map(|(name, value)| json_pair(name, value))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let peer = match peer {
+        Some(peer) => json_string(&peer.to_string()),
+        None => "null".to_string(),
+    };
+
+    let body = format!(
+        "{{\"method\":{},\"path\":{},\"query\":[{}],\"headers\":[{}],\"body\":{},\"peer\":{},\"request_id\":{}}}",
+        json_string(request.method.as_str()),
+        json_string(request.path()),
+        query_params,
+        headers,
+        json_string(&String::from_utf8_lossy(&request.body)),
+        peer,
+        request_id
+    );
+
+    Response::json(&body)
+}
+
+fn json_pair(name: &str, value: &str) -> String {
+    format!("[{},{}]", json_string(name), json_string(value))
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
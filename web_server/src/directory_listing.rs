@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use crate::html;
+use crate::http_date::format_http_date;
+use crate::percent_encoding::percent_encode_segment;
+use crate::Response;
+use crate::Result;
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+/// An HTML page listing `dir`, which is served at the already decoded
+/// `request_path`. Directories come first, then files, each sorted by name.
+pub(crate) fn listing_response(dir: &Path, request_path: &str) -> Result<Response> {
+    let mut entries = Vec::new();
+    for dir_entry in std::fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let name = match dir_entry.file_name().into_string() {
+            Ok(name) => name,
+            // Names that are not UTF-8 could not be requested anyway.
+            Err(_) => continue,
+        };
+        let metadata = match std::fs::metadata(dir_entry.path()) {
+            Ok(metadata) => metadata,
+            // A dangling symlink, for example.
+            Err(_) => continue,
+        };
+        entries.push(Entry {
+            name,
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    let title = format!("Index of {}", html::escape(request_path));
+
+    let mut rows = String::new();
+    if request_path != "/" {
+        rows.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in &entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            percent_encode_segment(&entry.name),
+            suffix,
+            html::escape(&entry.name),
+            suffix,
+            if entry.is_dir {
+                "-".to_string()
+            } else {
+                entry.size.to_string()
+            },
+            entry.modified.map(format_http_date).unwrap_or_default()
+        ));
+    }
+
+    let mut response = Response::new(200);
+    response.set_header("Content-Type", "text/html; charset=utf-8");
+    response.set_body(
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head><title>{}</title></head>\n<body>\n<h1>{}</h1>\n\
+             <table>\n<tr><th>Name</th><th>Size</th><th>Last modified</th></tr>\n{}</table>\n\
+             </body>\n</html>\n",
+            title, title, rows
+        )
+        .into_bytes(),
+    );
+    Ok(response)
+}
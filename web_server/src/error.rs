@@ -0,0 +1,111 @@
+use std::fmt::Display;
+
+/// Everything that can go wrong while serving. Errors caused by the client
+/// map to a 4xx status with `status_code`, so a malformed request is
+/// answered instead of the connection just being dropped.
+#[derive(Debug)]
+pub enum WebServerError {
+    /// The request is malformed: a broken request line, header or body.
+    BadRequest(String),
+    /// The request asks for something it may never have, such as a path
+    /// above the root.
+    Forbidden(String),
+    NotFound(String),
+    /// The client sent too little within the read timeout.
+    Timeout(String),
+    /// The request body exceeds the configured limit.
+    PayloadTooLarge(String),
+    /// The request target exceeds the configured limit.
+    UriTooLong(String),
+    /// The request head exceeds the configured limits.
+    HeaderFieldsTooLarge(String),
+    /// The request method is not one the server knows.
+    NotImplemented(String),
+    VersionNotSupported(String),
+    /// Invalid server settings, e.g. a pool without threads.
+    Config(String),
+    Io(std::io::Error),
+    /// A failure inside the server, such as a poisoned lock.
+    Internal(String),
+}
+
+pub(crate) type Result<T> = std::result::Result<T, WebServerError>;
+
+impl WebServerError {
+    /// The status a response caused by this error should have.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            WebServerError::BadRequest(_) => 400,
+            WebServerError::Forbidden(_) => 403,
+            WebServerError::NotFound(_) => 404,
+            WebServerError::Timeout(_) => 408,
+            WebServerError::PayloadTooLarge(_) => 413,
+            WebServerError::UriTooLong(_) => 414,
+            WebServerError::HeaderFieldsTooLarge(_) => 431,
+            WebServerError::NotImplemented(_) => 501,
+            WebServerError::VersionNotSupported(_) => 505,
+            WebServerError::Config(_) | WebServerError::Io(_) | WebServerError::Internal(_) => 500,
+        }
+    }
+}
+
+impl Display for WebServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebServerError::BadRequest(message) => write!(f, "Bad request: {}", message),
+            WebServerError::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            WebServerError::NotFound(message) => write!(f, "Not found: {}", message),
+            WebServerError::Timeout(message) => write!(f, "Timed out: {}", message),
+            WebServerError::PayloadTooLarge(message)
+            | WebServerError::UriTooLong(message)
+            | WebServerError::HeaderFieldsTooLarge(message) => {
+                write!(f, "Request too large: {}", message)
+            }
+            WebServerError::NotImplemented(message) => write!(f, "Not implemented: {}", message),
+            WebServerError::VersionNotSupported(message) => {
+                write!(f, "Unsupported HTTP version: {}", message)
+            }
+            WebServerError::Config(message) => write!(f, "Invalid configuration: {}", message),
+            WebServerError::Io(error) => write!(f, "I/O error: {}", error),
+            WebServerError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for WebServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WebServerError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WebServerError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                WebServerError::Timeout(error.to_string())
+            }
+            _ => WebServerError::Io(error),
+        }
+    }
+}
+
+/// Turns any other error into `WebServerError::Internal`. I/O errors convert
+/// with `?` instead, which keeps their kind.
+pub trait ConvertibleToResult<T> {
+    fn to_web_server_result(self) -> Result<T>;
+}
+
+impl<T, SomeError> ConvertibleToResult<T> for std::result::Result<T, SomeError>
+where
+    SomeError: Display,
+{
+    fn to_web_server_result(self) -> Result<T> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(an_error) => Err(WebServerError::Internal(an_error.to_string())),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error_page;
+use crate::html;
+use crate::html_error_code_to_str;
+use crate::Request;
+use crate::Response;
+
+/// Builds the page for an error status, given the request that caused it.
+pub type ErrorHandler = Arc<dyn Fn(&Request, u16) -> Response + Send + Sync + 'static>;
+
+#[derive(Clone)]
+enum ErrorPage {
+    /// A file under the content directory.
+    Template(PathBuf),
+    Handler(ErrorHandler),
+}
+
+/// Pages sent in place of the built-in ones for error statuses.
+///
+/// They replace the pages the server generates itself, such as its 404 and
+/// 405 pages, and any error response without body and `Content-Type`, such
+/// as a handler returning `Response::new(403)`. Error responses with a body
+/// of their own are left alone. Without a registered page, an error response
+/// without body gets a short default HTML page.
+#[derive(Clone, Default)]
+pub struct ErrorPages {
+    pages: HashMap<u16, ErrorPage>,
+}
+
+impl ErrorPages {
+    pub fn new() -> ErrorPages {
+        ErrorPages::default()
+    }
+
+    /// Serves the file at `path`, relative to the content directory, for
+    /// `status`, e.g. `errors/404.html`. `{{status}}`, `{{reason}}` and
+    /// `{{path}}` in the file are replaced by the status code, its reason
+    /// phrase and the HTML-escaped request path.
+    pub fn template(mut self, status: u16, path: impl Into<PathBuf>) -> ErrorPages {
+        self.pages.insert(status, ErrorPage::Template(path.into()));
+        self
+    }
+
+    /// Builds the page for `status` with `handler`. Headers of the original
+    /// response that the page does not set itself, such as `Allow` or
+    /// `Retry-After`, are kept.
+    pub fn handler<F>(mut self, status: u16, handler: F) -> ErrorPages
+    where
+        F: Fn(&Request, u16) -> Response + Send + Sync + 'static,
+    {
+        self.pages
+            .insert(status, ErrorPage::Handler(Arc::new(handler)));
+        self
+    }
+
+    /// Puts the page registered for the status of `response` in place, or the
+    /// default page if `response` has no body.
+    pub(crate) fn apply(&self, request: &Request, content_dir: &Path, response: &mut Response) {
+        let status = response.status();
+        // An empty body with a type, like that of a 416 for a static file,
+        // is deliberate.
+        let bare = response.has_empty_body() && response.header("Content-Type").is_none();
+        if status < 400 || !(response.is_default_error_page() || bare) {
+            return;
+        }
+
+        let page = match self.pages.get(&status) {
+            Some(ErrorPage::Template(path)) => {
+                render_template(&content_dir.join(path), request, status)
+            }
+            Some(ErrorPage::Handler(handler)) => Some(handler(request, status)),
+            None => None,
+        };
+        let page = match page {
+            Some(page) => page,
+            None if bare => default_page(status),
+            None => return,
+        };
+
+        let mut page = page;
+        page.set_status(status);
+        for (name, value) in response.headers().iter() {
+            let describes_body = [
+                "Content-Type",
+                "Content-Length",
+                "Content-Encoding",
+                "Transfer-Encoding",
+            ]
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name));
+            if !describes_body && page.header(name).is_none() {
+                page.append_header(name, value);
+            }
+        }
+        *response = page;
+    }
+}
+
+impl std::fmt::Debug for ErrorPages {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut statuses: Vec<_> = self.pages.keys().collect();
+        statuses.sort();
+        f.debug_struct("ErrorPages")
+            .field("statuses", &statuses)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A missing or unreadable template falls back to the default page, so an
+/// error page can never turn into another error.
+fn render_template(path: &Path, request: &Request, status: u16) -> Option<Response> {
+    let template = match std::fs::read_to_string(path) {
+        Ok(template) => template,
+        Err(error) => {
+            println!("Could not read error page {}: {}", path.display(), error);
+            return None;
+        }
+    };
+    let page = template
+        .replace("{{status}}", &status.to_string())
+        .replace("{{reason}}", &reason(status))
+        .replace("{{path}}", &html::escape(request.path()));
+
+    let mut response = Response::new(status);
+    response.set_header("Content-Type", "text/html; charset=utf-8");
+    response.set_body(page.into_bytes());
+    Some(response)
+}
+
+fn default_page(status: u16) -> Response {
+    let reason = reason(status);
+    let message = if status >= 500 {
+        "The server could not complete the request"
+    } else {
+        "The request could not be completed"
+    };
+    error_page(status, &reason, message)
+}
+
+/// The reason phrase of `status` in title case, e.g. `Method Not Allowed`.
+fn reason(status: u16) -> String {
+    let phrase = html_error_code_to_str(status.into()).unwrap_or("ERROR");
+    phrase
+        .split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_string() + &chars.as_str().to_ascii_lowercase(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Keeps the content of static files in memory, so hot files are not read
+/// from disk on every request.
+///
+/// Entries are keyed by canonical path and remember the modification time
+/// and size of the file they were read from. A file whose metadata no longer
+/// matches is read again; the `ETag` is derived from the same metadata, so
+/// it always describes the cached bytes. When the cache is full, the least
+/// recently used files make room. Clones share their entries and counters.
+#[derive(Clone)]
+pub struct FileCache {
+    max_bytes: u64,
+    max_file_bytes: u64,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Entries {
+    by_path: HashMap<PathBuf, Entry>,
+    bytes: u64,
+    /// Bumped on every use; entries remember when they were last used.
+    clock: u64,
+}
+
+struct Entry {
+    content: Arc<[u8]>,
+    modified: SystemTime,
+    len: u64,
+    last_used: u64,
+}
+
+impl FileCache {
+    /// A cache holding up to `max_bytes` of file content in total.
+    pub fn new(max_bytes: u64) -> FileCache {
+        FileCache {
+            max_bytes,
+            max_file_bytes: max_bytes,
+            shared: Arc::new(Shared {
+                entries: Mutex::new(Entries {
+                    by_path: HashMap::new(),
+                    bytes: 0,
+                    clock: 0,
+                }),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Files larger than `max_file_bytes` are streamed from disk instead, so
+    /// one big file cannot push out all the small ones. Defaults to the whole
+    /// budget.
+    pub fn max_file_size(mut self, max_file_bytes: u64) -> FileCache {
+        self.max_file_bytes = max_file_bytes.min(self.max_bytes);
+        self
+    }
+
+    /// Requests answered from memory.
+    pub fn hits(&self) -> u64 {
+        self.shared.hits.load(Ordering::SeqCst)
+    }
+
+    /// Requests for a cacheable file that had to read it from disk, because
+    /// it was not cached yet or had changed since.
+    pub fn misses(&self) -> u64 {
+        self.shared.misses.load(Ordering::SeqCst)
+    }
+
+    /// Bytes of file content held right now.
+    pub fn size(&self) -> u64 {
+        self.shared.entries.lock().unwrap().bytes
+    }
+
+    /// Files held right now.
+    pub fn len(&self) -> usize {
+        self.shared.entries.lock().unwrap().by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The content of the file at canonical `path`, whose current metadata
+    /// is `metadata`, or `None` if it is not worth caching and should be
+    /// streamed instead.
+    pub(crate) fn read(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        let len = metadata.len();
+        // Without a modification time a change could not be noticed.
+        let modified = match metadata.modified() {
+            Ok(modified) if len <= self.max_file_bytes => modified,
+            _ => return Ok(None),
+        };
+
+        {
+            let mut entries = self.shared.entries.lock().unwrap();
+            entries.clock += 1;
+            let clock = entries.clock;
+            if let Some(entry) = entries.by_path.get_mut(path) {
+                if entry.modified == modified && entry.len == len {
+                    entry.last_used = clock;
+                    self.shared.hits.fetch_add(1, Ordering::SeqCst);
+                    return Ok(Some(entry.content.to_vec()));
+                }
+            }
+        }
+
+        // Read without holding the lock, so a slow disk only holds up the
+        // requests for this file.
+        self.shared.misses.fetch_add(1, Ordering::SeqCst);
+        let content = std::fs::read(path)?;
+        if content.len() as u64 == len {
+            self.insert(path, &content, modified);
+        }
+        // Otherwise the file changed while it was read; it is served as read
+        // and cached on a later request, once it is stable.
+        Ok(Some(content))
+    }
+
+    fn insert(&self, path: &Path, content: &[u8], modified: SystemTime) {
+        let len = content.len() as u64;
+        let mut entries = self.shared.entries.lock().unwrap();
+        if let Some(stale) = entries.by_path.remove(path) {
+            entries.bytes -= stale.len;
+        }
+
+        while entries.bytes + len > self.max_bytes {
+            // A linear scan, which is cheap next to the disk read that made
+            // the eviction necessary.
+            let oldest = entries
+                .by_path
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+            match oldest.and_then(|oldest| entries.by_path.remove(&oldest)) {
+                Some(evicted) => entries.bytes -= evicted.len,
+                None => return,
+            }
+        }
+
+        entries.clock += 1;
+        let last_used = entries.clock;
+        entries.bytes += len;
+        entries.by_path.insert(
+            path.to_path_buf(),
+            Entry {
+                content: content.into(),
+                modified,
+                len,
+                last_used,
+            },
+        );
+    }
+}
+
+impl std::fmt::Debug for FileCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileCache")
+            .field("max_bytes", &self.max_bytes)
+            .field("max_file_bytes", &self.max_file_bytes)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::connection::{ConnectionReader, ConnectionStream};
+use crate::request::{read_request_head, RequestLimits};
+use crate::Response;
+use crate::Result;
+
+const DEFAULT_HTTPS_PORT: u16 = 443;
+
+/// Redirects are tiny, so a client gets little time to ask for one.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn handle_connection(stream: TcpStream, https_port: u16) -> Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut connection = ConnectionStream::new(stream);
+    connection.set_read_timeout(Some(REQUEST_TIMEOUT));
+    connection.set_deadline(Some(Instant::now() + REQUEST_TIMEOUT));
+    let mut reader = ConnectionReader::new(connection);
+
+    let limits = RequestLimits::default();
+    let head = read_request_head(
+        &mut reader.by_ref().take(limits.max_header_bytes as u64),
+        &limits,
+    )?;
+
+    let response = match head.headers.get("Host") {
+        Some(host) => {
+            let mut response = Response::new(301);
+            response.set_header("Location", &https_url(host, https_port, &head.target));
+            response
+        }
+        None => {
+            // Without a Host header there is no URL to redirect to, so just
+            // tell the client that it has to switch to TLS.
+            let mut response = Response::new(426);
+            response.set_header("Upgrade", "TLS/1.2, HTTP/1.1");
+            response.set_header("Connection", "Upgrade");
+            response
+        }
+    };
+
+    reader.get_mut().write_all(&response.to_bytes()?)?;
+    Ok(())
+}
+
+fn https_url(host: &str, https_port: u16, target: &str) -> String {
+    let host = strip_port(host);
+    if https_port == DEFAULT_HTTPS_PORT {
+        format!("https://{}{}", host, target)
+    } else {
+        format!("https://{}:{}{}", host, https_port, target)
+    }
+}
+
+pub(crate) fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        // IPv6 literal: "[::1]:8080"
+        match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        }
+    } else {
+        host.split_once(':').map_or(host, |(name, _)| name)
+    }
+}
@@ -1,254 +1,1491 @@
 use std::{
-    fmt::Display,
-    io::{BufRead, BufReader, Write as IO_Write},
-    net::{TcpListener, TcpStream},
-    sync::{atomic::AtomicBool, Mutex},
+    io::{BufRead, Write as IO_Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        mpsc, Condvar, Mutex,
+    },
     thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
 };
 
+mod access_log;
+mod builder;
+mod chunked;
+mod compression;
+mod conditional;
+mod config;
+mod config_file;
+mod connection;
+mod debug_echo;
+mod directory_listing;
+mod error;
+mod error_pages;
+mod file_cache;
+mod headers;
+mod html;
+mod http_date;
+mod https_redirect;
+mod listener;
+mod metrics;
+mod mime;
+mod percent_encoding;
+mod priority;
+mod proxy;
+mod query;
+mod range;
+mod rate_limit;
+mod reload;
+mod request;
+mod response;
+mod router;
+mod sha1;
+mod static_path;
+mod target;
 mod thread_pool;
+#[cfg(feature = "tls")]
+mod tls;
+mod toml;
+mod vhost;
+mod websocket;
+pub use access_log::{
+    AccessLog, AccessLogEntry, FileLogSink, LogFormat, LogSink, RequestTiming, StdoutLogSink,
+};
+pub use builder::HttpServerBuilder;
+pub use compression::CompressionPolicy;
+use conditional::Validators;
+pub use config::{HstsPolicy, ServerConfig};
+use connection::{ConnectionReader, ConnectionStream};
+use error::Result;
+pub use error::{ConvertibleToResult, WebServerError};
+pub use error_pages::{ErrorHandler, ErrorPages};
+pub use file_cache::FileCache;
+pub use headers::Headers;
+pub use http_date::{format_http_date, parse_http_date};
+use listener::bind_listeners;
+pub use listener::{bind_listener, IpPreference};
+use metrics::{CountingWriter, ServerStats};
+pub use mime::{MimeTypes, DEFAULT_MIME_TYPE};
+pub use proxy::ProxyHandler;
+pub use query::QueryParams;
+pub use range::{parse_range, RangeRequest};
+pub use rate_limit::RateLimit;
+use rate_limit::{ConnectionPermit, RateLimiter};
+use reload::{LiveSettings, Settings};
+use request::read_request;
+pub use request::{Method, Request, Version};
+pub use response::{canonical_header_name, HeaderCasing, Response};
+use router::RouteMatch;
+pub use router::{Handler, Middleware, Router};
+use static_path::resolve_decoded_path;
+pub use static_path::{resolve_static_path, PathResolution};
 use std::sync::Arc;
-use thread_pool::ThreadPool;
+pub use target::{normalize_path, RequestTarget};
+pub use thread_pool::{PoolConfig, PoolMonitor, Priority, ThreadPool};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use vhost::VirtualHost;
+use websocket::{Upgraded, WebSocketSlot};
+pub use websocket::{WebSocket, WebSocketMessage};
 
-#[derive(Debug)]
-pub struct WebServerError(String);
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
 
-type Result<T> = std::result::Result<T, WebServerError>;
+pub struct HttpServer {
+    state: Arc<ServerState>,
+    stats: Arc<ServerStats>,
+    pool: PoolMonitor,
+    /// One per listener, in the order the addresses were configured.
+    local_addrs: Vec<SocketAddr>,
+    thread: Option<JoinHandle<Result<()>>>,
+    /// Servers started along with this one and stopped together with it:
+    /// the plain HTTP listener redirecting to it and the metrics listener.
+    companion_servers: Vec<Arc<Mutex<HttpServer>>>,
+    /// `None` for servers whose settings cannot be reloaded, like the
+    /// redirect listener.
+    settings: Option<Arc<LiveSettings>>,
+}
 
-pub trait ConvertibleToResult<T> {
-    fn to_web_server_result(self) -> Result<T>;
+/// Shared between the server handle and its accept thread.
+struct ServerState {
+    stop_requested: AtomicBool,
+    /// Set once the accept loop has exited and in-flight requests are done.
+    stopped: Mutex<bool>,
+    stopped_changed: Condvar,
+}
+
+impl ServerState {
+    /// Sets the stop flag and wakes every accept loop so that it sees it.
+    /// The loops are blocked until the next connection arrives, so each
+    /// listener gets connected to once.
+    fn request_stop(&self, local_addrs: &[SocketAddr]) -> Result<()> {
+        self.stop_requested
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+
+        for local_addr in local_addrs {
+            let mut wake_addr = *local_addr;
+            if wake_addr.ip().is_unspecified() {
+                wake_addr.set_ip(match wake_addr {
+                    SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                    SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                });
+            }
+            match TcpStream::connect(wake_addr) {
+                Ok(_) => {}
+                // The listener may have stopped on its own already.
+                Err(error) if error.kind() == std::io::ErrorKind::ConnectionRefused => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn set_stopped(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.stopped_changed.notify_all();
+    }
+
+    fn wait_stopped(&self) -> Result<()> {
+        let mut stopped = self.stopped.lock().to_web_server_result()?;
+        while !*stopped {
+            stopped = self.stopped_changed.wait(stopped).to_web_server_result()?;
+        }
+        Ok(())
+    }
 }
 
-enum HttpRequest {
-    GET(HttpGetRequest),
+impl HttpServer {
+    pub fn builder() -> HttpServerBuilder {
+        HttpServerBuilder::new()
+    }
+
+    /// Number of requests whose handling panicked. Each of them was answered
+    /// with a `500` and the worker went on serving.
+    pub fn panic_count(&self) -> u64 {
+        self.stats
+            .handler_panics
+            .load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Number of worker threads running right now.
+    pub fn pool_size(&self) -> usize {
+        self.pool.size()
+    }
+
+    /// Number of accepted connections waiting for a worker.
+    pub fn queued_connections(&self) -> usize {
+        self.pool.queued_jobs()
+    }
+
+    /// Number of connections and requests answered with a `429` because the
+    /// client exceeded its `RateLimit`.
+    pub fn rate_limited_count(&self) -> u64 {
+        self.stats
+            .rate_limited
+            .load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Number of connections turned away with a `503` because the queue of
+    /// waiting connections was full.
+    pub fn rejected_count(&self) -> u64 {
+        self.stats
+            .rejected_connections
+            .load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Address of the first listener. For a configured port of `0` this
+    /// has the port the system picked.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs[0]
+    }
+
+    /// Addresses of all listeners, in the order they were configured.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Addresses of the servers started along with this one: the plain
+    /// HTTP redirect listener, if any, followed by the metrics listener.
+    pub fn companion_addrs(&self) -> Vec<SocketAddr> {
+        self.companion_servers
+            .iter()
+            .filter_map(|companion| Some(companion.lock().ok()?.local_addr()))
+            .collect()
+    }
+
+    /// Answers `request` the way one read from a connection would be, with
+    /// the middlewares, routes and static files of the current settings, but
+    /// without any network I/O. The response is returned as it would be
+    /// written, except for the `Connection` header. Rate limits do not
+    /// apply, as the request has no client address.
+    pub fn handle(&self, mut request: Request) -> Result<Response> {
+        let active = self.live_settings()?.load();
+        let secure = request.is_secure();
+        Ok(respond(
+            &mut request,
+            &active.config,
+            &active.router,
+            &self.stats,
+            None,
+            secure,
+        ))
+    }
+
+    /// Serves requests with `config` from now on, keeping the router. Requests
+    /// in flight finish with the settings they started with, and connections
+    /// stay open, so the content directory, error pages or headers can change
+    /// without dropping anyone.
+    ///
+    /// What shapes the listeners and the thread pool stays as the server was
+    /// started: addresses, thread counts, the queue length, the rate limit and
+    /// the metrics and redirect listeners. So do the health check and metrics
+    /// paths, which are part of the router.
+    pub fn reload(&self, config: ServerConfig) -> Result<()> {
+        let settings = self.live_settings()?;
+        let router = Arc::clone(&settings.load().router);
+        self.stats.set_file_cache(config.file_cache.clone());
+        settings.store(Settings { config, router });
+        Ok(())
+    }
+
+    /// Like `reload`, but also replaces the router, e.g. to change the
+    /// virtual hosts. The health check and metrics routes of `config` are
+    /// mounted on it the same way as at start.
+    pub fn reload_with_router(&self, config: ServerConfig, mut router: Router) -> Result<()> {
+        let settings = self.live_settings()?;
+        if settings.monitoring_routes {
+            add_monitoring_routes(&mut router, &config, &self.stats);
+        }
+        self.stats.set_file_cache(config.file_cache.clone());
+        settings.store(Settings {
+            config,
+            router: Arc::new(router),
+        });
+        Ok(())
+    }
+
+    fn live_settings(&self) -> Result<&LiveSettings> {
+        self.settings.as_deref().ok_or_else(|| {
+            WebServerError::Config("This server has no settings of its own.".to_string())
+        })
+    }
+
+    /// Stops accepting connections. Requests already accepted are still
+    /// served; `join_server` returns once they are done.
+    pub fn shutdown(&self) -> Result<()> {
+        self.state.request_stop(&self.local_addrs)?;
+
+        for companion in &self.companion_servers {
+            companion.lock().to_web_server_result()?.shutdown()?;
+        }
+        Ok(())
+    }
 }
 
-struct HttpGetRequest {
-    path: String,
+/// Shuts the server down on SIGINT or SIGTERM. Can only be installed once per
+/// process.
+#[cfg(feature = "signals")]
+pub fn shutdown_on_signal(server: Arc<Mutex<HttpServer>>) -> Result<()> {
+    ctrlc::set_handler(move || {
+        println!("Received termination signal, shutting down");
+        let result = server
+            .lock()
+            .to_web_server_result()
+            .and_then(|server| server.shutdown());
+        if let Err(error) = result {
+            println!("Failed to shut down: {}", error);
+        }
+    })
+    .to_web_server_result()
 }
 
-pub struct HttpServer {
-    started: AtomicBool,
-    stopped: AtomicBool,
-    thread: Option<JoinHandle<Result<()>>>,
+/// Reloads the server with the config `load` returns on every SIGHUP, e.g.
+/// after reading it from a file again. If `load` fails, the server keeps its
+/// settings. Can only be installed once per process, and has to come after
+/// `shutdown_on_signal`, which otherwise claims SIGHUP for stopping.
+#[cfg(all(unix, feature = "signals"))]
+pub fn reload_on_sighup<F>(server: Arc<Mutex<HttpServer>>, load: F) -> Result<()>
+where
+    F: Fn() -> Result<ServerConfig> + Send + 'static,
+{
+    let mut notified = reload::sighup::install()?;
+    std::thread::spawn(move || {
+        let mut signal = [0; 1];
+        while let Ok(1) = std::io::Read::read(&mut notified, &mut signal) {
+            println!("Received SIGHUP, reloading configuration");
+            let result = load().and_then(|config| {
+                server
+                    .lock()
+                    .to_web_server_result()
+                    .and_then(|server| server.reload(config))
+            });
+            if let Err(error) = result {
+                println!("Failed to reload configuration: {}", error);
+            }
+        }
+    });
+    Ok(())
 }
 
+/// Blocks until the server has stopped. The server lock is not held while
+/// waiting, so other threads can still call `shutdown`.
 pub fn join_server(server: Arc<Mutex<HttpServer>>) -> Result<()> {
-    // Wait until server start
-    loop {
-        let server = server.lock().to_web_server_result()?;
-        if server.stopped.load(std::sync::atomic::Ordering::Relaxed)
-        {
-            break;
-        }
+    let state = Arc::clone(&server.lock().to_web_server_result()?.state);
+    state.wait_stopped()?;
+
+    // Only the first caller gets the accept thread's result.
+    let handle = server.lock().to_web_server_result()?.thread.take();
+    if let Some(handle) = handle {
+        handle
+            .join()
+            .map_err(|_| WebServerError::Internal("Server thread panicked".to_string()))??;
     }
 
-    {
-        let mut server = server.lock().to_web_server_result()?;
-        if let Some(handle) = server.thread.take() {
-            handle.join().unwrap()?;
-        }
+    let companions = server
+        .lock()
+        .to_web_server_result()?
+        .companion_servers
+        .clone();
+    for companion in companions {
+        join_server(companion)?;
     }
 
     Ok(())
 }
 
 pub fn run_server(threads_count: usize, address: String) -> Result<Arc<Mutex<HttpServer>>> {
+    HttpServer::builder()
+        .threads(threads_count)
+        .bind(address)
+        .start()
+}
+
+/// Like `run_server`, but listens on every address `addresses` resolve to,
+/// e.g. `run_server_on(4, &["0.0.0.0:8080", "[::]:8080"])`.
+pub fn run_server_on<A: ToSocketAddrs>(
+    threads_count: usize,
+    addresses: &[A],
+) -> Result<Arc<Mutex<HttpServer>>> {
+    let mut resolved = Vec::new();
+    for address in addresses {
+        resolved.extend(
+            address
+                .to_socket_addrs()?
+                .map(|address| address.to_string()),
+        );
+    }
+    if resolved.is_empty() {
+        return Err(WebServerError::Config(
+            "No address to listen on.".to_string(),
+        ));
+    }
+
+    let address = resolved.remove(0);
+    run_server_with_config(ServerConfig {
+        threads_count,
+        address,
+        additional_addresses: resolved,
+        ..ServerConfig::default()
+    })
+}
+
+pub fn run_server_with_config(config: ServerConfig) -> Result<Arc<Mutex<HttpServer>>> {
+    run_server_with_router(config, Router::new())
+}
+
+/// Starts a server that dispatches requests through `router`. Requests that
+/// match no route are served from the content directory.
+pub fn run_server_with_router(
+    config: ServerConfig,
+    mut router: Router,
+) -> Result<Arc<Mutex<HttpServer>>> {
+    let stats = Arc::new(ServerStats::default());
+    stats.set_file_cache(config.file_cache.clone());
+    let monitoring_routes = config.metrics_address.is_none();
+    if monitoring_routes {
+        add_monitoring_routes(&mut router, &config, &stats);
+    }
+    let rate_limiter = config
+        .rate_limit
+        .clone()
+        .map(|limit| Arc::new(RateLimiter::new(limit)));
+    let settings = Arc::new(LiveSettings::new(
+        Settings {
+            config: config.clone(),
+            router: Arc::new(router),
+        },
+        monitoring_routes,
+    ));
+    let connection_settings = Arc::clone(&settings);
+    let connection_stats = Arc::clone(&stats);
+    let connection_limiter = rate_limiter.clone();
+    let priority_settings = Arc::clone(&settings);
+    let prioritize: Prioritizer =
+        Box::new(move |stream| priority::connection_priority(stream, &priority_settings));
+    let server = spawn_server(
+        &config,
+        Arc::clone(&stats),
+        rate_limiter,
+        Some(prioritize),
+        move |stream, permit| {
+            handle_connection(
+                stream,
+                &connection_settings,
+                &connection_stats,
+                connection_limiter.as_deref(),
+                permit,
+            )
+        },
+    )?;
+    server.lock().to_web_server_result()?.settings = Some(settings);
+
+    #[cfg(feature = "tls")]
+    if let (Some(_), Some(address)) = (&config.tls, &config.http_redirect_address) {
+        let mut https_server = server.lock().to_web_server_result()?;
+        let redirect_server = run_https_redirect_server(
+            config.threads_count,
+            address.clone(),
+            https_server.local_addrs[0].port(),
+        )?;
+        https_server.companion_servers.push(redirect_server);
+    }
+
+    if let Some(address) = &config.metrics_address {
+        let metrics_server = run_metrics_server(address.clone(), &config, &stats)?;
+        server
+            .lock()
+            .to_web_server_result()?
+            .companion_servers
+            .push(metrics_server);
+    }
+
+    Ok(server)
+}
+
+/// Mounts the health check and metrics endpoints `config` asks for, both
+/// reporting on the server `stats` belong to.
+fn add_monitoring_routes(router: &mut Router, config: &ServerConfig, stats: &Arc<ServerStats>) {
+    if let Some(path) = &config.health_path {
+        router.get(path, |_| metrics::health_response());
+    }
+    if let Some(path) = &config.metrics_path {
+        let stats = Arc::clone(stats);
+        router.get(path, move |request| {
+            metrics::metrics_response(&stats, request)
+        });
+    }
+}
+
+/// Starts a listener on `address` that serves only the health check and
+/// metrics of the server `stats` belong to, keeping them off its public
+/// port.
+fn run_metrics_server(
+    address: String,
+    config: &ServerConfig,
+    stats: &Arc<ServerStats>,
+) -> Result<Arc<Mutex<HttpServer>>> {
+    let metrics_config = ServerConfig {
+        threads_count: 1,
+        address,
+        health_path: config.health_path.clone(),
+        metrics_path: config.metrics_path.clone(),
+        ..ServerConfig::default()
+    };
+    let mut router = Router::new();
+    add_monitoring_routes(&mut router, &metrics_config, stats);
+    // Nothing else is served, least of all the content directory.
+    router.fallback(|_| Response::not_found());
+
+    let metrics_stats = Arc::new(ServerStats::default());
+    let connection_stats = Arc::clone(&metrics_stats);
+    let settings = LiveSettings::new(
+        Settings {
+            config: metrics_config.clone(),
+            router: Arc::new(router),
+        },
+        true,
+    );
+    spawn_server(
+        &metrics_config,
+        metrics_stats,
+        None,
+        None,
+        move |stream, permit| handle_connection(stream, &settings, &connection_stats, None, permit),
+    )
+}
+
+/// Starts a plaintext listener that answers every request with a redirect to
+/// the same host and path over HTTPS on `https_port`.
+pub fn run_https_redirect_server(
+    threads_count: usize,
+    address: String,
+    https_port: u16,
+) -> Result<Arc<Mutex<HttpServer>>> {
+    let config = ServerConfig {
+        threads_count,
+        address,
+        ..ServerConfig::default()
+    };
+    spawn_server(
+        &config,
+        Arc::new(ServerStats::default()),
+        None,
+        None,
+        move |stream, _| https_redirect::handle_connection(stream, https_port),
+    )
+}
+
+/// Picks the pool priority of a connection that is about to be queued, or
+/// returns `None` while it cannot tell yet.
+type Prioritizer = Box<dyn Fn(&TcpStream) -> Option<Priority> + Send + Sync>;
+
+fn spawn_server<F>(
+    config: &ServerConfig,
+    stats: Arc<ServerStats>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    prioritize: Option<Prioritizer>,
+    connection_handler: F,
+) -> Result<Arc<Mutex<HttpServer>>>
+where
+    F: Fn(TcpStream, Option<ConnectionPermit>) -> Result<()> + Send + Sync + 'static,
+{
+    let thread_pool = ThreadPool::with_config(config.pool_config())?;
+    let pool = thread_pool.monitor();
+    stats.set_pool(pool.clone());
+    let mut addresses = vec![config.address.clone()];
+    addresses.extend(config.additional_addresses.iter().cloned());
+    let listeners = bind_listeners(&addresses, config.ip_preference)?;
+    let local_addrs = listeners
+        .iter()
+        .map(|listener| listener.local_addr())
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let state = Arc::new(ServerState {
+        stop_requested: false.into(),
+        stopped: Mutex::new(false),
+        stopped_changed: Condvar::new(),
+    });
+    let acceptor = Acceptor {
+        thread_pool,
+        admission: Mutex::new(()),
+        state: Arc::clone(&state),
+        stats: Arc::clone(&stats),
+        local_addrs: local_addrs.clone(),
+        retry_after: config.retry_after_seconds(503),
+        rate_limiter,
+        prioritize,
+        connection_handler: Arc::new(connection_handler),
+    };
+
+    let thread = std::thread::spawn(move || {
+        // One accept loop per listener, all feeding the same pool.
+        let results: Vec<Result<()>> = std::thread::scope(|scope| {
+            // Connections to sort by priority go through a thread of their
+            // own, which ends once the accept loops have.
+            let (sender, receiver) = mpsc::channel();
+            let sorter = acceptor.prioritize.as_ref().map(|prioritize| {
+                let acceptor = &acceptor;
+                scope.spawn(move || acceptor.sort_connections(prioritize, receiver))
+            });
+            let sender = sorter.as_ref().map(|_| sender);
+            let mut loops: Vec<_> = listeners
+                .iter()
+                .map(|listener| {
+                    let sender = sender.clone();
+                    let acceptor = &acceptor;
+                    scope.spawn(move || acceptor.accept_connections(listener, sender))
+                })
+                .collect();
+            drop(sender);
+            loops.extend(sorter);
+            loops
+                .into_iter()
+                .map(|accept_loop| {
+                    accept_loop.join().unwrap_or_else(|_| {
+                        Err(WebServerError::Internal(
+                            "Accept thread panicked".to_string(),
+                        ))
+                    })
+                })
+                .collect()
+        });
+
+        // Stop listening first, then let the pool finish queued requests.
+        drop(listeners);
+        let state = Arc::clone(&acceptor.state);
+        drop(acceptor);
+        state.set_stopped();
+
+        results.into_iter().collect()
+    });
+
     let server = Arc::new(Mutex::new(HttpServer {
-        started: false.into(),
-        stopped: false.into(),
-        thread: None,
+        state,
+        stats,
+        pool,
+        local_addrs,
+        thread: Some(thread),
+        companion_servers: Vec::new(),
+        settings: None,
     }));
 
-    let src = Arc::clone(&server);
+    Ok(server)
+}
 
-    let thread = Some(std::thread::spawn(move || {
-        // Wait until server start
-        loop {
-            let server = src.lock().to_web_server_result()?;
-            if server.started.load(std::sync::atomic::Ordering::Relaxed)
-                || server.stopped.load(std::sync::atomic::Ordering::Relaxed)
+/// What the accept loops of one server share.
+struct Acceptor<F> {
+    thread_pool: ThreadPool,
+    /// Held while checking for room in the queue and queueing, so that two
+    /// loops cannot both take the last free slot.
+    admission: Mutex<()>,
+    state: Arc<ServerState>,
+    stats: Arc<ServerStats>,
+    local_addrs: Vec<SocketAddr>,
+    retry_after: Option<u64>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Consulted only while no worker is idle, since the order of the queue
+    /// does not matter otherwise.
+    prioritize: Option<Prioritizer>,
+    connection_handler: Arc<F>,
+}
+
+/// A connection waiting for its request line before it is queued.
+struct Unsorted {
+    stream: TcpStream,
+    permit: Option<ConnectionPermit>,
+    accepted: Instant,
+}
+
+impl<F> Acceptor<F>
+where
+    F: Fn(TcpStream, Option<ConnectionPermit>) -> Result<()> + Send + Sync + 'static,
+{
+    /// Never waits for a client: connections that would need sorting are
+    /// handed to `sorter` instead.
+    fn accept_connections(
+        &self,
+        listener: &TcpListener,
+        sorter: Option<mpsc::Sender<Unsorted>>,
+    ) -> Result<()> {
+        for stream in listener.incoming() {
+            if self
+                .state
+                .stop_requested
+                .load(std::sync::atomic::Ordering::SeqCst)
             {
                 break;
             }
+
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    // Take the other listeners down too, so that the server
+                    // stops as a whole and `join_server` reports the error.
+                    let _ = self.state.request_stop(&self.local_addrs);
+                    return Err(error.into());
+                }
+            };
+
+            // Taken before queueing, so that one client cannot fill the
+            // queue either.
+            let permit = match &self.rate_limiter {
+                Some(limiter) => match stream.peer_addr() {
+                    Ok(peer) => match limiter.connect(peer.ip()) {
+                        Some(permit) => Some(permit),
+                        None => {
+                            self.stats
+                                .rate_limited
+                                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            reject_rate_limited(stream);
+                            continue;
+                        }
+                    },
+                    // The client is gone already.
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+
+            match &sorter {
+                Some(sorter) if self.thread_pool.idle_workers() == 0 => {
+                    let unsorted = Unsorted {
+                        stream,
+                        permit,
+                        accepted: Instant::now(),
+                    };
+                    if let Err(mpsc::SendError(unsorted)) = sorter.send(unsorted) {
+                        self.admit(unsorted.stream, unsorted.permit, Priority::Normal)?;
+                    }
+                }
+                _ => self.admit(stream, permit, Priority::Normal)?,
+            }
         }
+        Ok(())
+    }
 
-        let thread_pool = ThreadPool::new(threads_count)?;
-        let tcp_listener = TcpListener::bind(address).to_web_server_result()?;
-        for stream in tcp_listener.incoming() {
-            {
-                let server = src.lock().to_web_server_result()?;
-                if server.stopped.load(std::sync::atomic::Ordering::Relaxed) {
-                    break;
+    /// Queues connections once their request line has arrived, or once
+    /// they have waited `PEEK_TIMEOUT` for it or a worker has become idle,
+    /// whichever comes first.
+    fn sort_connections(
+        &self,
+        prioritize: &Prioritizer,
+        receiver: mpsc::Receiver<Unsorted>,
+    ) -> Result<()> {
+        let mut pending: Vec<Unsorted> = Vec::new();
+        loop {
+            if pending.is_empty() {
+                match receiver.recv() {
+                    Ok(unsorted) => pending.push(unsorted),
+                    Err(_) => return Ok(()),
+                }
+            }
+            loop {
+                match receiver.try_recv() {
+                    Ok(unsorted) => pending.push(unsorted),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        for unsorted in pending {
+                            self.admit(unsorted.stream, unsorted.permit, Priority::Normal)?;
+                        }
+                        return Ok(());
+                    }
                 }
             }
 
-            let stream = stream.to_web_server_result()?;
-            thread_pool.execute(move || {
-                let r = handle_connection(stream);
-                if let Err(error) = r {
-                    println!("Request failed with an error: {}", error.0);
+            let mut waiting = Vec::with_capacity(pending.len());
+            for unsorted in pending {
+                let priority = match prioritize(&unsorted.stream) {
+                    Some(priority) => Some(priority),
+                    None if self.thread_pool.idle_workers() > 0
+                        || unsorted.accepted.elapsed() >= priority::PEEK_TIMEOUT =>
+                    {
+                        Some(Priority::Normal)
+                    }
+                    None => None,
+                };
+                match priority {
+                    Some(priority) => self.admit(unsorted.stream, unsorted.permit, priority)?,
+                    None => waiting.push(unsorted),
                 }
-            });
+            }
+            pending = waiting;
+            if !pending.is_empty() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
         }
+    }
 
-        {
-            let server = src.lock().to_web_server_result()?;
-            server
-                .stopped
-                .store(true, std::sync::atomic::Ordering::Relaxed);
+    fn admit(
+        &self,
+        stream: TcpStream,
+        permit: Option<ConnectionPermit>,
+        priority: Priority,
+    ) -> Result<()> {
+        let admission = self.admission.lock().to_web_server_result()?;
+        if self.thread_pool.is_full() {
+            drop(admission);
+            self.stats
+                .rejected_connections
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            reject_overloaded(stream, self.retry_after);
+            return Ok(());
         }
 
+        let connection_handler = Arc::clone(&self.connection_handler);
+        self.thread_pool.execute_with_priority(
+            move || {
+                // Released once the connection is closed, which may be
+                // after the handler returns for a connection that was
+                // upgraded.
+                let r = connection_handler(stream, permit);
+                if let Err(error) = r {
+                    println!("Request failed with an error: {}", error);
+                }
+            },
+            priority,
+        );
         Ok(())
-    }));
+    }
+}
 
-    {
-        let mut server = server.lock().unwrap();
-        server.thread = thread;
-        server
-            .started
-            .store(true, std::sync::atomic::Ordering::Relaxed);
+/// Answers a connection the pool has no room for.
+fn reject_overloaded(stream: TcpStream, retry_after: Option<u64>) {
+    let mut response = error_page(
+        503,
+        "Service Unavailable",
+        "The server is too busy to handle the request, try again later",
+    );
+    if let Some(seconds) = retry_after {
+        response.set_header("Retry-After", &seconds.to_string());
     }
+    reject_connection(stream, response);
+}
 
-    Ok(server)
+/// Answers a connection from a client that has as many open as it may.
+fn reject_rate_limited(stream: TcpStream) {
+    let mut response = too_many_requests(Duration::from_secs(1));
+    response.set_header("Connection", "close");
+    reject_connection(stream, response);
 }
 
-fn read_request(stream: &mut TcpStream) -> Result<HttpRequest> {
-    let lines = {
-        let reader = BufReader::new(stream);
-        let mut lines = Vec::new();
-        for result in reader.lines() {
-            let line = result.to_web_server_result()?;
-            if line.is_empty() {
-                break;
-            }
-            lines.push(line);
-        }
+fn too_many_requests(retry_after: Duration) -> Response {
+    let mut response = error_page(
+        429,
+        "Too Many Requests",
+        "Too many requests from your address, try again later",
+    );
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    response.set_header("Retry-After", &seconds.to_string());
+    response
+}
 
-        lines
-    };
+/// Sends `response` to a connection that is not served. This runs on the
+/// accept thread, so it never waits for the client.
+fn reject_connection(mut stream: TcpStream, mut response: Response) {
+    response.set_header("Date", &format_http_date(SystemTime::now()));
+    response.set_header("Connection", "close");
 
-    let mut tokens_iter = lines[0].split(' ');
-
-    let method = tokens_iter
-        .next()
-        .ok_or("Invalid request format")
-        .to_web_server_result()?;
-
-    if method == "GET" {
-        // read path for get request
-        let path = tokens_iter
-            .next()
-            .ok_or("Invalid request format")
-            .to_web_server_result()?;
-
-        let http_ver = tokens_iter
-            .next()
-            .ok_or("Invalid request format")
-            .to_web_server_result()?;
-        if http_ver != "HTTP/1.1" {
-            return Err(WebServerError(format!(
-                "Expected HTTP/1.1, got {}",
-                http_ver
-            )));
-        }
-
-        Ok(HttpRequest::GET(HttpGetRequest {
-            path: path.to_string(),
-        }))
-    } else {
-        Err(WebServerError(format!(
-            "Unsupported (or invalid) method {}",
-            method
-        )))
+    if stream.set_nonblocking(true).is_err() {
+        return;
     }
-}
+    let _ = response.write_to(&mut stream, HeaderCasing::default(), usize::MAX);
+    let _ = stream.shutdown(std::net::Shutdown::Write);
 
-fn get_absolute_path(path_from_request: &str) -> Result<String> {
-    let cwd = std::env::current_dir().to_web_server_result()?;
-    let content_dir = cwd.join("content");
-    let rel_path = path_from_request
-        .strip_prefix("/")
-        .ok_or(WebServerError("Failed to strip prefix".to_string()))?;
-    let abs_path = content_dir.join(rel_path);
-    let abs_str = abs_path.to_str().ok_or(WebServerError(
-        "Failed to convert path to string".to_string(),
-    ))?;
-    Ok(abs_str.to_string())
+    // Closing a socket with unread data resets the connection, which can
+    // discard the response before the client reads it. Drop whatever part of
+    // the request has already arrived.
+    let mut discard = [0; 4096];
+    for _ in 0..16 {
+        match std::io::Read::read(&mut stream, &mut discard) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
 }
 
 fn html_error_code_to_str(value: i32) -> Result<&'static str> {
     match value {
+        100 => Ok("CONTINUE"),
+        101 => Ok("SWITCHING PROTOCOLS"),
         200 => Ok("OK"),
+        201 => Ok("CREATED"),
+        202 => Ok("ACCEPTED"),
+        204 => Ok("NO CONTENT"),
+        206 => Ok("PARTIAL CONTENT"),
+        301 => Ok("MOVED PERMANENTLY"),
+        302 => Ok("FOUND"),
+        303 => Ok("SEE OTHER"),
+        304 => Ok("NOT MODIFIED"),
+        307 => Ok("TEMPORARY REDIRECT"),
+        308 => Ok("PERMANENT REDIRECT"),
+        400 => Ok("BAD REQUEST"),
+        401 => Ok("UNAUTHORIZED"),
+        403 => Ok("FORBIDDEN"),
         404 => Ok("NOT FOUND"),
+        405 => Ok("METHOD NOT ALLOWED"),
+        408 => Ok("REQUEST TIMEOUT"),
+        409 => Ok("CONFLICT"),
+        410 => Ok("GONE"),
+        411 => Ok("LENGTH REQUIRED"),
+        412 => Ok("PRECONDITION FAILED"),
+        413 => Ok("PAYLOAD TOO LARGE"),
+        414 => Ok("URI TOO LONG"),
+        415 => Ok("UNSUPPORTED MEDIA TYPE"),
+        416 => Ok("RANGE NOT SATISFIABLE"),
+        422 => Ok("UNPROCESSABLE CONTENT"),
+        426 => Ok("UPGRADE REQUIRED"),
+        429 => Ok("TOO MANY REQUESTS"),
+        431 => Ok("REQUEST HEADER FIELDS TOO LARGE"),
         500 => Ok("INTERNAL SERVER ERROR"),
-        _ => Err(WebServerError(format!("Unknown response conde {}", value))),
+        501 => Ok("NOT IMPLEMENTED"),
+        502 => Ok("BAD GATEWAY"),
+        503 => Ok("SERVICE UNAVAILABLE"),
+        504 => Ok("GATEWAY TIMEOUT"),
+        505 => Ok("HTTP VERSION NOT SUPPORTED"),
+        _ => Err(WebServerError::Internal(format!(
+            "Unknown response conde {}",
+            value
+        ))),
     }
 }
 
-fn handle_get_request(request: HttpGetRequest) -> Result<Vec<u8>> {
-    let path = get_absolute_path(&request.path)?;
-
-    let mut error_code = 200;
-    let mut maybe_content: Option<Vec<u8>> = None;
+/// A small HTML page for an error status. `message` must already be escaped.
+fn error_page(status: u16, title: &str, message: &str) -> Response {
+    let mut response = Response::new(status);
+    response.set_header("Content-Type", "text/html; charset=utf-8");
+    response.set_body(
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head><title>{} {}</title></head>\n\
+             <body>\n<h1>{}</h1>\n<p>{}</p>\n</body>\n</html>\n",
+            status, title, title, message
+        )
+        .into_bytes(),
+    );
+    response.mark_default_error_page();
+    response
+}
 
-    if !std::path::Path::new(&path).exists() {
-        error_code = 404;
+fn not_found_response(request_path: &str, config: &ServerConfig) -> Response {
+    let message = if config.not_found_reflects_path {
+        format!("Could not find {}", html::escape(request_path))
     } else {
-        println!("Reading path: {}", path);
-        maybe_content = Some(std::fs::read(path).to_web_server_result()?);
+        "The requested resource could not be found".to_string()
+    };
+
+    error_page(404, "Not Found", &message)
+}
+
+/// The `Allow` value for the methods `allowed` for a path. `OPTIONS` is
+/// always added, as every path answers it.
+fn allow_header(allowed: &[Method]) -> String {
+    let mut allowed = allowed.to_vec();
+    allowed.push(Method::Options);
+    allowed.sort();
+    allowed.dedup();
+    allowed
+        .iter()
+        .map(|method| method.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn method_not_allowed_response(allowed: &[Method]) -> Response {
+    let mut response = Response::new(405);
+    response.set_header("Allow", &allow_header(allowed));
+    response.set_body(Vec::new());
+    response
+}
+
+/// Answers `OPTIONS` with the methods `allowed` for the path.
+fn options_response(allowed: &[Method]) -> Response {
+    let mut response = Response::new(204);
+    response.set_header("Allow", &allow_header(allowed));
+    response
+}
+
+fn handle_static_request(request: &Request, config: &ServerConfig, content_dir: &Path) -> Response {
+    match request.method {
+        Method::Get | Method::Head => match handle_get_request(request, config, content_dir) {
+            Ok(response) => response,
+            Err(error) => {
+                println!("Internal server error: {}", error);
+                Response::internal_server_error()
+            }
+        },
+        Method::Options => options_response(&[Method::Get, Method::Head]),
+        _ => method_not_allowed_response(&[Method::Get, Method::Head]),
     }
+}
 
-    let mut response_head = Vec::new();
-    write!(
-        &mut response_head,
-        "HTTP/1.1 {} {}\r\n",
-        error_code,
-        html_error_code_to_str(error_code)?
-    )
-    .to_web_server_result()?;
+fn handle_get_request(
+    request: &Request,
+    config: &ServerConfig,
+    content_dir: &Path,
+) -> Result<Response> {
+    let mut path = match resolve_decoded_path(content_dir, request.path()) {
+        PathResolution::Found(path) => path,
+        PathResolution::NotFound => return Ok(not_found_response(request.path(), config)),
+        PathResolution::Forbidden => {
+            return Ok(error_page(
+                403,
+                "Forbidden",
+                "Access to the requested resource is not allowed",
+            ))
+        }
+        PathResolution::BadRequest => {
+            return Ok(error_page(
+                400,
+                "Bad Request",
+                "The request path is not valid",
+            ))
+        }
+    };
+
+    if path.is_dir() {
+        if !request.path().ends_with('/') {
+            // Relative links inside the index only work below the slash.
+            let mut location =
+                format!("{}/", percent_encoding::percent_encode_path(request.path()));
+            if let Some(query) = request.query_string() {
+                location = format!("{}?{}", location, query);
+            }
+            let mut response = Response::new(301);
+            response.set_header("Location", &location);
+            response.set_body(Vec::new());
+            return Ok(response);
+        }
 
-    if let Some(content) = &maybe_content {
-        write!(&mut response_head, "Content-Length: {}\r\n", content.len())
-            .to_web_server_result()?;
+        let index = config.index_files.iter().find_map(|index| {
+            let index_path = format!("{}{}", request.path(), index);
+            match resolve_decoded_path(content_dir, &index_path) {
+                PathResolution::Found(found) if found.is_file() => Some(found),
+                _ => None,
+            }
+        });
+        path = match index {
+            Some(index) => index,
+            None if config.directory_listing => {
+                return directory_listing::listing_response(&path, request.path())
+            }
+            None => return Ok(not_found_response(request.path(), config)),
+        };
     }
-    write!(&mut response_head, "\r\n").to_web_server_result()?;
 
-    if let Some(content) = &maybe_content {
-        response_head.extend(content);
+    let metadata = std::fs::metadata(&path)?;
+    let validators = Validators::of(&metadata);
+
+    let mut response = if validators.not_modified(request) {
+        Response::new(304)
+    } else {
+        let cached = match &config.file_cache {
+            Some(cache) => cache.read(&path, &metadata)?,
+            None => None,
+        };
+        let mut response = match cached {
+            Some(content) => {
+                let mut response = Response::new(200);
+                response.set_body(content);
+                response
+            }
+            None => {
+                println!("Reading path: {}", path.display());
+                Response::from_file(&path)?
+            }
+        };
+        response.set_header("Content-Type", config.mime_types.for_path(&path));
+        response.set_header("Accept-Ranges", "bytes");
+        response
+    };
+
+    response.set_header("ETag", &validators.etag);
+    if let Some(modified) = validators.last_modified {
+        response.set_header("Last-Modified", &format_http_date(modified));
+    }
+    if let Some(cache_control) = config.cache_control_for(request.path()) {
+        response.set_header("Cache-Control", cache_control);
+    }
+
+    if response.status() == 200 && request.method == Method::Get {
+        if let Some(range) = request.header("Range") {
+            if validators.range_allowed(request) {
+                apply_range(&mut response, range);
+            }
+        }
     }
 
     // std::thread::sleep(std::time::Duration::from_secs(5));
 
-    Ok(response_head)
+    Ok(response)
 }
 
-fn handle_connection(mut stream: TcpStream) -> Result<()> {
-    let response = match read_request(&mut stream)? {
-        HttpRequest::GET(get_request) => match handle_get_request(get_request) {
-            Ok(response) => response,
+/// Picks what answers the request once it has passed the middlewares.
+fn dispatch(
+    request: &mut Request,
+    config: &ServerConfig,
+    router: &Router,
+    peer: Option<SocketAddr>,
+) -> Response {
+    let request_id = NEXT_REQUEST_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let is_echo = config
+        .debug_echo_path
+        .as_ref()
+        .is_some_and(|echo_path| request.path() == echo_path);
+
+    if is_echo {
+        return debug_echo::echo_response(request, peer, request_id);
+    }
+
+    match router.virtual_hosts.find(request) {
+        Some(host) => {
+            let content_dir = host.content_dir_or(&config.content_dir);
+            host.router.run_middlewares(request, &|request| {
+                route(request, &host.router, config, content_dir)
+            })
+        }
+        None => route(request, router, config, &config.content_dir),
+    }
+}
+
+fn route(
+    request: &Request,
+    router: &Router,
+    config: &ServerConfig,
+    content_dir: &Path,
+) -> Response {
+    match router.find(request) {
+        RouteMatch::Handler(handler) => handler(request),
+        RouteMatch::MethodNotAllowed(allowed) => method_not_allowed_response(&allowed),
+        RouteMatch::Options(allowed) => options_response(&allowed),
+        RouteMatch::Static => handle_static_request(request, config, content_dir),
+    }
+}
+
+/// Answers a request that could not be read or parsed. The body only names
+/// the status, as the error may tell more about the server than the client
+/// should know; the details go to the log.
+fn error_response(error: &WebServerError) -> Response {
+    println!("Could not read request: {}", error);
+    let status = error.status_code();
+    let mut response = Response::text(html_error_code_to_str(status.into()).unwrap_or("ERROR"));
+    response.set_status(status);
+    response.set_header("Date", &format_http_date(SystemTime::now()));
+    response.set_header("Connection", "close");
+    response
+}
+
+/// Turns a full `200` response into `206` or `416` as the `Range` header asks.
+fn apply_range(response: &mut Response, range: &str) {
+    let len = match response.body_len() {
+        Some(len) => len,
+        None => return,
+    };
+
+    match parse_range(range, len) {
+        RangeRequest::Full => {}
+        RangeRequest::Partial { start, end } => {
+            response.set_status(206);
+            response.set_header("Content-Range", &format!("bytes {}-{}/{}", start, end, len));
+            response.slice_body(start, end - start + 1);
+        }
+        RangeRequest::Unsatisfiable => {
+            response.set_status(416);
+            response.set_header("Content-Range", &format!("bytes */{}", len));
+            response.set_body(Vec::new());
+        }
+    }
+}
+
+/// Serves requests on one connection until the client asks to close it,
+/// stays idle for too long, or the per-connection request cap is reached.
+fn handle_connection(
+    stream: TcpStream,
+    settings: &LiveSettings,
+    stats: &Arc<ServerStats>,
+    rate_limiter: Option<&RateLimiter>,
+    permit: Option<ConnectionPermit>,
+) -> Result<()> {
+    let mut active = settings.load();
+    let peer = stream.peer_addr().ok();
+    stream.set_write_timeout(active.config.write_timeout)?;
+    let connection = ConnectionStream::accept(stream, &active.config)?;
+    let secure = connection.is_secure();
+    let mut reader = ConnectionReader::new(connection);
+    let mut served = 0;
+
+    loop {
+        let idle_timeout = if served == 0 {
+            active.config.read_timeout
+        } else {
+            Some(active.config.keep_alive_timeout)
+        };
+        if !wait_for_request(&mut reader, idle_timeout)? {
+            return Ok(());
+        }
+
+        // Each request is served with the settings current when it arrives,
+        // so a persistent connection picks up a reload between requests.
+        active = settings.load();
+        let config = &active.config;
+        let router = &active.router;
+        let limits = config.request_limits();
+        let received_at = SystemTime::now();
+        let parse_started = Instant::now();
+        reader.get_mut().set_read_timeout(config.read_timeout);
+        reader.get_mut().set_deadline(
+            config
+                .request_read_timeout
+                .map(|timeout| parse_started + timeout),
+        );
+        // A client sending `Expect: 100-continue` holds the body back until
+        // it is told to go on.
+        let request = read_request(&mut reader, &limits, |reader| {
+            let interim = Response::new(100);
+            let connection = reader.get_mut();
+            interim.write_to(connection, HeaderCasing::default(), usize::MAX)?;
+            Ok(connection.flush()?)
+        });
+        reader.get_mut().set_deadline(None);
+        let mut request = match request {
+            Ok(request) => request,
             Err(error) => {
-                println!("Internal server error: {}", error.0);
+                // The rest of the stream cannot be trusted to start at a
+                // request boundary, so the connection is closed either way.
+                if !matches!(error, WebServerError::Io(_)) {
+                    let response = error_response(&error);
+                    let mut counted = CountingWriter::new(reader.get_mut());
+                    let _ = response.write_to(&mut counted, HeaderCasing::default(), usize::MAX);
+                    stats.request_started();
+                    stats.request_finished(
+                        response.status(),
+                        counted.count(),
+                        parse_started.elapsed(),
+                    );
+                    if let Some(access_log) = &config.access_log {
+                        let mut entry = AccessLogEntry::new(
+                            peer,
+                            received_at,
+                            None,
+                            response.status(),
+                            response.body_len().unwrap_or(0),
+                        );
+                        entry.timing.parse = parse_started.elapsed();
+                        access_log.log(entry);
+                    }
+                }
+                return Err(error);
+            }
+        };
+        request.peer = peer;
+        request.secure = secure;
+        served += 1;
+        stats.request_started();
+
+        let handle_started = Instant::now();
+        let parse_time = handle_started - parse_started;
+        let mut response = respond(&mut request, config, router, stats, rate_limiter, secure);
+        if request.version() == Version::Http10 {
+            response.remove_chunked_framing();
+        }
+        let write_started = Instant::now();
 
-                let mut body = Vec::new();
-                write!(&mut body, "HTTP/1.1 500 Internal server error\r\n")
-                    .to_web_server_result()?;
-                body
+        let keep_alive = served < config.max_requests_per_connection
+            && !config.keep_alive_timeout.is_zero()
+            && client_wants_keep_alive(&request)
+            && has_deterministic_length(&response);
+        // An upgrade keeps its `Connection: Upgrade`.
+        if !response.is_upgrade() {
+            response.set_header(
+                "Connection",
+                if keep_alive { "keep-alive" } else { "close" },
+            );
+        }
+
+        let head = match response
+            .serialize_head(HeaderCasing::default(), config.max_response_header_bytes)
+        {
+            Ok(head) => head,
+            Err(error) => {
+                println!("Internal server error: {}", error);
+                response = Response::internal_server_error();
+                response.set_header("Connection", "close");
+                let mut counted = CountingWriter::new(reader.get_mut());
+                let result = response.write_to(&mut counted, HeaderCasing::default(), usize::MAX);
+                stats.request_finished(500, counted.count(), parse_started.elapsed());
+                log_access(
+                    config,
+                    AccessLogEntry::new(peer, received_at, Some(&request), 500, 0),
+                    parse_time,
+                    write_started - handle_started,
+                    write_started.elapsed(),
+                );
+                return result;
             }
-        },
+        };
+        let mut counted = CountingWriter::new(reader.get_mut());
+        let written = counted
+            .write_all(&head)
+            .map_err(WebServerError::from)
+            .and_then(|_| response.write_body(&mut counted));
+        stats.request_finished(response.status(), counted.count(), parse_started.elapsed());
+        written?;
+
+        log_access(
+            config,
+            AccessLogEntry::new(
+                peer,
+                received_at,
+                Some(&request),
+                response.status(),
+                response.body_len().unwrap_or(0),
+            ),
+            parse_time,
+            write_started - handle_started,
+            write_started.elapsed(),
+        );
+
+        if let Some(upgrade) = response.take_upgrade() {
+            // The connection now belongs to the handler, on a thread of its
+            // own, and the worker goes back to the pool.
+            let upgraded = Upgraded {
+                connection: reader,
+                request,
+                read_timeout: config.read_timeout,
+                max_message_size: config.max_body_size,
+            };
+            std::thread::Builder::new()
+                .name("websocket".to_string())
+                .spawn(move || {
+                    let _permit = permit;
+                    upgrade(upgraded);
+                })?;
+            return Ok(());
+        }
+
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+fn log_access(
+    config: &ServerConfig,
+    mut entry: AccessLogEntry,
+    parse: Duration,
+    handle: Duration,
+    write: Duration,
+) {
+    if let Some(access_log) = &config.access_log {
+        entry.timing = RequestTiming {
+            parse,
+            handle,
+            write,
+        };
+        access_log.log(entry);
+    }
+}
+
+/// Blocks until the next request starts arriving. Returns `false` if the
+/// client closed the connection or sent nothing within `timeout`.
+fn wait_for_request(reader: &mut ConnectionReader, timeout: Option<Duration>) -> Result<bool> {
+    reader.get_mut().set_read_timeout(timeout);
+    match reader.fill_buf() {
+        Ok(buffer) => Ok(!buffer.is_empty()),
+        // A TLS client that closes the socket without saying goodbye first
+        // shows up as an unexpected EOF.
+        Err(error)
+            if matches!(
+                error.kind(),
+                std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// HTTP/1.1 connections are persistent unless the client sends
+/// `Connection: close`; HTTP/1.0 ones only with `Connection: keep-alive`.
+fn client_wants_keep_alive(request: &Request) -> bool {
+    let has_token = |name: &str| {
+        request
+            .headers()
+            .get_all("Connection")
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case(name))
     };
+    match request.version() {
+        Version::Http10 => has_token("keep-alive"),
+        Version::Http11 => !has_token("close"),
+    }
+}
 
-    stream.write_all(&response).to_web_server_result()
+/// Whether the client can tell where the response ends without the
+/// connection being closed.
+fn has_deterministic_length(response: &Response) -> bool {
+    matches!(response.status(), 100..=199 | 204 | 304)
+        || response.header("Content-Length").is_some()
+        || response
+            .header("Transfer-Encoding")
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
 }
 
-impl<T, SomeError> ConvertibleToResult<T> for std::result::Result<T, SomeError>
-where
-    SomeError: Display,
-{
-    fn to_web_server_result(self) -> Result<T> {
-        match self {
-            Ok(value) => Ok(value),
-            Err(an_error) => Err(WebServerError(an_error.to_string())),
+fn respond(
+    request: &mut Request,
+    config: &ServerConfig,
+    router: &Router,
+    stats: &Arc<ServerStats>,
+    rate_limiter: Option<&RateLimiter>,
+    secure: bool,
+) -> Response {
+    let peer = request.peer;
+    let limited = match (rate_limiter, peer) {
+        (Some(limiter), Some(peer)) => limiter.check_request(peer.ip()).err(),
+        _ => None,
+    };
+
+    // A panicking handler or middleware only fails its own request; the
+    // client gets a 500 and the connection stays usable.
+    let handled = match limited {
+        Some(retry_after) => {
+            stats
+                .rate_limited
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(too_many_requests(retry_after))
+        }
+        None => panic::catch_unwind(AssertUnwindSafe(|| {
+            router.run_middlewares(request, &|request| dispatch(request, config, router, peer))
+        })),
+    };
+    let mut response = match handled {
+        Ok(response) => response,
+        Err(_) => {
+            stats
+                .handler_panics
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            println!("Request handler panicked");
+            Response::internal_server_error()
+        }
+    };
+    let request = &*request;
+
+    // Every WebSocket holds a thread for as long as it is open, so their
+    // number is capped.
+    if let Some(upgrade) = response.take_upgrade() {
+        match WebSocketSlot::acquire(stats, config.max_websockets) {
+            Some(slot) => response.set_upgrade(Box::new(move |upgraded| {
+                let _slot = slot;
+                upgrade(upgraded);
+            })),
+            None => {
+                response = error_page(
+                    503,
+                    "Service Unavailable",
+                    "Too many WebSocket connections are open",
+                )
+            }
         }
     }
+
+    config
+        .error_pages
+        .apply(request, &config.content_dir, &mut response);
+
+    // Compressed before a HEAD body is dropped, so HEAD reports the same
+    // headers as GET.
+    if let Some(compression) = &config.compression {
+        if let Err(error) = compression.apply(request, &mut response) {
+            println!("Failed to compress response: {}", error);
+        }
+    }
+
+    if request.method == Method::Head {
+        response.remove_body();
+    }
+
+    if secure {
+        if let Some(hsts) = &config.hsts {
+            response.set_header("Strict-Transport-Security", &hsts.header_value());
+        }
+    }
+
+    if response.header("Retry-After").is_none() {
+        if let Some(seconds) = config.retry_after_seconds(response.status()) {
+            response.set_header("Retry-After", &seconds.to_string());
+        }
+    }
+
+    if response.header("Date").is_none() {
+        response.set_header("Date", &format_http_date(SystemTime::now()));
+    }
+    if let Some(server) = &config.server_header {
+        if response.header("Server").is_none() {
+            response.set_header("Server", server);
+        }
+    }
+
+    response
 }
@@ -0,0 +1,128 @@
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::Result;
+use crate::WebServerError;
+
+/// Order in which the addresses a host name resolves to are tried when
+/// binding a listener.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IpPreference {
+    /// Keep the order returned by the resolver.
+    #[default]
+    AsResolved,
+    Ipv4First,
+    Ipv6First,
+    /// Listen on every address, e.g. on both `127.0.0.1` and `::1` for
+    /// `localhost`, each with a listener of its own. Addresses that cannot
+    /// be bound are skipped. Port `0` becomes the port picked for the first
+    /// listener, so all of them share it.
+    Both,
+}
+
+/// Resolves `address` and binds the first candidate that succeeds, in the
+/// order given by `preference`. Fails only if every candidate fails.
+/// `IpPreference::Both` needs more than one listener, so here it keeps the
+/// order returned by the resolver like `AsResolved`.
+pub fn bind_listener(address: &str, preference: IpPreference) -> Result<TcpListener> {
+    bind_candidates(address, preference, false)
+}
+
+/// Binds one listener per address, or with `IpPreference::Both` one per
+/// address it resolves to. With more than one listener, IPv6 listeners only
+/// take IPv6 connections, so that `[::]:8080` and `0.0.0.0:8080` can be
+/// bound side by side. Nothing stays bound if any address fails.
+pub(crate) fn bind_listeners(
+    addresses: &[String],
+    preference: IpPreference,
+) -> Result<Vec<TcpListener>> {
+    let only_v6 = addresses.len() > 1;
+    let mut listeners = Vec::new();
+    for address in addresses {
+        match preference {
+            IpPreference::Both => listeners.extend(bind_all(address, only_v6)?),
+            _ => listeners.push(bind_candidates(address, preference, only_v6)?),
+        }
+    }
+    Ok(listeners)
+}
+
+/// Binds every candidate of `address` that can be bound. Fails only if
+/// none can.
+fn bind_all(address: &str, only_v6: bool) -> Result<Vec<TcpListener>> {
+    let candidates: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    let only_v6 = only_v6 || candidates.len() > 1;
+
+    let mut listeners: Vec<TcpListener> = Vec::new();
+    let mut failures = Vec::new();
+    for mut candidate in candidates {
+        if candidate.port() == 0 {
+            if let Some(first) = listeners.first() {
+                candidate.set_port(first.local_addr()?.port());
+            }
+        }
+        match bind_candidate(&candidate, only_v6) {
+            Ok(listener) => listeners.push(listener),
+            Err(error) => failures.push(format!("{}: {}", candidate, error)),
+        }
+    }
+
+    if listeners.is_empty() {
+        return Err(bind_error(address, &failures));
+    }
+    Ok(listeners)
+}
+
+fn bind_candidates(address: &str, preference: IpPreference, only_v6: bool) -> Result<TcpListener> {
+    let mut candidates: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+
+    match preference {
+        IpPreference::AsResolved | IpPreference::Both => {}
+        IpPreference::Ipv4First => candidates.sort_by_key(|candidate| candidate.is_ipv6()),
+        IpPreference::Ipv6First => candidates.sort_by_key(|candidate| candidate.is_ipv4()),
+    }
+
+    let mut failures = Vec::new();
+    for candidate in &candidates {
+        match bind_candidate(candidate, only_v6) {
+            Ok(listener) => return Ok(listener),
+            Err(error) => failures.push(format!("{}: {}", candidate, error)),
+        }
+    }
+    Err(bind_error(address, &failures))
+}
+
+fn bind_candidate(candidate: &SocketAddr, only_v6: bool) -> std::io::Result<TcpListener> {
+    if only_v6 && candidate.is_ipv6() {
+        bind_only_v6(candidate)
+    } else {
+        TcpListener::bind(candidate)
+    }
+}
+
+fn bind_error(address: &str, failures: &[String]) -> WebServerError {
+    WebServerError::Io(std::io::Error::new(
+        std::io::ErrorKind::AddrNotAvailable,
+        format!(
+            "Failed to bind any address of {} ({})",
+            address,
+            if failures.is_empty() {
+                "no addresses resolved".to_string()
+            } else {
+                failures.join(", ")
+            }
+        ),
+    ))
+}
+
+fn bind_only_v6(address: &SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(true)?;
+    // Matches what `TcpListener::bind` does on Unix.
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&(*address).into())?;
+    socket.listen(128)?;
+    Ok(socket.into())
+}
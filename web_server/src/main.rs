@@ -1,7 +1,121 @@
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
 
-fn main() {
-    let threads_count = 20;
-    let http_server = web_server::run_server(threads_count, "127.0.0.1:7878".to_string()).unwrap();
+use web_server::{AccessLog, FileLogSink, HttpServer, LogFormat, StdoutLogSink, WebServerError};
+
+const USAGE: &str = "\
+Usage: web_server [OPTIONS]
+
+Serves the files of a directory over HTTP.
+
+Options:
+  -c, --config <FILE>    read settings from a TOML file
+  -b, --bind <ADDRESS>   address to listen on [default: 127.0.0.1:7878]
+  -t, --threads <COUNT>  worker threads [default: 20]
+  -r, --root <DIR>       directory to serve [default: content]
+  -l, --log <FILE>       write an access log to FILE, or to stdout for -
+  -h, --help             print this help
+
+Options given here override the configuration file.";
+
+/// Command line options. `None` keeps the value from the configuration file,
+/// or the default.
+#[derive(Default)]
+struct Options {
+    config: Option<PathBuf>,
+    bind: Option<String>,
+    threads: Option<usize>,
+    root: Option<PathBuf>,
+    log: Option<String>,
+}
+
+/// Returns `None` for `--help`.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        // Both `--name value` and `--name=value`.
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-c" | "--config" | "-b" | "--bind" | "-t" | "--threads" | "-r" | "--root" | "-l"
+            | "--log" => {}
+            _ => return Err(format!("unknown option `{}`", name)),
+        }
+
+        let value = value
+            .or_else(|| args.next())
+            .ok_or_else(|| format!("`{}` needs a value", name))?;
+        match name.as_str() {
+            "-c" | "--config" => options.config = Some(PathBuf::from(value)),
+            "-b" | "--bind" => options.bind = Some(value),
+            "-t" | "--threads" => {
+                let threads = value.parse().ok().filter(|threads| *threads > 0);
+                options.threads =
+                    Some(threads.ok_or_else(|| format!("invalid thread count `{}`", value))?);
+            }
+            "-r" | "--root" => options.root = Some(PathBuf::from(value)),
+            _ => options.log = Some(value),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn start(options: Options) -> Result<Arc<Mutex<HttpServer>>, WebServerError> {
+    let mut builder = HttpServer::builder();
+    if let Some(path) = &options.config {
+        builder = builder.config_file(path)?;
+    }
+    if let Some(address) = options.bind {
+        builder = builder.bind(address);
+    }
+    if let Some(threads) = options.threads {
+        builder = builder.threads(threads);
+    }
+    if let Some(root) = options.root {
+        builder = builder.content_dir(root);
+    }
+    if let Some(log) = options.log {
+        let access_log = match log.as_str() {
+            "-" => AccessLog::new(StdoutLogSink::new(LogFormat::Combined)),
+            _ => AccessLog::new(FileLogSink::open(log, LogFormat::Combined)?),
+        };
+        builder = builder.access_log(access_log);
+    }
+    builder.start()
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("web_server: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let http_server = match start(options) {
+        Ok(http_server) => http_server,
+        Err(error) => {
+            eprintln!("web_server: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    // The port is only known here when it was given as `0`.
+    for address in http_server.lock().unwrap().local_addrs() {
+        eprintln!("web_server: listening on {}", address);
+    }
+    #[cfg(feature = "signals")]
+    web_server::shutdown_on_signal(Arc::clone(&http_server)).unwrap();
     web_server::join_server(Arc::clone(&http_server)).unwrap();
-}
\ No newline at end of file
+    ExitCode::SUCCESS
+}
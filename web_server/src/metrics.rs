@@ -0,0 +1,354 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::FileCache;
+use crate::PoolMonitor;
+use crate::Request;
+use crate::Response;
+
+/// Upper bounds of the latency histogram buckets, in seconds. A last bucket
+/// without bound catches everything slower.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Status codes with a counter of their own. Anything outside is counted
+/// under 0, although the server never sends such a status.
+const STATUS_CODES: std::ops::Range<u16> = 100..600;
+
+/// Counters updated by the connections of one server. All of them only
+/// ever grow, except for `in_flight`.
+pub(crate) struct ServerStats {
+    pub handler_panics: AtomicU64,
+    pub rejected_connections: AtomicU64,
+    pub rate_limited: AtomicU64,
+    /// WebSocket connections open right now.
+    pub websockets: AtomicU64,
+    requests: AtomicU64,
+    in_flight: AtomicU64,
+    bytes_sent: AtomicU64,
+    /// Indexed by status minus 100, with the last slot for anything else.
+    statuses: Vec<AtomicU64>,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_micros: AtomicU64,
+    /// Set once the pool exists, which is after the stats are created.
+    pool: OnceLock<PoolMonitor>,
+    /// The cache of the current config, which a reload may replace.
+    file_cache: Mutex<Option<FileCache>>,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        ServerStats {
+            handler_panics: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            websockets: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            statuses: (0..=STATUS_CODES.len())
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            latency_buckets: Default::default(),
+            latency_micros: AtomicU64::new(0),
+            pool: OnceLock::new(),
+            file_cache: Mutex::new(None),
+        }
+    }
+}
+
+impl ServerStats {
+    pub fn set_pool(&self, pool: PoolMonitor) {
+        let _ = self.pool.set(pool);
+    }
+
+    pub fn set_file_cache(&self, file_cache: Option<FileCache>) {
+        *self.file_cache.lock().unwrap() = file_cache;
+    }
+
+    pub fn request_started(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records an answered request. Every call has to follow a
+    /// `request_started`.
+    pub fn request_finished(&self, status: u16, bytes_sent: u64, latency: Duration) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        self.bytes_sent.fetch_add(bytes_sent, Ordering::SeqCst);
+
+        let status_slot = if STATUS_CODES.contains(&status) {
+            (status - STATUS_CODES.start) as usize
+        } else {
+            STATUS_CODES.len()
+        };
+        self.statuses[status_slot].fetch_add(1, Ordering::SeqCst);
+
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::SeqCst);
+        self.latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::SeqCst);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// Statuses that were sent at least once, with their counts, in
+    /// ascending order.
+    fn status_counts(&self) -> Vec<(u16, u64)> {
+        self.statuses
+            .iter()
+            .enumerate()
+            .map(|(slot, count)| {
+                let status = if slot < STATUS_CODES.len() {
+                    STATUS_CODES.start + slot as u16
+                } else {
+                    0
+                };
+                (status, count.load(Ordering::SeqCst))
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Cumulative counts per bucket bound, `None` standing for `+Inf`.
+    fn latency_histogram(&self) -> Vec<(Option<f64>, u64)> {
+        let mut total = 0;
+        self.latency_buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                total += count.load(Ordering::SeqCst);
+                (LATENCY_BUCKETS.get(bucket).copied(), total)
+            })
+            .collect()
+    }
+
+    fn latency_seconds(&self) -> f64 {
+        self.latency_micros.load(Ordering::SeqCst) as f64 / 1_000_000.0
+    }
+
+    fn gauges(&self) -> Vec<(&'static str, &'static str, u64)> {
+        let mut gauges = vec![
+            (
+                "http_requests_in_flight",
+                "Requests being handled right now.",
+                self.in_flight.load(Ordering::SeqCst),
+            ),
+            (
+                "websocket_connections",
+                "WebSocket connections open right now.",
+                self.websockets.load(Ordering::SeqCst),
+            ),
+        ];
+        if let Some(pool) = self.pool.get() {
+            gauges.push((
+                "thread_pool_workers",
+                "Worker threads running.",
+                pool.size() as u64,
+            ));
+            gauges.push((
+                "thread_pool_idle_workers",
+                "Worker threads waiting for a connection.",
+                pool.idle_workers() as u64,
+            ));
+            gauges.push((
+                "thread_pool_queued_connections",
+                "Accepted connections waiting for a worker.",
+                pool.queued_jobs() as u64,
+            ));
+        }
+        if let Some(cache) = &*self.file_cache.lock().unwrap() {
+            gauges.push((
+                "file_cache_bytes",
+                "Bytes of static file content held in memory.",
+                cache.size(),
+            ));
+            gauges.push((
+                "file_cache_entries",
+                "Static files held in memory.",
+                cache.len() as u64,
+            ));
+        }
+        gauges
+    }
+
+    fn counters(&self) -> Vec<(&'static str, &'static str, u64)> {
+        let mut counters = vec![
+            (
+                "http_response_bytes_total",
+                "Bytes sent in responses, headers included.",
+                self.bytes_sent.load(Ordering::SeqCst),
+            ),
+            (
+                "http_handler_panics_total",
+                "Requests whose handler panicked.",
+                self.handler_panics.load(Ordering::SeqCst),
+            ),
+            (
+                "http_rejected_connections_total",
+                "Connections turned away because the queue was full.",
+                self.rejected_connections.load(Ordering::SeqCst),
+            ),
+            (
+                "http_rate_limited_total",
+                "Connections and requests answered with 429 by the rate limit.",
+                self.rate_limited.load(Ordering::SeqCst),
+            ),
+        ];
+        if let Some(cache) = &*self.file_cache.lock().unwrap() {
+            counters.push((
+                "file_cache_hits_total",
+                "Static files served from memory.",
+                cache.hits(),
+            ));
+            counters.push((
+                "file_cache_misses_total",
+                "Static files read from disk into the cache.",
+                cache.misses(),
+            ));
+        }
+        counters
+    }
+
+    /// The stats in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        text.push_str("# HELP http_requests_total Requests answered, by status code.\n");
+        text.push_str("# TYPE http_requests_total counter\n");
+        for (status, count) in self.status_counts() {
+            let _ = writeln!(text, "http_requests_total{{code=\"{}\"}} {}", status, count);
+        }
+
+        for (name, help, value) in self.counters() {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} counter", name);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+        for (name, help, value) in self.gauges() {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} gauge", name);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+
+        text.push_str(
+            "# HELP http_request_duration_seconds Time from reading a request to sending \
+             the response.\n",
+        );
+        text.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (bound, count) in self.latency_histogram() {
+            let bound = bound.map_or("+Inf".to_string(), |bound| bound.to_string());
+            let _ = writeln!(
+                text,
+                "http_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, count
+            );
+        }
+        let _ = writeln!(
+            text,
+            "http_request_duration_seconds_sum {}",
+            self.latency_seconds()
+        );
+        let _ = writeln!(
+            text,
+            "http_request_duration_seconds_count {}",
+            self.requests()
+        );
+        text
+    }
+
+    /// The same stats as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"requests_total\":{}", self.requests());
+
+        let statuses: Vec<String> = self
+            .status_counts()
+            .iter()
+            .map(|(status, count)| format!("\"{}\":{}", status, count))
+            .collect();
+        let _ = write!(json, ",\"statuses\":{{{}}}", statuses.join(","));
+
+        for (name, _, value) in self.counters().into_iter().chain(self.gauges()) {
+            let _ = write!(json, ",\"{}\":{}", name, value);
+        }
+
+        let buckets: Vec<String> = self
+            .latency_histogram()
+            .iter()
+            .map(|(bound, count)| match bound {
+                Some(bound) => format!("{{\"le\":{},\"count\":{}}}", bound, count),
+                None => format!("{{\"le\":null,\"count\":{}}}", count),
+            })
+            .collect();
+        let _ = write!(
+            json,
+            ",\"request_duration_seconds\":{{\"buckets\":[{}],\"sum\":{},\"count\":{}}}}}",
+            buckets.join(","),
+            self.latency_seconds(),
+            self.requests()
+        );
+        json
+    }
+}
+
+/// The metrics endpoint: Prometheus text by default, JSON for `?format=json`
+/// or a client that only accepts JSON.
+pub(crate) fn metrics_response(stats: &ServerStats, request: &Request) -> Response {
+    let wants_json = request.query().get("format") == Some("json")
+        || request
+            .header("Accept")
+            .is_some_and(|accept| accept.trim().eq_ignore_ascii_case("application/json"));
+    let mut response = if wants_json {
+        Response::json(&stats.to_json())
+    } else {
+        let mut response = Response::text(&stats.to_prometheus());
+        response.set_header("Content-Type", "text/plain; version=0.0.4; charset=utf-8");
+        response
+    };
+    response.set_header("Cache-Control", "no-store");
+    response
+}
+
+/// The health check endpoint: a server that can answer is healthy.
+pub(crate) fn health_response() -> Response {
+    let mut response = Response::text("ok\n");
+    response.set_header("Cache-Control", "no-store");
+    response
+}
+
+/// Passes writes through to `inner`, counting the bytes that went out.
+pub(crate) struct CountingWriter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W) -> CountingWriter<W> {
+        CountingWriter { inner, count: 0 }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
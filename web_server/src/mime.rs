@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+const DEFAULT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("xml", "application/xml"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Maps file extensions to `Content-Type` values. Starts with common web
+/// types; custom mappings override them.
+#[derive(Clone, Debug)]
+pub struct MimeTypes {
+    by_extension: HashMap<String, String>,
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        MimeTypes {
+            by_extension: DEFAULT_TYPES
+                .iter()
+                .map(|(extension, mime)| (extension.to_string(), mime.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl MimeTypes {
+    pub fn new() -> MimeTypes {
+        MimeTypes::default()
+    }
+
+    /// Registers `mime` for files ending in `.extension` (leading dot
+    /// optional, case-insensitive).
+    pub fn insert(&mut self, extension: &str, mime: &str) {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.by_extension.insert(extension, mime.to_string());
+    }
+
+    pub fn get(&self, extension: &str) -> Option<&str> {
+        self.by_extension
+            .get(&extension.to_ascii_lowercase())
+            .map(|mime| mime.as_str())
+    }
+
+    /// The type for `path`, falling back to `application/octet-stream`.
+    pub fn for_path(&self, path: &Path) -> &str {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.get(extension))
+            .unwrap_or(DEFAULT_MIME_TYPE)
+    }
+}
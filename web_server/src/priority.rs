@@ -0,0 +1,103 @@
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::reload::LiveSettings;
+use crate::router::RouteMatch;
+use crate::static_path::{resolve_decoded_path, PathResolution};
+use crate::Method;
+use crate::Priority;
+use crate::Request;
+
+/// How long a connection may wait for its request line before it is
+/// queued without one.
+pub(crate) const PEEK_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Static files up to this size are served ahead of other requests.
+const SMALL_FILE_SIZE: u64 = 64 * 1024;
+
+/// Picks the pool priority of a connection from its first request line,
+/// peeked at without consuming it: small static files and the monitoring
+/// endpoints go first, routed handlers, such as dynamic pages and proxied
+/// requests, go last. Virtual hosts are not looked at, since the headers
+/// may not have arrived yet. Encrypted connections stay `Normal`, as do
+/// ones whose request line cannot be read. `None` means the line has not
+/// arrived yet; the peek never waits for it. Later requests on a persistent
+/// connection keep the priority of the first.
+pub(crate) fn connection_priority(stream: &TcpStream, settings: &LiveSettings) -> Option<Priority> {
+    let settings = settings.load();
+    let config = &settings.config;
+    #[cfg(feature = "tls")]
+    if config.tls.is_some() {
+        return Some(Priority::Normal);
+    }
+
+    let request = match peek_request_line(stream) {
+        Peeked::Request(request) => request,
+        Peeked::Pending => return None,
+        Peeked::Unusable => return Some(Priority::Normal),
+    };
+    let is_monitoring = [&config.health_path, &config.metrics_path]
+        .into_iter()
+        .flatten()
+        .any(|path| request.path() == path);
+    if is_monitoring {
+        return Some(Priority::High);
+    }
+    Some(match settings.router.find(&request) {
+        RouteMatch::Handler(_) => Priority::Low,
+        RouteMatch::Static if is_small_file(&config.content_dir, &request) => Priority::High,
+        _ => Priority::Normal,
+    })
+}
+
+enum Peeked {
+    Request(Request),
+    /// The request line is still on its way.
+    Pending,
+    Unusable,
+}
+
+fn peek_request_line(stream: &TcpStream) -> Peeked {
+    let mut buffer = [0; 2048];
+    if stream.set_nonblocking(true).is_err() {
+        return Peeked::Unusable;
+    }
+    let peeked = stream.peek(&mut buffer);
+    if stream.set_nonblocking(false).is_err() {
+        return Peeked::Unusable;
+    }
+
+    let peeked = match peeked {
+        Ok(len) => &buffer[..len],
+        Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => return Peeked::Pending,
+        Err(_) => return Peeked::Unusable,
+    };
+    match peeked.iter().position(|byte| *byte == b'\n') {
+        Some(line_end) => match parse_request_line(&peeked[..line_end]) {
+            Some(request) => Peeked::Request(request),
+            None => Peeked::Unusable,
+        },
+        // Nothing at all means the client has closed the connection.
+        None if !peeked.is_empty() && peeked.len() < buffer.len() => Peeked::Pending,
+        None => Peeked::Unusable,
+    }
+}
+
+fn parse_request_line(line: &[u8]) -> Option<Request> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.trim_end().split(' ');
+    let method = Method::parse(parts.next()?)?;
+    Request::new(method, parts.next()?).ok()
+}
+
+fn is_small_file(content_dir: &Path, request: &Request) -> bool {
+    if !matches!(request.method(), Method::Get | Method::Head) {
+        return false;
+    }
+    match resolve_decoded_path(content_dir, request.path()) {
+        PathResolution::Found(path) => std::fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() <= SMALL_FILE_SIZE),
+        _ => false,
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::error_page;
+use crate::html_error_code_to_str;
+use crate::request::is_token;
+use crate::Headers;
+use crate::Method;
+use crate::Request;
+use crate::Response;
+use crate::Result;
+use crate::WebServerError;
+
+/// Upper bound for the status line plus headers of an upstream response.
+const MAX_RESPONSE_HEAD: u64 = 64 * 1024;
+
+/// Headers that only concern one connection and are never forwarded
+/// (RFC 9110, section 7.6.1).
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "Proxy-Connection",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+/// Forwards requests to an upstream HTTP server and streams its responses
+/// back. Mount it with `Router::proxy`.
+#[derive(Clone, Debug)]
+pub struct ProxyHandler {
+    upstream: String,
+    connect_timeout: Duration,
+    timeout: Duration,
+}
+
+impl ProxyHandler {
+    /// Forwards to `upstream`, given as `host:port`.
+    pub fn new(upstream: impl Into<String>) -> ProxyHandler {
+        ProxyHandler {
+            upstream: upstream.into(),
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> ProxyHandler {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// How long a single read from or write to the upstream may block.
+    pub fn timeout(mut self, timeout: Duration) -> ProxyHandler {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Answers `request` with the upstream's response, or with `502`/`504`
+    /// if the upstream cannot be reached or does not answer in time.
+    pub fn handle(&self, request: &Request) -> Response {
+        match self.forward(request) {
+            Ok(response) => response,
+            Err(error) => {
+                println!("Proxying to {} failed: {}", self.upstream, error);
+                match error {
+                    WebServerError::Timeout(_) => error_page(
+                        504,
+                        "Gateway Timeout",
+                        "The upstream server did not answer in time",
+                    ),
+                    _ => error_page(
+                        502,
+                        "Bad Gateway",
+                        "The upstream server could not be reached",
+                    ),
+                }
+            }
+        }
+    }
+
+    fn forward(&self, request: &Request) -> Result<Response> {
+        let mut upstream = self.connect()?;
+        upstream.set_read_timeout(Some(self.timeout))?;
+        upstream.set_write_timeout(Some(self.timeout))?;
+
+        upstream.write_all(&upstream_request_head(request))?;
+        upstream.write_all(request.body())?;
+
+        let mut reader = BufReader::new(upstream);
+        let (status, headers) = loop {
+            let (status, headers) = read_response_head(&mut reader)?;
+            // Interim responses such as 103 Early Hints are not passed on.
+            if !(100..200).contains(&status) {
+                break (status, headers);
+            }
+        };
+        if html_error_code_to_str(status.into()).is_err() {
+            return Err(WebServerError::Internal(format!(
+                "Upstream answered with unsupported status {}",
+                status
+            )));
+        }
+
+        let mut response = Response::new(status);
+        let connection_headers = listed_in_connection(&headers);
+        for (name, value) in headers.iter() {
+            // A chunked body is passed through as it is, so its header stays.
+            let keep = name.eq_ignore_ascii_case("Transfer-Encoding")
+                || !is_hop_by_hop(name, &connection_headers);
+            if keep {
+                response.append_header(name, value);
+            }
+        }
+
+        let has_body = request.method() != Method::Head && !matches!(status, 204 | 304);
+        if has_body {
+            if headers.contains("Transfer-Encoding") {
+                // The upstream closes the connection after the last chunk.
+                response.set_body_reader(reader, None);
+            } else {
+                let len = headers
+                    .get("Content-Length")
+                    .and_then(|len| len.trim().parse().ok());
+                response.set_body_reader(reader, len);
+            }
+        }
+        Ok(response)
+    }
+
+    fn connect(&self) -> Result<TcpStream> {
+        let mut last_error = None;
+        for address in self.upstream.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.connect_timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(match last_error {
+            Some(error) => error.into(),
+            None => WebServerError::Config(format!("{} resolves to no address", self.upstream)),
+        })
+    }
+}
+
+/// The request line and headers sent upstream. The body is already read in
+/// full, so it always goes with a `Content-Length`.
+fn upstream_request_head(request: &Request) -> Vec<u8> {
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\n",
+        request.method().as_str(),
+        request.target()
+    );
+
+    let connection_headers = listed_in_connection(request.headers());
+    let skipped = [
+        "Content-Length",
+        "Expect",
+        "X-Forwarded-For",
+        "X-Forwarded-Proto",
+    ];
+    for (name, value) in request.headers().iter() {
+        let skip = is_hop_by_hop(name, &connection_headers)
+            || skipped
+                .iter()
+                .any(|skipped| skipped.eq_ignore_ascii_case(name));
+        if !skip {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+
+    let previous_hops = request.headers().get_all("X-Forwarded-For");
+    let mut forwarded_for: Vec<String> = previous_hops.map(str::to_string).collect();
+    if let Some(peer) = request.peer_addr() {
+        forwarded_for.push(peer.ip().to_string());
+    }
+    if !forwarded_for.is_empty() {
+        head.push_str(&format!(
+            "X-Forwarded-For: {}\r\n",
+            forwarded_for.join(", ")
+        ));
+    }
+    let proto = if request.is_secure() { "https" } else { "http" };
+    head.push_str(&format!("X-Forwarded-Proto: {}\r\n", proto));
+
+    if !request.body().is_empty() || request.headers().contains("Content-Length") {
+        head.push_str(&format!("Content-Length: {}\r\n", request.body().len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    head.into_bytes()
+}
+
+/// Names listed in the `Connection` header, which are hop-by-hop as well.
+fn listed_in_connection(headers: &Headers) -> Vec<String> {
+    headers
+        .get_all("Connection")
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn is_hop_by_hop(name: &str, connection_headers: &[String]) -> bool {
+    HOP_BY_HOP_HEADERS
+        .iter()
+        .any(|hop| hop.eq_ignore_ascii_case(name))
+        || connection_headers
+            .iter()
+            .any(|listed| listed.eq_ignore_ascii_case(name))
+}
+
+fn read_response_head(reader: &mut impl BufRead) -> Result<(u16, Headers)> {
+    let mut reader = reader.take(MAX_RESPONSE_HEAD);
+    let status_line = read_line(&mut reader)?;
+    let status = status_line
+        .strip_prefix("HTTP/1.")
+        .and_then(|rest| rest.split(' ').nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| {
+            WebServerError::Internal(format!("Invalid upstream status line {:?}", status_line))
+        })?;
+
+    let mut headers = Headers::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            return Ok((status, headers));
+        }
+        let invalid_header =
+            || WebServerError::Internal(format!("Invalid upstream header line {:?}", line));
+        let (name, value) = line.split_once(':').ok_or_else(invalid_header)?;
+        if !is_token(name) {
+            return Err(invalid_header());
+        }
+        headers.append(name, value.trim());
+    }
+}
+
+/// One line without its line break. Running out of input before the head
+/// is complete is an error.
+fn read_line(reader: &mut impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(WebServerError::Internal(
+            "Upstream response head ended early".to_string(),
+        ));
+    }
+    let line = line.strip_suffix('\n').ok_or_else(|| {
+        WebServerError::Internal("Upstream response head is too large".to_string())
+    })?;
+    Ok(line.strip_suffix('\r').unwrap_or(line).to_string())
+}
@@ -0,0 +1,56 @@
+use crate::percent_encoding::percent_decode;
+
+/// Parameters of a query string (`?a=1&b=two`), in order of appearance.
+/// Names and values are percent-decoded and `+` is read as a space.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    params: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    pub fn parse(query: &str) -> QueryParams {
+        let params = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(name), decode(value))
+            })
+            .collect();
+        QueryParams { params }
+    }
+
+    /// The first value of the parameter `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.params
+            .iter()
+            .filter(move |(existing, _)| existing == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// Invalid escapes are kept literally rather than rejecting the request.
+fn decode(component: &str) -> String {
+    let component = component.replace('+', " ");
+    match percent_decode(&component) {
+        Some(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        None => component,
+    }
+}
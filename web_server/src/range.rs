@@ -0,0 +1,68 @@
+/// What a `Range` header asks for, relative to a representation of a known
+/// length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// No usable range: the whole representation is sent with `200`.
+    Full,
+    /// The inclusive byte range `start..=end`, sent with `206`.
+    Partial { start: u64, end: u64 },
+    /// No byte of the range exists, answered with `416`.
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range against a body of `len` bytes.
+///
+/// Headers that are malformed, use another unit, or list several ranges are
+/// ignored, so the full body is served; the standard allows that.
+pub fn parse_range(header: &str, len: u64) -> RangeRequest {
+    let spec = match header.trim().split_once('=') {
+        Some((unit, spec)) if unit.trim().eq_ignore_ascii_case("bytes") => spec.trim(),
+        _ => return RangeRequest::Full,
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let (first, last) = match spec.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => return RangeRequest::Full,
+    };
+
+    if first.is_empty() {
+        // `bytes=-N` asks for the last N bytes.
+        let suffix: u64 = match last.parse() {
+            Ok(suffix) => suffix,
+            Err(_) => return RangeRequest::Full,
+        };
+        if suffix == 0 || len == 0 {
+            return RangeRequest::Unsatisfiable;
+        }
+        return RangeRequest::Partial {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        };
+    }
+
+    let start: u64 = match first.parse() {
+        Ok(start) => start,
+        Err(_) => return RangeRequest::Full,
+    };
+    let end = if last.is_empty() {
+        u64::MAX
+    } else {
+        match last.parse() {
+            Ok(end) => end,
+            Err(_) => return RangeRequest::Full,
+        }
+    };
+    if end < start {
+        return RangeRequest::Full;
+    }
+    if start >= len {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Partial {
+        start,
+        end: end.min(len - 1),
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Limits on what a single client IP address may use of a server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    /// Connections one address may have open at once. Further connections
+    /// are answered with `429` right away, before they take up a worker.
+    pub max_connections_per_ip: Option<usize>,
+    /// Requests one address may send per second on average, across all of
+    /// its connections. Requests beyond that are answered with `429`.
+    pub requests_per_second: Option<f64>,
+    /// Requests an address may send in a burst on top of the average rate,
+    /// i.e. the capacity of its token bucket.
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            max_connections_per_ip: None,
+            requests_per_second: None,
+            burst: 10,
+        }
+    }
+}
+
+/// Per-address state of a `RateLimit`, shared by the accept loops and the
+/// workers of one server.
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    peers: Mutex<Peers>,
+}
+
+struct Peers {
+    by_ip: HashMap<IpAddr, PeerState>,
+    last_sweep: Instant,
+}
+
+struct PeerState {
+    connections: usize,
+    /// Requests the address may still send right now.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl PeerState {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        if let Some(rate) = limit.requests_per_second {
+            let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate).min(limit.burst as f64);
+        }
+        self.refilled_at = now;
+    }
+
+    /// Whether forgetting the address would change nothing.
+    fn is_idle(&self, limit: &RateLimit) -> bool {
+        self.connections == 0 && self.tokens >= limit.burst as f64
+    }
+}
+
+/// Held for as long as a connection is open. Dropping it releases the slot
+/// of the address.
+pub(crate) struct ConnectionPermit {
+    limiter: Arc<RateLimiter>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.limiter.release(self.ip);
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> RateLimiter {
+        RateLimiter {
+            limit,
+            peers: Mutex::new(Peers {
+                by_ip: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a connection slot for `ip`, or `None` if it already has as many
+    /// connections as allowed.
+    pub fn connect(self: &Arc<Self>, ip: IpAddr) -> Option<ConnectionPermit> {
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers.by_ip.entry(ip).or_insert_with(|| self.new_peer());
+        if let Some(max) = self.limit.max_connections_per_ip {
+            if peer.connections >= max {
+                return None;
+            }
+        }
+        peer.connections += 1;
+        Some(ConnectionPermit {
+            limiter: Arc::clone(self),
+            ip,
+        })
+    }
+
+    /// Takes a token for one request from `ip`. If there is none left, returns
+    /// how long until there is.
+    pub fn check_request(&self, ip: IpAddr) -> Result<(), Duration> {
+        let rate = match self.limit.requests_per_second {
+            Some(rate) => rate,
+            None => return Ok(()),
+        };
+        let now = Instant::now();
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers.by_ip.entry(ip).or_insert_with(|| self.new_peer());
+        peer.refill(&self.limit, now);
+        if peer.tokens >= 1.0 {
+            peer.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - peer.tokens) / rate))
+        }
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let now = Instant::now();
+        let mut peers = self.peers.lock().unwrap();
+        if let Some(peer) = peers.by_ip.get_mut(&ip) {
+            peer.connections = peer.connections.saturating_sub(1);
+            peer.refill(&self.limit, now);
+            if peer.is_idle(&self.limit) {
+                peers.by_ip.remove(&ip);
+            }
+        }
+
+        // Addresses whose bucket was still refilling when their last
+        // connection closed are dropped here once it is full again.
+        if now.duration_since(peers.last_sweep) >= Duration::from_secs(1) {
+            peers.last_sweep = now;
+            peers.by_ip.retain(|_, peer| {
+                peer.refill(&self.limit, now);
+                !peer.is_idle(&self.limit)
+            });
+        }
+    }
+
+    fn new_peer(&self) -> PeerState {
+        PeerState {
+            connections: 0,
+            tokens: self.limit.burst as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+}
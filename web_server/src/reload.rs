@@ -0,0 +1,92 @@
+use std::sync::{Arc, RwLock};
+
+use crate::Router;
+use crate::ServerConfig;
+
+/// What a request is served with. Replaced as a whole by a reload, so a
+/// request never sees the config of one reload and the router of another.
+pub(crate) struct Settings {
+    pub config: ServerConfig,
+    pub router: Arc<Router>,
+}
+
+/// The settings of a running server. Each request takes a snapshot when it
+/// starts and keeps it until it is answered, so a reload only affects
+/// requests that start after it.
+pub(crate) struct LiveSettings {
+    current: RwLock<Arc<Settings>>,
+    /// Whether the health check and metrics routes are mounted on the
+    /// router, as opposed to served by a listener of their own.
+    pub monitoring_routes: bool,
+}
+
+impl LiveSettings {
+    pub fn new(settings: Settings, monitoring_routes: bool) -> LiveSettings {
+        LiveSettings {
+            current: RwLock::new(Arc::new(settings)),
+            monitoring_routes,
+        }
+    }
+
+    /// The settings current right now. The lock is only held for cloning the
+    /// `Arc`, so a reload never waits for a request to finish.
+    pub fn load(&self) -> Arc<Settings> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    pub fn store(&self, settings: Settings) {
+        *self.current.write().unwrap() = Arc::new(settings);
+    }
+}
+
+/// Turns SIGHUP into a byte on a socket, which a regular thread can wait for.
+/// Writing to a socket is one of the few things a signal handler may do.
+#[cfg(all(unix, feature = "signals"))]
+pub(crate) mod sighup {
+    use std::os::fd::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use crate::Result;
+    use crate::WebServerError;
+
+    static NOTIFY_FD: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn on_sighup(_: libc::c_int) {
+        let fd = NOTIFY_FD.load(Ordering::SeqCst);
+        if fd >= 0 {
+            // Non-blocking: with a byte already pending, another one adds
+            // nothing.
+            unsafe { libc::write(fd, [1u8].as_ptr().cast(), 1) };
+        }
+    }
+
+    /// Installs the handler and returns the end that becomes readable on
+    /// every SIGHUP. Can only be done once per process.
+    pub fn install() -> Result<UnixStream> {
+        let (notified, notify) = UnixStream::pair()?;
+        notify.set_nonblocking(true)?;
+        let fd = notify.into_raw_fd();
+        if NOTIFY_FD
+            .compare_exchange(-1, fd, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            unsafe { libc::close(fd) };
+            return Err(WebServerError::Config(
+                "A SIGHUP handler is already installed.".to_string(),
+            ));
+        }
+
+        let installed = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sighup as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGHUP, &action, std::ptr::null_mut())
+        };
+        if installed != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(notified)
+    }
+}
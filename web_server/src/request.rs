@@ -0,0 +1,510 @@
+use std::io::{BufRead, Read};
+use std::net::SocketAddr;
+
+use crate::chunked::read_chunked_body;
+use crate::Headers;
+use crate::QueryParams;
+use crate::RequestTarget;
+use crate::Result;
+use crate::WebServerError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+        }
+    }
+
+    pub(crate) fn parse(method: &str) -> Option<Method> {
+        match method {
+            "GET" => Some(Method::Get),
+            "HEAD" => Some(Method::Head),
+            "POST" => Some(Method::Post),
+            "PUT" => Some(Method::Put),
+            "DELETE" => Some(Method::Delete),
+            "OPTIONS" => Some(Method::Options),
+            _ => None,
+        }
+    }
+}
+
+/// Protocol versions requests may use. Responses are sent as HTTP/1.1,
+/// which HTTP/1.0 clients understand as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        }
+    }
+
+    fn parse(version: &str) -> Option<Version> {
+        match version {
+            "HTTP/1.0" => Some(Version::Http10),
+            "HTTP/1.1" => Some(Version::Http11),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed HTTP request as seen by handlers.
+pub struct Request {
+    pub(crate) method: Method,
+    pub(crate) target: String,
+    pub(crate) version: Version,
+    /// Decoded and normalized path of `target`.
+    pub(crate) path: String,
+    pub(crate) query: QueryParams,
+    pub(crate) headers: Headers,
+    pub(crate) body: Vec<u8>,
+    pub(crate) peer: Option<SocketAddr>,
+    pub(crate) secure: bool,
+}
+
+impl Request {
+    /// A request built in code rather than read from a connection, e.g. to
+    /// pass to `HttpServer::handle`. The target is parsed as it would be on
+    /// the request line, so a path above the root is refused the same way.
+    pub fn new(method: Method, target: &str) -> Result<Request> {
+        let parsed_target = RequestTarget::parse(target)?;
+        Ok(Request {
+            method,
+            target: target.to_string(),
+            version: Version::Http11,
+            path: parsed_target.path().to_string(),
+            query: parse_query(parsed_target.query()),
+            headers: Headers::new(),
+            body: Vec::new(),
+            peer: None,
+            secure: false,
+        })
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// The request target exactly as it appeared in the request line.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// The path of the request target, percent-decoded and normalized as
+    /// described for `RequestTarget`. Routes and static files are matched
+    /// against this form.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The query string of the target without the `?`, still encoded.
+    pub fn query_string(&self) -> Option<&str> {
+        self.target
+            .split('#')
+            .next()
+            .and_then(|target| target.split_once('?'))
+            .map(|(_, query)| query)
+    }
+
+    /// Parameters of the query string, if any.
+    pub fn query(&self) -> &QueryParams {
+        &self.query
+    }
+
+    /// Headers in the order they were received.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Lets middleware add, replace or strip headers before the handler runs.
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// Rewrites the request target, e.g. to route a legacy path to a new
+    /// handler. The path and query string are parsed again; a target that
+    /// does not parse leaves its path as it is, without decoding it.
+    pub fn set_target(&mut self, target: &str) {
+        self.target = target.to_string();
+        self.path = match RequestTarget::parse(target) {
+            Ok(parsed) => parsed.path().to_string(),
+            Err(_) => target.split(['?', '#']).next().unwrap_or("").to_string(),
+        };
+        self.query = parse_query(self.query_string());
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Replaces the body. Headers such as `Content-Length` are left as they
+    /// are.
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.body = body;
+    }
+
+    /// Address of the client the request came from.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// Whether the request arrived over TLS.
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+}
+
+pub(crate) struct RequestHead {
+    pub(crate) method: String,
+    pub(crate) target: String,
+    pub(crate) version: Version,
+    pub(crate) headers: Headers,
+}
+
+impl RequestHead {
+    /// Whether the client holds back the body until it gets a `100
+    /// Continue` (RFC 9110, section 10.1.1). HTTP/1.0 clients cannot ask for
+    /// one, so their `Expect` is ignored.
+    fn expects_continue(&self) -> bool {
+        self.version == Version::Http11
+            && self
+                .headers
+                .get("Expect")
+                .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"))
+    }
+}
+
+/// Bounds on a single request.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RequestLimits {
+    /// Head plus body, in bytes.
+    pub max_request_size: usize,
+    /// The body on its own, after removing any chunked framing.
+    pub max_body_size: usize,
+    /// Request line plus all header lines, in bytes.
+    pub max_header_bytes: usize,
+    pub max_header_count: usize,
+    /// The request target as sent, in bytes.
+    pub max_uri_length: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            max_request_size: 1024 * 1024,
+            max_body_size: 1024 * 1024,
+            max_header_bytes: 16 * 1024,
+            max_header_count: 100,
+            max_uri_length: 8 * 1024,
+        }
+    }
+}
+
+/// Room on the request line for the method, the version and the separators
+/// around the target.
+const REQUEST_LINE_OVERHEAD: usize = 32;
+
+/// Raised by line reads for header lines that are not UTF-8.
+fn head_read_error(error: std::io::Error) -> WebServerError {
+    match error.kind() {
+        std::io::ErrorKind::InvalidData => {
+            WebServerError::BadRequest("Request head is not valid UTF-8".to_string())
+        }
+        _ => error.into(),
+    }
+}
+
+/// Reads the request line and headers. `reader` is expected to limit the
+/// size of the whole head; the length of the request line and the header
+/// count are checked here, the former before the line is read to its end.
+pub(crate) fn read_request_head(
+    reader: &mut impl BufRead,
+    limits: &RequestLimits,
+) -> Result<RequestHead> {
+    let max_request_line = limits.max_uri_length + REQUEST_LINE_OVERHEAD;
+    let mut request_line = String::new();
+    let mut limited = reader.by_ref().take(max_request_line as u64);
+    limited
+        .read_line(&mut request_line)
+        .map_err(head_read_error)?;
+    if !request_line.ends_with('\n') && limited.limit() == 0 {
+        return Err(uri_too_long(limits));
+    }
+    let request_line = request_line
+        .strip_suffix('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .unwrap_or(&request_line)
+        .to_string();
+
+    let lines = {
+        let mut lines = vec![request_line];
+        let mut complete = false;
+        for result in reader.lines() {
+            let line = result.map_err(head_read_error)?;
+            if line.is_empty() {
+                complete = true;
+                break;
+            }
+            // The request line does not count as a header.
+            if lines.len() > limits.max_header_count {
+                return Err(WebServerError::HeaderFieldsTooLarge(format!(
+                    "More than {} header fields",
+                    limits.max_header_count
+                )));
+            }
+            lines.push(line);
+        }
+
+        if !complete {
+            return Err(WebServerError::BadRequest(
+                "Connection closed before the end of the request head".to_string(),
+            ));
+        }
+
+        lines
+    };
+
+    let invalid_request_line = || WebServerError::BadRequest("Invalid request line".to_string());
+    let request_line = lines.first().ok_or_else(invalid_request_line)?;
+    let mut tokens_iter = request_line.split(' ');
+
+    let method = tokens_iter.next().ok_or_else(invalid_request_line)?;
+    let target = tokens_iter.next().ok_or_else(invalid_request_line)?;
+    let http_ver = tokens_iter.next().ok_or_else(invalid_request_line)?;
+    if tokens_iter.next().is_some() || method.is_empty() || target.is_empty() {
+        return Err(invalid_request_line());
+    }
+    if target.len() > limits.max_uri_length {
+        return Err(uri_too_long(limits));
+    }
+    let version = Version::parse(http_ver).ok_or_else(|| {
+        WebServerError::VersionNotSupported(format!(
+            "Expected HTTP/1.0 or HTTP/1.1, got {}",
+            http_ver
+        ))
+    })?;
+
+    let mut headers = Headers::new();
+    for line in &lines[1..] {
+        let invalid_header =
+            || WebServerError::BadRequest(format!("Invalid header line {:?}", line));
+        // Whitespace before the colon and lines continuing the previous one
+        // (obs-fold) are refused, as a proxy in front of the server may read
+        // them differently, e.g. one not seeing `Content-Length : 5`.
+        let (name, value) = line.split_once(':').ok_or_else(invalid_header)?;
+        if !is_token(name) {
+            return Err(invalid_header());
+        }
+        headers.append(name, value.trim());
+    }
+
+    Ok(RequestHead {
+        method: method.to_string(),
+        target: target.to_string(),
+        version,
+        headers,
+    })
+}
+
+/// Whether `name` is a token, the only form a header name may take.
+pub(crate) fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn uri_too_long(limits: &RequestLimits) -> WebServerError {
+    WebServerError::UriTooLong(format!(
+        "Request target exceeds {} bytes",
+        limits.max_uri_length
+    ))
+}
+
+/// How the end of a request body is found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BodyFraming {
+    Empty,
+    Length(u64),
+    Chunked,
+}
+
+/// Works out the framing of the body from `Content-Length` or
+/// `Transfer-Encoding: chunked`, before any of it is read. A body announced
+/// to be longer than `max_size` is refused right away.
+fn body_framing(head: &RequestHead, max_size: u64) -> Result<BodyFraming> {
+    if let Some(encoding) = transfer_encoding(&head.headers) {
+        // Both at once is a classic request smuggling vector (RFC 9112,
+        // section 6.1), so it is refused rather than resolved.
+        if head.headers.contains("Content-Length") {
+            return Err(WebServerError::BadRequest(
+                "Both Transfer-Encoding and Content-Length are set".to_string(),
+            ));
+        }
+        // Chunked has to be the final coding, otherwise the end of the body
+        // is unknown. Other codings are not decoded.
+        if encoding != ["chunked"] {
+            return Err(WebServerError::NotImplemented(format!(
+                "Unsupported Transfer-Encoding {:?}",
+                encoding.join(", ")
+            )));
+        }
+        return Ok(BodyFraming::Chunked);
+    }
+
+    let length = match content_length(&head.headers)? {
+        Some(length) => length,
+        None => return Ok(BodyFraming::Empty),
+    };
+    if length > max_size {
+        return Err(WebServerError::PayloadTooLarge(format!(
+            "Request body of {} bytes exceeds the limit",
+            length
+        )));
+    }
+    Ok(match length {
+        0 => BodyFraming::Empty,
+        length => BodyFraming::Length(length),
+    })
+}
+
+/// The value of every `Content-Length` field, which may repeat or be a
+/// list, as long as all of them agree (RFC 9110, section 8.6). Only digits
+/// are allowed, `parse` would take a sign too.
+fn content_length(headers: &Headers) -> Result<Option<u64>> {
+    let mut length = None;
+    for value in headers.get_all("Content-Length") {
+        for value in value.split(',').map(str::trim) {
+            let parsed = Some(value)
+                .filter(|value| {
+                    !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit())
+                })
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    WebServerError::BadRequest(format!("Invalid Content-Length {:?}", value))
+                })?;
+            if length.is_some_and(|length| length != parsed) {
+                return Err(WebServerError::BadRequest(
+                    "Conflicting Content-Length values".to_string(),
+                ));
+            }
+            length = Some(parsed);
+        }
+    }
+    Ok(length)
+}
+
+/// Reads the body from the same reader the head was read from. Fails as
+/// soon as a chunked body turns out to be longer than `max_size`.
+fn read_body(reader: &mut impl BufRead, framing: BodyFraming, max_size: u64) -> Result<Vec<u8>> {
+    let length = match framing {
+        BodyFraming::Empty => return Ok(Vec::new()),
+        BodyFraming::Chunked => return read_chunked_body(reader, max_size),
+        BodyFraming::Length(length) => length,
+    };
+
+    let mut body = Vec::new();
+    reader.take(length).read_to_end(&mut body)?;
+    if (body.len() as u64) < length {
+        return Err(WebServerError::BadRequest(format!(
+            "Expected {} bytes of body, got {}",
+            length,
+            body.len()
+        )));
+    }
+
+    Ok(body)
+}
+
+fn transfer_encoding(headers: &Headers) -> Option<Vec<String>> {
+    if !headers.contains("Transfer-Encoding") {
+        return None;
+    }
+    let codings = headers
+        .get_all("Transfer-Encoding")
+        .flat_map(|value| value.split(','))
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty())
+        .collect();
+    Some(codings)
+}
+
+/// Reads one request within `limits`. `send_continue` is called with the
+/// reader before the body is read if the client waits for a `100 Continue`,
+/// unless the body is refused already on its announced length.
+pub(crate) fn read_request<R: BufRead>(
+    reader: &mut R,
+    limits: &RequestLimits,
+    send_continue: impl FnOnce(&mut R) -> Result<()>,
+) -> Result<Request> {
+    let head_limit = limits.max_header_bytes.min(limits.max_request_size) as u64;
+    let mut limited = reader.by_ref().take(head_limit);
+    let head = match read_request_head(&mut limited, limits) {
+        Ok(head) => head,
+        Err(error @ WebServerError::UriTooLong(_)) => return Err(error),
+        Err(_) if limited.limit() == 0 => {
+            return Err(WebServerError::HeaderFieldsTooLarge(format!(
+                "Request head exceeds {} bytes",
+                head_limit
+            )))
+        }
+        Err(error) => return Err(error),
+    };
+    let head_size = head_limit - limited.limit();
+
+    let method = Method::parse(&head.method).ok_or_else(|| {
+        WebServerError::NotImplemented(format!("Unsupported (or invalid) method {}", head.method))
+    })?;
+    let parsed_target = RequestTarget::parse(&head.target)?;
+    let remaining = limits.max_request_size as u64 - head_size;
+    let max_body_size = remaining.min(limits.max_body_size as u64);
+    let framing = body_framing(&head, max_body_size)?;
+    if framing != BodyFraming::Empty && head.expects_continue() {
+        send_continue(reader)?;
+    }
+    let body = read_body(reader, framing, max_body_size)?;
+
+    Ok(Request {
+        method,
+        path: parsed_target.path().to_string(),
+        query: parse_query(parsed_target.query()),
+        target: head.target,
+        version: head.version,
+        headers: head.headers,
+        body,
+        peer: None,
+        secure: false,
+    })
+}
+
+fn parse_query(query: Option<&str>) -> QueryParams {
+    query.map(QueryParams::parse).unwrap_or_default()
+}
@@ -0,0 +1,607 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Mutex, PoisonError};
+
+use crate::chunked::{copy_unchunked, ChunkedWriter};
+use crate::html_error_code_to_str;
+use crate::websocket::UpgradeHandler;
+use crate::ConvertibleToResult;
+use crate::Headers;
+use crate::Result;
+use crate::WebServerError;
+
+/// Header names whose canonical spelling is not simple title case.
+const KNOWN_HEADERS: &[&str] = &[
+    "Content-MD5",
+    "DNT",
+    "ETag",
+    "TE",
+    "WWW-Authenticate",
+    "X-XSS-Protection",
+    "X-UA-Compatible",
+    "Sec-WebSocket-Accept",
+    "Sec-WebSocket-Extensions",
+    "Sec-WebSocket-Key",
+    "Sec-WebSocket-Protocol",
+    "Sec-WebSocket-Version",
+];
+
+/// How header names are spelled when a response is serialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HeaderCasing {
+    /// Canonical HTTP casing: `content-type` becomes `Content-Type`.
+    #[default]
+    Canonical,
+    /// Header names are written exactly as they were set.
+    AsSet,
+}
+
+enum Body {
+    Bytes(Vec<u8>),
+    /// `len` bytes from `start` on, copied from the file to the connection
+    /// as the response is written.
+    File {
+        file: File,
+        start: u64,
+        len: u64,
+    },
+    /// Copied from `reader` as the response is written: `len` bytes, or
+    /// everything up to the end of the reader if the length is unknown.
+    Stream {
+        reader: Mutex<Box<dyn Read + Send>>,
+        len: Option<u64>,
+    },
+    /// Produced while the response is written and sent as chunks. Taken out
+    /// on the first write, since the producer can only run once.
+    Chunked(Mutex<Option<BodyProducer>>),
+    /// A chunked body for a client that does not understand chunks, sent as
+    /// it is produced. The end of the connection marks its end.
+    Unframed(Mutex<Option<BodyProducer>>),
+}
+
+pub(crate) type BodyProducer = Box<dyn FnOnce(&mut dyn Write) -> std::io::Result<()> + Send>;
+
+pub struct Response {
+    status: u16,
+    headers: Headers,
+    body: Option<Body>,
+    /// Set for the pages the server generates for errors, which registered
+    /// error pages replace.
+    default_error_page: bool,
+    /// Takes over the connection once a `101` has been sent.
+    upgrade: Option<UpgradeHandler>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Response {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: None,
+            default_error_page: false,
+            upgrade: None,
+        }
+    }
+
+    /// A `200 OK` response with an empty body.
+    pub fn ok() -> Response {
+        let mut response = Response::new(200);
+        response.set_body(Vec::new());
+        response
+    }
+
+    /// A `200 OK` response with a plain text body.
+    pub fn text(body: &str) -> Response {
+        let mut response = Response::new(200);
+        response.set_header("Content-Type", "text/plain; charset=utf-8");
+        response.set_body(body.as_bytes().to_vec());
+        response
+    }
+
+    /// A `200 OK` response with an already serialized JSON body.
+    pub fn json(body: &str) -> Response {
+        let mut response = Response::new(200);
+        response.set_header("Content-Type", "application/json");
+        response.set_body(body.as_bytes().to_vec());
+        response
+    }
+
+    /// A `404 Not Found` response with a short plain text body.
+    pub fn not_found() -> Response {
+        let mut response = Response::text("Not Found");
+        response.set_status(404);
+        response
+    }
+
+    /// A `500 Internal Server Error` response with an empty body.
+    pub fn internal_server_error() -> Response {
+        let mut response = Response::new(500);
+        response.set_body(Vec::new());
+        response
+    }
+
+    /// A `200 OK` response that streams the file at `path`, so the file is
+    /// never loaded into memory as a whole. `Content-Length` comes from the
+    /// file metadata; `Content-Type` is left to the caller.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Response> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(WebServerError::NotFound(format!(
+                "{} is not a regular file",
+                path.display()
+            )));
+        }
+
+        let mut response = Response::new(200);
+        response.set_header("Content-Length", &metadata.len().to_string());
+        response.body = Some(Body::File {
+            file,
+            start: 0,
+            len: metadata.len(),
+        });
+        Ok(response)
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: u16) {
+        self.status = status;
+    }
+
+    /// Sets a header, replacing any previous value with the same
+    /// (case-insensitive) name.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.set(name, value);
+    }
+
+    /// Adds a header without replacing existing ones, e.g. for `Set-Cookie`.
+    pub fn append_header(&mut self, name: &str, value: &str) {
+        self.headers.append(name, value);
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.remove(name);
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// The body, unless there is none or it is streamed from a file.
+    pub fn body(&self) -> Option<&[u8]> {
+        match &self.body {
+            Some(Body::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Sets the body and the matching `Content-Length` header.
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.set_header("Content-Length", &body.len().to_string());
+        self.body = Some(Body::Bytes(body));
+        self.default_error_page = false;
+    }
+
+    pub(crate) fn mark_default_error_page(&mut self) {
+        self.default_error_page = true;
+    }
+
+    pub(crate) fn is_default_error_page(&self) -> bool {
+        self.default_error_page
+    }
+
+    pub(crate) fn set_upgrade(&mut self, upgrade: UpgradeHandler) {
+        self.upgrade = Some(upgrade);
+    }
+
+    pub(crate) fn take_upgrade(&mut self) -> Option<UpgradeHandler> {
+        self.upgrade.take()
+    }
+
+    pub(crate) fn is_upgrade(&self) -> bool {
+        self.upgrade.is_some()
+    }
+
+    /// Whether there is no body or one without a single byte. Streamed bodies
+    /// of unknown length do not count as empty.
+    pub(crate) fn has_empty_body(&self) -> bool {
+        match &self.body {
+            None => true,
+            Some(Body::Bytes(bytes)) => bytes.is_empty(),
+            Some(Body::File { len, .. }) => *len == 0,
+            Some(Body::Stream { len, .. }) => *len == Some(0),
+            Some(Body::Chunked(_) | Body::Unframed(_)) => false,
+        }
+    }
+
+    /// Streams the body from `reader` while the response is written, e.g.
+    /// from an upstream connection. With a known `len` the matching
+    /// `Content-Length` is set; without one the body runs until the reader
+    /// ends, and the connection is closed afterwards unless the caller sets
+    /// `Transfer-Encoding` itself.
+    pub fn set_body_reader(&mut self, reader: impl Read + Send + 'static, len: Option<u64>) {
+        match len {
+            Some(len) => self.set_header("Content-Length", &len.to_string()),
+            None => self.remove_header("Content-Length"),
+        }
+        self.body = Some(Body::Stream {
+            reader: Mutex::new(Box::new(reader)),
+            len,
+        });
+    }
+
+    /// A `200 OK` response whose body `producer` writes while the response
+    /// is sent, using `Transfer-Encoding: chunked`. Everything written goes
+    /// out as it is produced, so the size never has to be known up front.
+    pub fn streaming(
+        producer: impl FnOnce(&mut dyn Write) -> std::io::Result<()> + Send + 'static,
+    ) -> Response {
+        let mut response = Response::new(200);
+        response.set_chunked_producer(Box::new(producer));
+        response
+    }
+
+    /// Streams the body from `reader` using `Transfer-Encoding: chunked`, so
+    /// the connection can stay open without knowing the length.
+    pub fn set_chunked_body(&mut self, reader: impl Read + Send + 'static) {
+        let mut reader = reader;
+        self.set_chunked_producer(Box::new(move |writer| {
+            std::io::copy(&mut reader, writer).map(|_| ())
+        }));
+    }
+
+    pub(crate) fn set_chunked_producer(&mut self, producer: BodyProducer) {
+        self.remove_header("Content-Length");
+        self.set_header("Transfer-Encoding", "chunked");
+        self.body = Some(Body::Chunked(Mutex::new(Some(producer))));
+    }
+
+    /// Takes a body of unknown length out as a producer, e.g. to compress it
+    /// while it is written. The body is read up to `min_size` bytes first;
+    /// if it ends before that, it is kept in memory with a known length and
+    /// `None` is returned. A producer runs on a thread of its own for that,
+    /// as nothing can be sent before the head. Bodies that carry their own
+    /// framing, as through a proxy, are left alone.
+    pub(crate) fn take_streamed_body(&mut self, min_size: u64) -> Result<Option<BodyProducer>> {
+        let has_framing = self.headers.contains("Transfer-Encoding");
+        match self.body.take() {
+            Some(Body::Chunked(producer)) => {
+                let producer = match producer
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner)
+                {
+                    Some(producer) => producer,
+                    None => return Ok(None),
+                };
+                let pieces = run_producer(producer);
+                let mut start = Vec::new();
+                while (start.len() as u64) < min_size {
+                    match pieces.recv() {
+                        Ok(Ok(Piece::Data(data))) => start.extend_from_slice(&data),
+                        Ok(Ok(Piece::Flush)) => {}
+                        ended => {
+                            self.remove_header("Transfer-Encoding");
+                            self.set_body(start);
+                            return match ended {
+                                Ok(Err(error)) => Err(error.into()),
+                                _ => Ok(None),
+                            };
+                        }
+                    }
+                }
+                Ok(Some(Box::new(move |writer| {
+                    writer.write_all(&start)?;
+                    for piece in pieces {
+                        match piece? {
+                            Piece::Data(data) => writer.write_all(&data)?,
+                            Piece::Flush => writer.flush()?,
+                        }
+                    }
+                    Ok(())
+                })))
+            }
+            Some(Body::Stream { reader, len: None }) if !has_framing => {
+                let mut reader = reader.into_inner().unwrap_or_else(PoisonError::into_inner);
+                let mut start = Vec::new();
+                let read = reader.by_ref().take(min_size).read_to_end(&mut start);
+                if read.is_err() || (start.len() as u64) < min_size {
+                    self.set_body(start);
+                    read?;
+                    return Ok(None);
+                }
+                Ok(Some(Box::new(move |writer| {
+                    writer.write_all(&start)?;
+                    std::io::copy(&mut reader, writer).map(|_| ())
+                })))
+            }
+            body => {
+                self.body = body;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sends a chunked body without the chunks, for HTTP/1.0 clients, which
+    /// do not know the coding. The connection has to be closed after it.
+    /// Bodies whose chunks come from elsewhere, as through a proxy, are
+    /// decoded on the way.
+    pub(crate) fn remove_chunked_framing(&mut self) {
+        let is_chunked = self
+            .header("Transfer-Encoding")
+            .is_some_and(|encoding| encoding.trim().eq_ignore_ascii_case("chunked"));
+        self.body = match self.body.take() {
+            Some(Body::Chunked(producer)) => {
+                self.remove_header("Transfer-Encoding");
+                Some(Body::Unframed(producer))
+            }
+            Some(Body::Stream { reader, len: None }) if is_chunked => {
+                self.remove_header("Transfer-Encoding");
+                let reader = reader.into_inner().unwrap_or_else(PoisonError::into_inner);
+                let producer: BodyProducer =
+                    Box::new(move |writer| copy_unchunked(&mut BufReader::new(reader), writer));
+                Some(Body::Unframed(Mutex::new(Some(producer))))
+            }
+            body => body,
+        };
+    }
+
+    /// Length of the body in bytes, whether it is in memory or in a file.
+    /// `None` for no body and for streamed bodies of unknown length.
+    pub fn body_len(&self) -> Option<u64> {
+        match &self.body {
+            None => None,
+            Some(Body::Bytes(bytes)) => Some(bytes.len() as u64),
+            Some(Body::File { len, .. }) => Some(*len),
+            Some(Body::Stream { len, .. }) => *len,
+            Some(Body::Chunked(_) | Body::Unframed(_)) => None,
+        }
+    }
+
+    /// The whole body in memory, read from the file or reader it is streamed
+    /// from. A reader can only be read once. A chunked body comes with its
+    /// framing.
+    pub(crate) fn read_body(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_body(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Narrows the body to `len` bytes starting at `start` and updates
+    /// `Content-Length`. The range must lie within the current body.
+    pub(crate) fn slice_body(&mut self, start: u64, len: u64) {
+        match &mut self.body {
+            None => return,
+            Some(Body::Bytes(bytes)) => {
+                bytes.truncate((start + len) as usize);
+                bytes.drain(..start as usize);
+            }
+            Some(Body::File {
+                start: file_start,
+                len: file_len,
+                ..
+            }) => {
+                *file_start += start;
+                *file_len = len;
+            }
+            // Already partly consumed readers cannot be rewound.
+            Some(Body::Stream { .. } | Body::Chunked(_) | Body::Unframed(_)) => return,
+        }
+        self.set_header("Content-Length", &len.to_string());
+    }
+
+    /// Drops the body but keeps its `Content-Length`, as required for
+    /// responses to `HEAD` requests.
+    pub(crate) fn remove_body(&mut self) {
+        self.body = None;
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.serialize(HeaderCasing::default())
+    }
+
+    pub fn serialize(&self, casing: HeaderCasing) -> Result<Vec<u8>> {
+        self.serialize_with_limit(casing, usize::MAX)
+    }
+
+    /// Serializes the response, failing if the status line and headers take
+    /// more than `max_header_bytes`. A file body is read into the result.
+    pub fn serialize_with_limit(
+        &self,
+        casing: HeaderCasing,
+        max_header_bytes: usize,
+    ) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes, casing, max_header_bytes)?;
+        Ok(bytes)
+    }
+
+    /// Writes the response to `writer`, streaming a file body in chunks.
+    /// Nothing is written if the headers exceed `max_header_bytes`.
+    pub fn write_to(
+        &self,
+        writer: &mut impl Write,
+        casing: HeaderCasing,
+        max_header_bytes: usize,
+    ) -> Result<()> {
+        let head = self.serialize_head(casing, max_header_bytes)?;
+        writer.write_all(&head)?;
+        self.write_body(writer)
+    }
+
+    /// The status line and headers, including the blank line after them.
+    pub(crate) fn serialize_head(
+        &self,
+        casing: HeaderCasing,
+        max_header_bytes: usize,
+    ) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        write!(
+            &mut bytes,
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            html_error_code_to_str(self.status.into())?
+        )
+        .to_web_server_result()?;
+
+        for (name, value) in self.headers.iter() {
+            let name = match casing {
+                HeaderCasing::Canonical => canonical_header_name(name),
+                HeaderCasing::AsSet => name.to_string(),
+            };
+            write!(&mut bytes, "{}: {}\r\n", name, value).to_web_server_result()?;
+        }
+        // A body always goes out with its length, even if the header was
+        // removed after the body was set.
+        if let Some(len) = self.body_len() {
+            if !self.headers.contains("Content-Length")
+                && !self.headers.contains("Transfer-Encoding")
+            {
+                write!(&mut bytes, "Content-Length: {}\r\n", len).to_web_server_result()?;
+            }
+        }
+        write!(&mut bytes, "\r\n").to_web_server_result()?;
+
+        if bytes.len() > max_header_bytes {
+            return Err(WebServerError::Internal(format!(
+                "Response headers take {} bytes, the limit is {}",
+                bytes.len(),
+                max_header_bytes
+            )));
+        }
+
+        Ok(bytes)
+    }
+
+    pub(crate) fn write_body(&self, writer: &mut impl Write) -> Result<()> {
+        match &self.body {
+            None => Ok(()),
+            Some(Body::Bytes(bytes)) => Ok(writer.write_all(bytes)?),
+            Some(Body::File { file, start, len }) => {
+                let mut file = file;
+                file.seek(SeekFrom::Start(*start))?;
+                let copied = std::io::copy(&mut file.take(*len), writer)?;
+                // The length has already been announced, so a file that shrank
+                // meanwhile leaves the connection unusable.
+                if copied != *len {
+                    return Err(WebServerError::Io(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("File ended after {} of {} bytes", copied, len),
+                    )));
+                }
+                Ok(())
+            }
+            Some(Body::Stream { reader, len }) => {
+                let mut reader = reader.lock().to_web_server_result()?;
+                let len = match len {
+                    Some(len) => *len,
+                    None => {
+                        std::io::copy(&mut *reader, writer)?;
+                        return Ok(());
+                    }
+                };
+                let copied = std::io::copy(&mut reader.by_ref().take(len), writer)?;
+                if copied != len {
+                    return Err(WebServerError::Io(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("Body ended after {} of {} bytes", copied, len),
+                    )));
+                }
+                Ok(())
+            }
+            Some(Body::Chunked(producer)) => {
+                let producer = producer.lock().to_web_server_result()?.take();
+                let producer = producer.ok_or_else(|| {
+                    WebServerError::Internal("Chunked body was already written".to_string())
+                })?;
+                // Buffered, so that many small writes do not each become a
+                // chunk of their own.
+                let mut chunked = BufWriter::new(ChunkedWriter::new(writer));
+                producer(&mut chunked)?;
+                let chunked = chunked.into_inner().map_err(|error| error.into_error())?;
+                Ok(chunked.finish()?)
+            }
+            Some(Body::Unframed(producer)) => {
+                let producer = producer.lock().to_web_server_result()?.take();
+                let producer = producer.ok_or_else(|| {
+                    WebServerError::Internal("Chunked body was already written".to_string())
+                })?;
+                Ok(producer(writer)?)
+            }
+        }
+    }
+}
+
+/// Returns the canonical spelling of a header name: title-case words joined
+/// by hyphens, except for the names listed in `KNOWN_HEADERS`.
+pub fn canonical_header_name(name: &str) -> String {
+    if let Some(known) = KNOWN_HEADERS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(name))
+    {
+        return known.to_string();
+    }
+
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Part of what a producer wrote.
+enum Piece {
+    Data(Vec<u8>),
+    Flush,
+}
+
+/// Runs `producer` on a thread of its own and hands what it writes over,
+/// ending with its error if it fails. Once the receiver is dropped, writes
+/// fail, so the producer stops.
+fn run_producer(producer: BodyProducer) -> Receiver<std::io::Result<Piece>> {
+    let (sender, receiver) = mpsc::sync_channel(16);
+    std::thread::spawn(move || {
+        let mut writer = PieceWriter(sender);
+        if let Err(error) = producer(&mut writer) {
+            let _ = writer.0.send(Err(error));
+        }
+    });
+    receiver
+}
+
+struct PieceWriter(SyncSender<std::io::Result<Piece>>);
+
+impl PieceWriter {
+    fn send(&self, piece: Piece) -> std::io::Result<()> {
+        self.0.send(Ok(piece)).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "The response was dropped")
+        })
+    }
+}
+
+impl Write for PieceWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.send(Piece::Data(buf.to_vec()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send(Piece::Flush)
+    }
+}
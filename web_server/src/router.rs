@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use crate::vhost::VirtualHosts;
+use crate::websocket::{self, WebSocketHandler};
+use crate::Method;
+use crate::ProxyHandler;
+use crate::Request;
+use crate::Response;
+use crate::VirtualHost;
+use crate::WebSocket;
+
+pub type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync + 'static>;
+
+/// Runs around request handling. It gets the request and the rest of the
+/// chain as `next`; it may change the request before calling `next`, change
+/// the response afterwards, or answer without calling `next` at all.
+pub type Middleware = Box<
+    dyn Fn(&mut Request, &dyn Fn(&mut Request) -> Response) -> Response + Send + Sync + 'static,
+>;
+
+struct Route {
+    /// `None` answers every method.
+    method: Option<Method>,
+    path: String,
+    handler: Handler,
+}
+
+impl Route {
+    /// How closely the route matches `path`, if at all. Exact routes beat
+    /// prefix routes, and longer prefixes beat shorter ones.
+    fn specificity(&self, path: &str) -> Option<usize> {
+        if self.path == path {
+            return Some(usize::MAX);
+        }
+        let prefix = self.path.strip_suffix("/*")?;
+        let under_prefix = path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        under_prefix.then_some(prefix.len())
+    }
+}
+
+pub(crate) enum RouteMatch<'a> {
+    Handler(&'a Handler),
+    /// The path has routes, but none for the request method.
+    MethodNotAllowed(Vec<Method>),
+    /// An `OPTIONS` request for a path with routes, none of them for
+    /// `OPTIONS` itself.
+    Options(Vec<Method>),
+    /// Nothing matched and the fallback was not replaced, so static file
+    /// serving should take over.
+    Static,
+}
+
+/// Dispatches requests to handlers registered per method and path. Requests
+/// that match no route go to the fallback handler, which serves static files
+/// from the content directory unless replaced.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Option<Handler>,
+    pub(crate) middlewares: Vec<Middleware>,
+    pub(crate) virtual_hosts: VirtualHosts,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Registers a `GET` handler. It also answers `HEAD` requests unless a
+    /// separate `HEAD` handler is registered.
+    pub fn get<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Get, path, handler)
+    }
+
+    pub fn head<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Head, path, handler)
+    }
+
+    pub fn post<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Post, path, handler)
+    }
+
+    pub fn put<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Put, path, handler)
+    }
+
+    pub fn delete<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Delete, path, handler)
+    }
+
+    /// Registers a handler for `method` and `path`. A path ending in `/*`
+    /// is a prefix: `/api/*` matches `/api` and everything below it. Exact
+    /// paths take precedence over prefixes, and longer prefixes over shorter
+    /// ones.
+    pub fn route<F>(&mut self, method: Method, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.add_route(Some(method), path, Box::new(handler))
+    }
+
+    /// Registers a handler for `path` that answers every method.
+    pub fn any<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.add_route(None, path, Box::new(handler))
+    }
+
+    /// Accepts WebSocket connections on `path`. Once the handshake is done,
+    /// `handler` gets the connection on a thread of its own, so it may block
+    /// for as long as the connection lives without holding up a worker of
+    /// the pool. Requests that are not a valid handshake are answered with
+    /// `426` or `400`.
+    pub fn websocket<F>(&mut self, path: &str, handler: F) -> &mut Router
+    where
+        F: Fn(WebSocket) + Send + Sync + 'static,
+    {
+        let handler: WebSocketHandler = Arc::new(handler);
+        self.get(path, move |request| websocket::upgrade(request, &handler))
+    }
+
+    /// Forwards every request below `path` to the upstream of `proxy`, e.g.
+    /// `router.proxy("/api/*", ProxyHandler::new("127.0.0.1:9000"))`.
+    pub fn proxy(&mut self, path: &str, proxy: ProxyHandler) -> &mut Router {
+        self.any(path, move |request| proxy.handle(request))
+    }
+
+    fn add_route(&mut self, method: Option<Method>, path: &str, handler: Handler) -> &mut Router {
+        self.routes
+            .retain(|route| route.method != method || route.path != path);
+        self.routes.push(Route {
+            method,
+            path: path.to_string(),
+            handler,
+        });
+        self
+    }
+
+    /// Replaces the handler used for requests that match no route.
+    pub fn fallback<F>(&mut self, handler: F) -> &mut Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    /// Adds a middleware around every request, including static files and
+    /// error responses. The first one added is the outermost, e.g.
+    /// `router.middleware(|request, next| { let mut response = next(request);
+    /// response.set_header("X-Frame-Options", "DENY"); response })`.
+    pub fn middleware<F>(&mut self, middleware: F) -> &mut Router
+    where
+        F: Fn(&mut Request, &dyn Fn(&mut Request) -> Response) -> Response + Send + Sync + 'static,
+    {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    /// Serves requests whose `Host` matches `pattern` from `host` instead of
+    /// this router. `*` in a pattern matches any run of characters, so
+    /// `*.example.com` covers every subdomain of `example.com` but not
+    /// `example.com` itself. Requests for other hosts, or without a `Host`
+    /// header, are handled by this router as usual. The middlewares of this
+    /// router run for every host, outside those of `host`.
+    pub fn virtual_host(&mut self, pattern: &str, host: VirtualHost) -> &mut Router {
+        self.virtual_hosts.add(pattern, host);
+        self
+    }
+
+    /// Passes `request` through the middlewares, then to `endpoint`.
+    pub(crate) fn run_middlewares(
+        &self,
+        request: &mut Request,
+        endpoint: &dyn Fn(&mut Request) -> Response,
+    ) -> Response {
+        run_chain(&self.middlewares, request, endpoint)
+    }
+
+    pub(crate) fn find(&self, request: &Request) -> RouteMatch<'_> {
+        let path = request.path();
+        let find_method = |method: Method| {
+            self.routes
+                .iter()
+                .filter(|route| {
+                    route
+                        .method
+                        .is_none_or(|route_method| route_method == method)
+                })
+                .filter_map(|route| Some((route.specificity(path)?, route)))
+                .max_by_key(|(specificity, _)| *specificity)
+                .map(|(_, route)| route)
+        };
+
+        let route = match request.method {
+            Method::Head => find_method(Method::Head).or_else(|| find_method(Method::Get)),
+            method => find_method(method),
+        };
+        if let Some(route) = route {
+            return RouteMatch::Handler(&route.handler);
+        }
+
+        let allowed = self.allowed_methods(path);
+        if !allowed.is_empty() {
+            return match request.method {
+                Method::Options => RouteMatch::Options(allowed),
+                _ => RouteMatch::MethodNotAllowed(allowed),
+            };
+        }
+
+        match &self.fallback {
+            Some(fallback) => RouteMatch::Handler(fallback),
+            None => RouteMatch::Static,
+        }
+    }
+
+    /// Methods registered for `path`, including the implicit `HEAD` of a
+    /// `GET` route.
+    fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        // Routes for any method always match, so only the others are left.
+        let mut allowed: Vec<Method> = self
+            .routes
+            .iter()
+            .filter(|route| route.specificity(path).is_some())
+            .filter_map(|route| route.method)
+            .collect();
+        if allowed.contains(&Method::Get) && !allowed.contains(&Method::Head) {
+            allowed.push(Method::Head);
+        }
+        allowed.sort();
+        allowed.dedup();
+        allowed
+    }
+}
+
+fn run_chain(
+    middlewares: &[Middleware],
+    request: &mut Request,
+    endpoint: &dyn Fn(&mut Request) -> Response,
+) -> Response {
+    match middlewares.split_first() {
+        Some((first, rest)) => first(request, &|request| run_chain(rest, request, endpoint)),
+        None => endpoint(request),
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::percent_encoding::percent_decode;
+
+/// Outcome of mapping a request path onto the content root.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PathResolution {
+    /// Canonical path of an existing file or directory inside the root.
+    Found(PathBuf),
+    NotFound,
+    /// The path points outside the content root, e.g. through `..` or a
+    /// symlink, or contains characters that are never valid in it.
+    Forbidden,
+    /// The path is not validly percent-encoded UTF-8.
+    BadRequest,
+}
+
+/// Maps `request_path` (the request target without its query string) to a
+/// file under `content_root`.
+///
+/// The path is percent-decoded and its `.` and `..` segments are applied
+/// lexically. The result is then canonicalized, so symlinks cannot lead out of
+/// the root either.
+pub fn resolve_static_path(content_root: &Path, request_path: &str) -> PathResolution {
+    match percent_decode(request_path).map(String::from_utf8) {
+        Some(Ok(decoded)) => resolve_decoded_path(content_root, &decoded),
+        _ => PathResolution::BadRequest,
+    }
+}
+
+/// Like `resolve_static_path`, for a path that is already percent-decoded,
+/// such as `Request::path`.
+pub(crate) fn resolve_decoded_path(content_root: &Path, decoded: &str) -> PathResolution {
+    // A backslash is a separator on Windows and a NUL byte truncates paths
+    // in system calls; neither has a legitimate use here.
+    if decoded.contains(['\\', '\0']) {
+        return PathResolution::Forbidden;
+    }
+
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if !relative.pop() {
+                    return PathResolution::Forbidden;
+                }
+            }
+            segment => {
+                // Anything that is not a plain name, such as a drive prefix,
+                // could replace the root when joined.
+                let mut components = Path::new(segment).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(_)), None) => relative.push(segment),
+                    _ => return PathResolution::Forbidden,
+                }
+            }
+        }
+    }
+
+    let root = match content_root.canonicalize() {
+        Ok(root) => root,
+        Err(_) => return PathResolution::NotFound,
+    };
+    let resolved = match root.join(&relative).canonicalize() {
+        Ok(resolved) => resolved,
+        Err(_) => return PathResolution::NotFound,
+    };
+
+    if resolved.starts_with(&root) {
+        PathResolution::Found(resolved)
+    } else {
+        PathResolution::Forbidden
+    }
+}
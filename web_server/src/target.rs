@@ -0,0 +1,120 @@
+use crate::percent_encoding::percent_decode;
+use crate::Result;
+use crate::WebServerError;
+
+/// A request target split into path, query string and fragment.
+///
+/// The path is percent-decoded, repeated slashes are merged and `.` and `..`
+/// segments are applied, so `/a//b/./c/../%64` becomes `/a/b/d`. The query
+/// string and fragment are kept as they were sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestTarget {
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+impl RequestTarget {
+    /// Parses an origin-form target (`/path?query`) or an absolute-form one
+    /// (`http://host/path?query`), whose scheme and authority are dropped.
+    ///
+    /// Fails with `BadRequest` for escapes that are not `%XX`, paths that do
+    /// not decode to UTF-8 and targets of any other form, and with
+    /// `Forbidden` for `..` segments leading above the root.
+    pub fn parse(target: &str) -> Result<RequestTarget> {
+        let (rest, fragment) = match target.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.to_string())),
+            None => (target, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (rest, None),
+        };
+        let path = strip_scheme_and_authority(path).ok_or_else(|| {
+            WebServerError::BadRequest(format!("Invalid request target {:?}", target))
+        })?;
+
+        let decoded = match percent_decode(path).map(String::from_utf8) {
+            Some(Ok(decoded)) => decoded,
+            _ => {
+                return Err(WebServerError::BadRequest(format!(
+                    "Request path {:?} is not validly percent-encoded UTF-8",
+                    path
+                )))
+            }
+        };
+        let path = normalize_path(&decoded).ok_or_else(|| {
+            WebServerError::Forbidden(format!("Request path {:?} leaves the root", path))
+        })?;
+
+        Ok(RequestTarget {
+            path,
+            query,
+            fragment,
+        })
+    }
+
+    /// The decoded and normalized path, always starting with `/`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The query string without the `?`, still percent-encoded.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The fragment without the `#`. Clients are not supposed to send one,
+    /// but some do.
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+}
+
+/// The path of an origin-form or absolute-form target. `None` for anything
+/// else, such as `*` or a path without the leading slash.
+fn strip_scheme_and_authority(path: &str) -> Option<&str> {
+    if path.starts_with('/') {
+        return Some(path);
+    }
+    let scheme_end = path.find("://")?;
+    let scheme = &path[..scheme_end];
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+    let after_scheme = &path[scheme_end + 3..];
+    match after_scheme.find('/') {
+        Some(start) => Some(&after_scheme[start..]),
+        // `http://host` asks for the root.
+        None if !after_scheme.is_empty() => Some("/"),
+        None => None,
+    }
+}
+
+/// Merges repeated slashes and applies `.` and `..` segments to an already
+/// decoded path. A path ending in a directory (`/a/`, `/a/.` or `/a/b/..`)
+/// keeps its trailing slash. `None` if a `..` would go above the root.
+pub fn normalize_path(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/') {
+        trailing_slash = matches!(segment, "" | "." | "..");
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing_slash || segments.is_empty() {
+        normalized.push('/');
+    }
+    Some(normalized)
+}
@@ -1,14 +1,75 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 use crate::Result;
 use crate::WebServerError;
-use std::sync::mpsc;
 use std::sync::Arc;
+use std::sync::Condvar;
 use std::sync::Mutex;
 
+/// How many times a waiting job may be passed over by jobs of a higher
+/// priority before it is dequeued anyway.
+const MAX_SKIPS: usize = 8;
+
+const PRIORITY_LEVELS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Priority {
+    fn level(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+/// Bounds within which a `ThreadPool` sizes itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Workers kept running even when there is nothing to do.
+    pub min_threads: usize,
+    /// Most workers running at once. Another one is started whenever a job
+    /// is queued and no idle worker is left to take it.
+    pub max_threads: usize,
+    /// How long a worker beyond `min_threads` waits for a job before it
+    /// exits.
+    pub idle_timeout: Duration,
+    /// Most jobs that may wait for a worker; `None` is unbounded.
+    pub queue_capacity: Option<usize>,
+}
+
+impl PoolConfig {
+    /// Exactly `threads_count` workers and an unbounded queue.
+    pub fn fixed(threads_count: usize) -> PoolConfig {
+        PoolConfig {
+            min_threads: threads_count,
+            max_threads: threads_count,
+            idle_timeout: Duration::from_secs(60),
+            queue_capacity: None,
+        }
+    }
+}
+
 pub struct ThreadPool {
-    _workers: Vec<Worker>,
-    sender: Option<mpsc::Sender<Job>>,
+    workers: Mutex<Vec<Worker>>,
+    shared: Arc<Shared>,
+}
+
+/// A handle for watching a pool from elsewhere, e.g. a metrics endpoint.
+/// It does not keep the workers alive.
+#[derive(Clone)]
+pub struct PoolMonitor {
+    shared: Arc<Shared>,
 }
 
 type Job = Box<dyn FnOnce() + Send + 'static>;
@@ -18,14 +79,99 @@ struct Worker {
     thread: Option<JoinHandle<()>>,
 }
 
+struct Shared {
+    config: PoolConfig,
+    state: Mutex<SchedulerState>,
+    job_available: Condvar,
+    space_available: Condvar,
+    panicked_jobs: AtomicUsize,
+    next_worker_id: AtomicUsize,
+}
+
+/// Everything workers and producers coordinate on, behind one lock.
+struct SchedulerState {
+    queue: JobQueue,
+    /// Workers started and not yet exited.
+    workers: usize,
+    /// Workers blocked waiting for a job.
+    idle_workers: usize,
+}
+
+impl SchedulerState {
+    /// Whether a job that was just queued has no worker to pick it up soon.
+    fn needs_worker(&self, config: &PoolConfig) -> bool {
+        self.queue.len() > self.idle_workers && self.workers < config.max_threads
+    }
+}
+
+/// One FIFO per priority level. A level that keeps being skipped in favor of
+/// higher priorities ages and eventually gets served first.
+struct JobQueue {
+    levels: [VecDeque<Job>; PRIORITY_LEVELS],
+    skips: [usize; PRIORITY_LEVELS],
+    /// Most jobs that may wait at once, all levels together; `None` is
+    /// unbounded.
+    capacity: Option<usize>,
+    closed: bool,
+}
+
+impl JobQueue {
+    fn new(capacity: Option<usize>) -> JobQueue {
+        JobQueue {
+            levels: Default::default(),
+            skips: [0; PRIORITY_LEVELS],
+            capacity,
+            closed: false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.levels.iter().all(|level| level.is_empty())
+    }
+
+    fn len(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|capacity| self.len() >= capacity)
+    }
+
+    fn push(&mut self, job: Job, priority: Priority) {
+        self.levels[priority.level()].push_back(job);
+    }
+
+    fn pop(&mut self) -> Option<Job> {
+        let waiting = |level: &usize| !self.levels[*level].is_empty();
+        let starved = (0..PRIORITY_LEVELS)
+            .filter(waiting)
+            .find(|level| self.skips[*level] >= MAX_SKIPS);
+        let level = starved.or_else(|| (0..PRIORITY_LEVELS).find(waiting))?;
+
+        for lower in level + 1..PRIORITY_LEVELS {
+            if !self.levels[lower].is_empty() {
+                self.skips[lower] += 1;
+            }
+        }
+        self.skips[level] = 0;
+
+        self.levels[level].pop_front()
+    }
+}
+
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = std::thread::spawn(move || loop {
-            let maybe_job = receiver.lock().unwrap().recv();
-            match maybe_job {
-                Ok(job) => job(),
-                Err(_) => break,
-            };
+    fn spawn(shared: &Arc<Shared>) -> Worker {
+        let id = shared.next_worker_id.fetch_add(1, Ordering::SeqCst);
+        let shared = Arc::clone(shared);
+        let thread = std::thread::spawn(move || {
+            while let Some(job) = next_job(&shared) {
+                // A panicking job must not take the worker down with it. Jobs
+                // run without the lock held, so nothing shared is poisoned.
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    shared.panicked_jobs.fetch_add(1, Ordering::SeqCst);
+                    println!("Job panicked on worker {}", id);
+                }
+            }
         });
 
         Worker {
@@ -33,29 +179,101 @@ impl Worker {
             thread: Some(thread),
         }
     }
+
+    fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+}
+
+/// Waits for the next job of a worker. `None` tells the worker to exit,
+/// either because the pool is closing or because it idled for too long while
+/// more than `min_threads` workers are running.
+fn next_job(shared: &Shared) -> Option<Job> {
+    let mut state = shared.state.lock().unwrap();
+    let job = loop {
+        if let Some(job) = state.queue.pop() {
+            break Some(job);
+        }
+        if state.queue.closed {
+            break None;
+        }
+
+        state.idle_workers += 1;
+        let (guard, wait) = shared
+            .job_available
+            .wait_timeout(state, shared.config.idle_timeout)
+            .unwrap();
+        state = guard;
+        state.idle_workers -= 1;
+
+        let retire =
+            wait.timed_out() && state.queue.is_empty() && state.workers > shared.config.min_threads;
+        if retire {
+            break None;
+        }
+    };
+
+    match job {
+        Some(_) => shared.space_available.notify_one(),
+        None => state.workers -= 1,
+    }
+    job
 }
 
 impl ThreadPool {
     pub fn new(threads_count: usize) -> Result<ThreadPool> {
-        if threads_count == 0 {
-            return Err(WebServerError(
+        ThreadPool::with_config(PoolConfig::fixed(threads_count))
+    }
+
+    /// A pool that lets at most `capacity` jobs wait for a worker. Once that
+    /// many are waiting, `execute` blocks until a worker takes one.
+    pub fn bounded(threads_count: usize, capacity: usize) -> Result<ThreadPool> {
+        ThreadPool::with_config(PoolConfig {
+            queue_capacity: Some(capacity),
+            ..PoolConfig::fixed(threads_count)
+        })
+    }
+
+    /// A pool that starts with `min_threads` workers and grows up to
+    /// `max_threads` while jobs are waiting.
+    pub fn with_config(config: PoolConfig) -> Result<ThreadPool> {
+        if config.max_threads == 0 {
+            return Err(WebServerError::Config(
                 "Threads count could not be zero.".to_string(),
             ));
         }
-
-        let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
-        let mut workers = Vec::with_capacity(threads_count);
-
-        for id in 0..threads_count {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+        if config.min_threads > config.max_threads {
+            return Err(WebServerError::Config(format!(
+                "Minimum threads count {} is above the maximum {}.",
+                config.min_threads, config.max_threads
+            )));
+        }
+        if config.queue_capacity == Some(0) {
+            return Err(WebServerError::Config(
+                "Queue capacity could not be zero.".to_string(),
+            ));
         }
 
-        drop(receiver);
+        let min_threads = config.min_threads;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(SchedulerState {
+                queue: JobQueue::new(config.queue_capacity),
+                workers: min_threads,
+                idle_workers: 0,
+            }),
+            config,
+            job_available: Condvar::new(),
+            space_available: Condvar::new(),
+            panicked_jobs: AtomicUsize::new(0),
+            next_worker_id: AtomicUsize::new(0),
+        });
+        let workers = (0..min_threads).map(|_| Worker::spawn(&shared)).collect();
 
         Ok(ThreadPool {
-            _workers: workers,
-            sender: Some(sender),
+            workers: Mutex::new(workers),
+            shared,
         })
     }
 
@@ -63,17 +281,92 @@ impl ThreadPool {
     where
         F: FnOnce() + Send + 'static,
     {
-        let job = Box::new(f);
-        self.sender.as_ref().expect("Sender was deleted").send(job).unwrap();
+        self.execute_with_priority(f, Priority::Normal);
+    }
+
+    pub fn execute_with_priority<F>(&self, f: F, priority: Priority)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        while state.queue.is_full() {
+            state = self.shared.space_available.wait(state).unwrap();
+        }
+        state.queue.push(Box::new(f), priority);
+
+        let grow = state.needs_worker(&self.shared.config);
+        if grow {
+            // Counted right away so that producers racing on the lock do not
+            // start more workers than allowed.
+            state.workers += 1;
+        }
+        drop(state);
+        self.shared.job_available.notify_one();
+
+        if grow {
+            let mut workers = self.workers.lock().unwrap();
+            // Retired workers have exited already, so joining them is quick.
+            workers.retain(|worker| !worker.is_finished());
+            workers.push(Worker::spawn(&self.shared));
+        }
+    }
+
+    /// Whether the queue holds as many waiting jobs as it may, so that
+    /// `execute` would block.
+    pub fn is_full(&self) -> bool {
+        self.shared.state.lock().unwrap().queue.is_full()
+    }
+
+    /// Number of jobs waiting for a worker.
+    pub fn queued_jobs(&self) -> usize {
+        self.monitor().queued_jobs()
+    }
+
+    /// Number of workers running right now, busy or idle.
+    pub fn size(&self) -> usize {
+        self.monitor().size()
+    }
+
+    /// Number of workers waiting for a job.
+    pub fn idle_workers(&self) -> usize {
+        self.monitor().idle_workers()
+    }
+
+    /// Number of jobs that panicked so far. The workers that ran them keep
+    /// serving the queue.
+    pub fn panic_count(&self) -> usize {
+        self.shared.panicked_jobs.load(Ordering::SeqCst)
+    }
+
+    pub fn monitor(&self) -> PoolMonitor {
+        PoolMonitor {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl PoolMonitor {
+    /// See `ThreadPool::size`.
+    pub fn size(&self) -> usize {
+        self.shared.state.lock().unwrap().workers
+    }
+
+    /// Workers waiting for a job.
+    pub fn idle_workers(&self) -> usize {
+        self.shared.state.lock().unwrap().idle_workers
+    }
+
+    /// See `ThreadPool::queued_jobs`.
+    pub fn queued_jobs(&self) -> usize {
+        self.shared.state.lock().unwrap().queue.len()
     }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        if let Some(sender) = self.sender.take() {
-            println!("Dropping sender");
-            drop(sender);
-        }
+        println!("Closing job queue");
+        self.shared.state.lock().unwrap().queue.closed = true;
+        self.shared.job_available.notify_all();
     }
 }
 
@@ -0,0 +1,67 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+use crate::Result;
+use crate::WebServerError;
+
+/// Certificate and key an HTTPS listener presents to clients.
+#[derive(Clone)]
+pub struct TlsConfig {
+    pub(crate) server_config: Arc<rustls::ServerConfig>,
+}
+
+impl TlsConfig {
+    /// Reads a PEM certificate chain, leaf first, and the PEM private key
+    /// that belongs to it.
+    pub fn from_pem_files(
+        cert_chain_path: impl AsRef<Path>,
+        private_key_path: impl AsRef<Path>,
+    ) -> Result<TlsConfig> {
+        let cert_chain = std::fs::read(cert_chain_path)?;
+        let private_key = std::fs::read(private_key_path)?;
+        TlsConfig::from_pem(&cert_chain, &private_key)
+    }
+
+    pub fn from_pem(cert_chain: &[u8], private_key: &[u8]) -> Result<TlsConfig> {
+        let cert_chain = CertificateDer::pem_slice_iter(cert_chain)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|error| {
+                WebServerError::Config(format!("Invalid certificate chain: {}", error))
+            })?;
+        if cert_chain.is_empty() {
+            return Err(WebServerError::Config(
+                "The certificate chain contains no certificate".to_string(),
+            ));
+        }
+        let private_key = PrivateKeyDer::from_pem_slice(private_key)
+            .map_err(|error| WebServerError::Config(format!("Invalid private key: {}", error)))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut server_config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| {
+                builder
+                    .with_no_client_auth()
+                    .with_single_cert(cert_chain, private_key)
+            })
+            .map_err(|error| WebServerError::Config(format!("Invalid TLS setup: {}", error)))?;
+        server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(TlsConfig::from_rustls(Arc::new(server_config)))
+    }
+
+    /// Uses a `rustls` configuration as is, for settings the PEM
+    /// constructors do not cover such as client certificates.
+    pub fn from_rustls(server_config: Arc<rustls::ServerConfig>) -> TlsConfig {
+        TlsConfig { server_config }
+    }
+}
+
+impl std::fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConfig").finish_non_exhaustive()
+    }
+}
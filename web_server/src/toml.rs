@@ -0,0 +1,334 @@
+use crate::Result;
+use crate::WebServerError;
+
+/// A value of a configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a number",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// `key = value` with the line it is on, for error messages.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+/// The keys before the first header, of a `[name]` table or of one element
+/// of a `[[name]]` array of tables.
+#[derive(Debug)]
+pub(crate) struct Table {
+    /// `None` for the keys before the first header.
+    pub name: Option<String>,
+    pub is_array: bool,
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+/// Parses the subset of TOML configuration files need: tables, arrays of
+/// tables and `key = value` pairs of strings, integers, floats, booleans and
+/// arrays. Dotted keys, inline tables, multi-line strings and dates are
+/// refused. The tables are returned in file order, starting with the keys
+/// before the first header.
+pub(crate) fn parse(text: &str) -> Result<Vec<Table>> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        line: 1,
+    };
+    let mut tables = vec![Table {
+        name: None,
+        is_array: false,
+        line: 1,
+        entries: Vec::new(),
+    }];
+
+    loop {
+        parser.skip_blank();
+        let line = parser.line;
+        match parser.peek() {
+            None => return Ok(tables),
+            Some('[') => {
+                parser.bump();
+                let is_array = parser.eat('[');
+                parser.skip_spaces();
+                let name = parser.key()?;
+                parser.skip_spaces();
+                parser.expect(']')?;
+                if is_array {
+                    parser.expect(']')?;
+                }
+                parser.end_of_line()?;
+
+                let previous = tables
+                    .iter()
+                    .find(|table| table.name.as_deref() == Some(name.as_str()));
+                if let Some(previous) = previous {
+                    if !(is_array && previous.is_array) {
+                        return Err(parser.error_at(
+                            line,
+                            &format!("`{}` is already defined on line {}", name, previous.line),
+                        ));
+                    }
+                }
+                tables.push(Table {
+                    name: Some(name),
+                    is_array,
+                    line,
+                    entries: Vec::new(),
+                });
+            }
+            Some(_) => {
+                let key = parser.key()?;
+                parser.skip_spaces();
+                parser.expect('=')?;
+                parser.skip_spaces();
+                let value = parser.value()?;
+                parser.end_of_line()?;
+
+                let table = tables.last_mut().unwrap();
+                if let Some(previous) = table.entries.iter().find(|entry| entry.key == key) {
+                    return Err(parser.error_at(
+                        line,
+                        &format!("`{}` is already defined on line {}", key, previous.line),
+                    ));
+                }
+                table.entries.push(Entry { key, value, line });
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("expected `{}` at the end of the file", expected))),
+        }
+    }
+
+    fn error(&self, message: &str) -> WebServerError {
+        self.error_at(self.line, message)
+    }
+
+    fn error_at(&self, line: usize, message: &str) -> WebServerError {
+        WebServerError::Config(format!("line {}: {}", line, message))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips spaces, comments and line breaks, as allowed between lines and
+    /// between the values of an array.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            if !matches!(self.peek(), Some('\n' | '\r')) {
+                return;
+            }
+            self.bump();
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        self.skip_comment();
+        self.eat('\r');
+        match self.bump() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(self.error(&format!("unexpected `{}` at the end of the line", c))),
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        let key = match self.peek() {
+            Some('"') => self.basic_string()?,
+            Some('\'') => self.literal_string()?,
+            _ => {
+                let key = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if key.is_empty() {
+                    return Err(self.error("expected a key"));
+                }
+                key.to_string()
+            }
+        };
+        if self.peek() == Some('.') {
+            return Err(self.error("dotted keys are not supported"));
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => Err(self.error("inline tables are not supported")),
+            Some(c) if c.is_ascii_alphanumeric() || c == '+' || c == '-' => {
+                let word = self.take_while(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_')
+                });
+                parse_word(word).ok_or_else(|| self.error(&format!("invalid value `{}`", word)))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.eat(']') {
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank();
+            if !self.eat(',') {
+                self.skip_blank();
+                self.expect(']')?;
+                return Ok(Value::Array(values));
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) if c != '\n' => c,
+                _ => return Err(self.error("unterminated string")),
+            };
+            self.bump();
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('u') => self.unicode_escape(4)?,
+                        Some('U') => self.unicode_escape(8)?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, digits: usize) -> Result<char> {
+        let hex = self.text[self.position..].get(..digits).unwrap_or("");
+        // from_str_radix alone would also take a sign, as in `\u+041`.
+        let c = Some(hex)
+            .filter(|hex| hex.len() == digits && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.position += digits;
+        Ok(c)
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        self.expect('\'')?;
+        let string = self.take_while(|c| c != '\'' && c != '\n').to_string();
+        if !self.eat('\'') {
+            return Err(self.error("unterminated string"));
+        }
+        Ok(string)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.text[start..self.position]
+    }
+}
+
+/// A boolean or number, with `_` allowed between digits.
+fn parse_word(word: &str) -> Option<Value> {
+    match word {
+        "true" => return Some(Value::Boolean(true)),
+        "false" => return Some(Value::Boolean(false)),
+        _ => {}
+    }
+    let digits = word.strip_prefix(['+', '-']).unwrap_or(word);
+    if !digits.starts_with(|c: char| c.is_ascii_digit())
+        || word.contains("__")
+        || word.ends_with('_')
+    {
+        return None;
+    }
+    let number = word.replace('_', "");
+    if number.contains(['.', 'e', 'E']) {
+        number.parse().ok().map(Value::Float)
+    } else {
+        number.parse().ok().map(Value::Integer)
+    }
+}
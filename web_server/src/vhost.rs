@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::matches_pattern;
+use crate::https_redirect::strip_port;
+use crate::Request;
+use crate::Router;
+
+/// A site served for the host names matching its pattern, see
+/// `Router::virtual_host`.
+#[derive(Default)]
+pub struct VirtualHost {
+    pub(crate) router: Router,
+    content_dir: Option<PathBuf>,
+}
+
+impl VirtualHost {
+    pub fn new() -> VirtualHost {
+        VirtualHost::default()
+    }
+
+    /// Directory static files of this host are served from. Without one the
+    /// content directory of the server is used.
+    pub fn content_dir(mut self, content_dir: impl Into<PathBuf>) -> VirtualHost {
+        self.content_dir = Some(content_dir.into());
+        self
+    }
+
+    /// Routes of this host. Requests matching none of them fall through to
+    /// its static files, as with the top-level router.
+    pub fn router(mut self, router: Router) -> VirtualHost {
+        self.router = router;
+        self
+    }
+
+    pub(crate) fn content_dir_or<'a>(&'a self, default: &'a Path) -> &'a Path {
+        self.content_dir.as_deref().unwrap_or(default)
+    }
+}
+
+/// Host patterns in the order they were added. The first match wins.
+#[derive(Default)]
+pub(crate) struct VirtualHosts {
+    hosts: Vec<(String, VirtualHost)>,
+}
+
+impl VirtualHosts {
+    pub fn add(&mut self, pattern: &str, host: VirtualHost) {
+        self.hosts.push((pattern.to_ascii_lowercase(), host));
+    }
+
+    /// The host the `Host` header of `request` names, if any is configured
+    /// for it.
+    pub fn find(&self, request: &Request) -> Option<&VirtualHost> {
+        if self.hosts.is_empty() {
+            return None;
+        }
+        let name = host_name(request.headers.get("Host")?);
+        self.hosts
+            .iter()
+            .find(|(pattern, _)| matches_pattern(pattern, &name))
+            .map(|(_, host)| host)
+    }
+}
+
+/// The host name of a `Host` header: without the port, in lowercase and
+/// without the trailing dot of a fully qualified name.
+fn host_name(header: &str) -> String {
+    let host = strip_port(header.trim());
+    host.strip_suffix('.').unwrap_or(host).to_ascii_lowercase()
+}
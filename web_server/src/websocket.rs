@@ -0,0 +1,574 @@
+use std::io::{BufRead, Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::connection::ConnectionReader;
+use crate::error_page;
+use crate::metrics::ServerStats;
+use crate::sha1::sha1;
+use crate::Request;
+use crate::Response;
+use crate::Result;
+use crate::Version;
+use crate::WebServerError;
+
+/// Appended to the client's key before hashing it (RFC 6455, section 1.3).
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Close codes of RFC 6455, section 7.4.1.
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Serves one WebSocket connection, on a thread of its own.
+pub(crate) type WebSocketHandler = Arc<dyn Fn(WebSocket) + Send + Sync + 'static>;
+
+/// Takes over a connection once the `101` has been sent.
+pub(crate) type UpgradeHandler = Box<dyn FnOnce(Upgraded) + Send + 'static>;
+
+/// A connection that switched protocols, with the request that asked for it.
+pub(crate) struct Upgraded {
+    pub connection: ConnectionReader,
+    pub request: Request,
+    /// How long reading the rest of a frame may stall once it has started.
+    pub read_timeout: Option<Duration>,
+    pub max_message_size: usize,
+}
+
+/// A message received from or sent to a WebSocket client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
+    /// Answered with a pong before `recv` returns it.
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// The closing handshake, with the status code and reason if the peer
+    /// sent one. `recv` answers it before returning it; sending it starts
+    /// the handshake from this side.
+    Close(Option<(u16, String)>),
+}
+
+/// Everything that ends reading a message early.
+enum ReadError {
+    Io(std::io::Error),
+    /// The peer broke the protocol; the connection is closed with the code.
+    Fail(u16, String),
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(error: std::io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// A blocking WebSocket connection, handed to the handler of a
+/// `Router::websocket` route.
+///
+/// `recv` waits for the next message and takes care of the protocol on the
+/// way: it reassembles fragmented messages, answers pings and completes the
+/// closing handshake. A connection dropped without `close` is closed with
+/// code 1000.
+pub struct WebSocket {
+    connection: ConnectionReader,
+    request: Request,
+    read_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_message_size: usize,
+    /// Opcode and payload so far of a fragmented message.
+    fragments: Option<(u8, Vec<u8>)>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl WebSocket {
+    pub(crate) fn new(upgraded: Upgraded) -> WebSocket {
+        let mut connection = upgraded.connection;
+        connection.get_mut().set_deadline(None);
+        WebSocket {
+            connection,
+            request: upgraded.request,
+            read_timeout: upgraded.read_timeout,
+            idle_timeout: None,
+            max_message_size: upgraded.max_message_size,
+            fragments: None,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    /// The handshake request, e.g. for its path, query or cookies.
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
+    /// How long `recv` waits for the next message to start; `None`, the
+    /// default, waits forever. A `recv` that times out fails with
+    /// `WebServerError::Timeout` and the connection stays usable.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Waits for the next message. Fails once the connection is closed,
+    /// after `WebSocketMessage::Close` has been returned.
+    pub fn recv(&mut self) -> Result<WebSocketMessage> {
+        loop {
+            if self.close_received {
+                return Err(closed());
+            }
+            self.wait_for_frame()?;
+            let frame = match self.read_frame() {
+                Ok(frame) => frame,
+                Err(ReadError::Io(error)) => {
+                    self.close_received = true;
+                    return Err(error.into());
+                }
+                Err(ReadError::Fail(code, message)) => return Err(self.fail(code, message)),
+            };
+            match self.handle_frame(frame) {
+                Ok(Some(message)) => return Ok(message),
+                Ok(None) => {}
+                Err(ReadError::Io(error)) => return Err(error.into()),
+                Err(ReadError::Fail(code, message)) => return Err(self.fail(code, message)),
+            }
+        }
+    }
+
+    pub fn send(&mut self, message: WebSocketMessage) -> Result<()> {
+        if self.close_sent {
+            return Err(closed());
+        }
+        match message {
+            WebSocketMessage::Text(text) => self.write_frame(OP_TEXT, text.as_bytes()),
+            WebSocketMessage::Binary(data) => self.write_frame(OP_BINARY, &data),
+            WebSocketMessage::Ping(data) => self.write_control_frame(OP_PING, &data),
+            WebSocketMessage::Pong(data) => self.write_control_frame(OP_PONG, &data),
+            WebSocketMessage::Close(close) => {
+                self.close_sent = true;
+                self.write_control_frame(OP_CLOSE, &close_payload(close))
+            }
+        }
+    }
+
+    /// Starts the closing handshake and waits for the client to answer it,
+    /// discarding messages that arrive in between.
+    pub fn close(&mut self, code: u16, reason: &str) -> Result<()> {
+        if !self.close_sent {
+            self.send(WebSocketMessage::Close(Some((code, reason.to_string()))))?;
+        }
+        // A client that never answers holds the thread for one read timeout
+        // at most.
+        self.idle_timeout = self.read_timeout;
+        while !self.close_received {
+            match self.recv() {
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks until the first byte of a frame arrives, for at most
+    /// `idle_timeout`. Nothing is consumed, so a timeout loses nothing.
+    fn wait_for_frame(&mut self) -> Result<()> {
+        self.connection
+            .get_mut()
+            .set_read_timeout(self.idle_timeout);
+        let waited = self.connection.fill_buf().map(|buffer| buffer.is_empty());
+        self.connection
+            .get_mut()
+            .set_read_timeout(self.read_timeout);
+        match waited {
+            Ok(false) => Ok(()),
+            Ok(true) => {
+                self.close_received = true;
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "WebSocket closed without a close frame",
+                )
+                .into())
+            }
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                Err(WebServerError::Timeout(
+                    "No WebSocket message within the idle timeout".to_string(),
+                ))
+            }
+            Err(error) => {
+                self.close_received = true;
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Reads one frame (RFC 6455, section 5.2), unmasking its payload.
+    fn read_frame(&mut self) -> std::result::Result<Frame, ReadError> {
+        let mut header = [0; 2];
+        self.connection.read_exact(&mut header)?;
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        if header[0] & 0x70 != 0 {
+            // No extension was negotiated that could give them a meaning.
+            return Err(protocol_error("Reserved bits are set"));
+        }
+        if header[1] & 0x80 == 0 {
+            return Err(protocol_error("Client frames have to be masked"));
+        }
+
+        let len = match header[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                self.connection.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0; 8];
+                self.connection.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        if opcode >= OP_CLOSE && (!fin || len > 125) {
+            return Err(protocol_error("Control frames have to be short and whole"));
+        }
+        let buffered = self.fragments.as_ref().map_or(0, |(_, data)| data.len());
+        if len > self.max_message_size.saturating_sub(buffered) as u64 {
+            return Err(ReadError::Fail(
+                CLOSE_TOO_BIG,
+                format!("Message exceeds {} bytes", self.max_message_size),
+            ));
+        }
+
+        let mut mask = [0; 4];
+        self.connection.read_exact(&mut mask)?;
+        let mut payload = Vec::new();
+        (&mut self.connection).take(len).read_to_end(&mut payload)?;
+        if (payload.len() as u64) < len {
+            return Err(ReadError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Frame {
+            fin,
+            opcode,
+            payload,
+        })
+    }
+
+    /// Returns the message `frame` completes, if any.
+    fn handle_frame(
+        &mut self,
+        frame: Frame,
+    ) -> std::result::Result<Option<WebSocketMessage>, ReadError> {
+        let Frame {
+            fin,
+            opcode,
+            payload,
+        } = frame;
+        match opcode {
+            OP_PING => {
+                if !self.close_sent {
+                    self.write_control_frame(OP_PONG, &payload)
+                        .map_err(io_error)?;
+                }
+                Ok(Some(WebSocketMessage::Ping(payload)))
+            }
+            OP_PONG => Ok(Some(WebSocketMessage::Pong(payload))),
+            OP_CLOSE => {
+                let close = parse_close(&payload)?;
+                self.close_received = true;
+                if !self.close_sent {
+                    self.close_sent = true;
+                    // Echoes the code, as suggested by section 5.5.1.
+                    let code = close.as_ref().map(|(code, _)| (*code, String::new()));
+                    self.write_control_frame(OP_CLOSE, &close_payload(code))
+                        .map_err(io_error)?;
+                }
+                Ok(Some(WebSocketMessage::Close(close)))
+            }
+            OP_TEXT | OP_BINARY => {
+                if self.fragments.is_some() {
+                    return Err(protocol_error("Expected a continuation frame"));
+                }
+                if fin {
+                    message(opcode, payload).map(Some)
+                } else {
+                    self.fragments = Some((opcode, payload));
+                    Ok(None)
+                }
+            }
+            OP_CONTINUATION => {
+                let (message_opcode, mut data) = self
+                    .fragments
+                    .take()
+                    .ok_or_else(|| protocol_error("Continuation without a message"))?;
+                data.extend_from_slice(&payload);
+                if fin {
+                    message(message_opcode, data).map(Some)
+                } else {
+                    self.fragments = Some((message_opcode, data));
+                    Ok(None)
+                }
+            }
+            _ => Err(protocol_error("Unknown opcode")),
+        }
+    }
+
+    /// Closes the connection because of a protocol violation by the peer.
+    fn fail(&mut self, code: u16, message: String) -> WebServerError {
+        if !self.close_sent {
+            self.close_sent = true;
+            let _ = self.write_control_frame(OP_CLOSE, &close_payload(Some((code, String::new()))));
+        }
+        self.close_received = true;
+        if code == CLOSE_TOO_BIG {
+            WebServerError::PayloadTooLarge(message)
+        } else {
+            WebServerError::BadRequest(message)
+        }
+    }
+
+    fn write_control_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<()> {
+        if payload.len() > 125 {
+            return Err(WebServerError::Internal(
+                "Control frame payloads are limited to 125 bytes".to_string(),
+            ));
+        }
+        self.write_frame(opcode, payload)
+    }
+
+    /// Writes `payload` as one unmasked, final frame.
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let mut header = vec![0x80 | opcode];
+        match payload.len() {
+            len if len < 126 => header.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                header.push(126);
+                header.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                header.push(127);
+                header.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let stream = self.connection.get_mut();
+        stream.write_all(&header)?;
+        stream.write_all(payload)?;
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        if !self.close_sent {
+            let _ = self.send(WebSocketMessage::Close(Some((CLOSE_NORMAL, String::new()))));
+        }
+    }
+}
+
+impl std::fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("path", &self.request.path())
+            .field("close_sent", &self.close_sent)
+            .field("close_received", &self.close_received)
+            .finish_non_exhaustive()
+    }
+}
+
+fn closed() -> WebServerError {
+    WebServerError::Io(std::io::Error::new(
+        std::io::ErrorKind::NotConnected,
+        "WebSocket is closed",
+    ))
+}
+
+fn protocol_error(message: &str) -> ReadError {
+    ReadError::Fail(CLOSE_PROTOCOL_ERROR, message.to_string())
+}
+
+fn io_error(error: WebServerError) -> ReadError {
+    match error {
+        WebServerError::Io(error) => ReadError::Io(error),
+        error => ReadError::Io(std::io::Error::other(error.to_string())),
+    }
+}
+
+fn message(opcode: u8, payload: Vec<u8>) -> std::result::Result<WebSocketMessage, ReadError> {
+    if opcode == OP_BINARY {
+        return Ok(WebSocketMessage::Binary(payload));
+    }
+    String::from_utf8(payload)
+        .map(WebSocketMessage::Text)
+        .map_err(|_| ReadError::Fail(CLOSE_INVALID_DATA, "Text is not valid UTF-8".to_string()))
+}
+
+fn parse_close(payload: &[u8]) -> std::result::Result<Option<(u16, String)>, ReadError> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(protocol_error("Close frame with half a status code")),
+        [high, low, reason @ ..] => {
+            let reason = String::from_utf8(reason.to_vec()).map_err(|_| {
+                ReadError::Fail(
+                    CLOSE_INVALID_DATA,
+                    "Close reason is not valid UTF-8".to_string(),
+                )
+            })?;
+            Ok(Some((u16::from_be_bytes([*high, *low]), reason)))
+        }
+    }
+}
+
+fn close_payload(close: Option<(u16, String)>) -> Vec<u8> {
+    match close {
+        Some((code, reason)) => {
+            let mut payload = code.to_be_bytes().to_vec();
+            // Cut to fit into a control frame, on a character boundary.
+            let mut end = reason.len().min(123);
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            payload.extend_from_slice(&reason.as_bytes()[..end]);
+            payload
+        }
+        None => Vec::new(),
+    }
+}
+
+/// `Sec-WebSocket-Accept` for the client's `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    base64_encode(&sha1(format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes()))
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Whether `key` is 16 bytes in base64, as section 4.1 requires.
+fn is_valid_key(key: &str) -> bool {
+    let key = key.trim();
+    key.len() == 24
+        && key.ends_with("==")
+        && key[..22]
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'+' || byte == b'/')
+}
+
+/// Whether a comma-separated header contains `token`, ignoring case.
+fn has_token(request: &Request, name: &str, token: &str) -> bool {
+    request
+        .headers()
+        .get_all(name)
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Answers a request for a WebSocket route: with a `101` that hands the
+/// connection to `handler` once it is sent, or with an error if the request
+/// is not a valid opening handshake.
+pub(crate) fn upgrade(request: &Request, handler: &WebSocketHandler) -> Response {
+    if !has_token(request, "Connection", "upgrade") || !has_token(request, "Upgrade", "websocket") {
+        let mut response = error_page(
+            426,
+            "Upgrade Required",
+            "This resource is only available over WebSocket",
+        );
+        response.set_header("Upgrade", "websocket");
+        return response;
+    }
+    if request.version() == Version::Http10 {
+        return error_page(400, "Bad Request", "The WebSocket handshake needs HTTP/1.1");
+    }
+    if request.header("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        let mut response = error_page(
+            426,
+            "Upgrade Required",
+            "Only WebSocket version 13 is supported",
+        );
+        response.set_header("Sec-WebSocket-Version", "13");
+        return response;
+    }
+    let key = match request.header("Sec-WebSocket-Key") {
+        Some(key) if is_valid_key(key) => key,
+        _ => {
+            return error_page(
+                400,
+                "Bad Request",
+                "The WebSocket handshake has no valid key",
+            )
+        }
+    };
+
+    let mut response = Response::new(101);
+    response.set_header("Upgrade", "websocket");
+    response.set_header("Connection", "Upgrade");
+    response.set_header("Sec-WebSocket-Accept", &accept_key(key));
+    let handler = Arc::clone(handler);
+    response.set_upgrade(Box::new(move |upgraded| handler(WebSocket::new(upgraded))));
+    response
+}
+
+/// Held by an open WebSocket for as long as its thread runs, so that their
+/// number can be capped.
+pub(crate) struct WebSocketSlot {
+    stats: Arc<ServerStats>,
+}
+
+impl WebSocketSlot {
+    pub fn acquire(stats: &Arc<ServerStats>, max: usize) -> Option<WebSocketSlot> {
+        stats
+            .websockets
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                (open < max as u64).then_some(open + 1)
+            })
+            .ok()?;
+        Some(WebSocketSlot {
+            stats: Arc::clone(stats),
+        })
+    }
+}
+
+impl Drop for WebSocketSlot {
+    fn drop(&mut self) {
+        self.stats.websockets.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use web_server::{
+    AccessLog, AccessLogEntry, FileLogSink, HttpServer, LogFormat, LogSink, Method, RequestTiming,
+    Response, Router, Version,
+};
+
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<AccessLogEntry>>>);
+
+impl LogSink for Collect {
+    fn log(&self, entry: &AccessLogEntry) {
+        self.0.lock().unwrap().push(entry.clone());
+    }
+}
+
+impl Collect {
+    /// Entries are logged after the response is written, so wait for them.
+    fn wait_for(&self, count: usize) -> Vec<AccessLogEntry> {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) {
+            let entries = self.0.lock().unwrap();
+            if entries.len() >= count {
+                return entries.clone();
+            }
+            drop(entries);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("expected {} access log entries", count);
+    }
+}
+
+fn entry() -> AccessLogEntry {
+    AccessLogEntry {
+        peer: Some("10.0.0.7:5000".parse().unwrap()),
+        time: UNIX_EPOCH + Duration::from_secs(971186136),
+        method: Some(Method::Get),
+        target: Some("/apache_pb.gif?x=\"1\"".to_string()),
+        version: Some(Version::Http11),
+        status: 200,
+        response_size: 2326,
+        referer: Some("http://www.example.com/start.html".to_string()),
+        user_agent: Some("Mozilla/4.08".to_string()),
+        timing: RequestTiming {
+            parse: Duration::from_micros(30),
+            handle: Duration::from_micros(200),
+            write: Duration::from_micros(70),
+        },
+        detailed_timing: false,
+    }
+}
+
+#[test]
+fn entries_use_common_and_combined_format() {
+    let entry = entry();
+
+    assert_eq!(
+        entry.format(LogFormat::Common),
+        "10.0.0.7 - - [10/Oct/2000:13:55:36 +0000] \
+         \"GET /apache_pb.gif?x=\\\"1\\\" HTTP/1.1\" 200 2326"
+    );
+    assert_eq!(
+        entry.format(LogFormat::Combined),
+        "10.0.0.7 - - [10/Oct/2000:13:55:36 +0000] \
+         \"GET /apache_pb.gif?x=\\\"1\\\" HTTP/1.1\" 200 2326 \
+         \"http://www.example.com/start.html\" \"Mozilla/4.08\""
+    );
+}
+
+#[test]
+fn detailed_timing_breaks_down_the_time_of_a_request() {
+    let sink = Collect::default();
+    let mut access_log = AccessLog::new(sink.clone());
+    access_log.detailed_timing = true;
+    let mut router = Router::new();
+    router.get("/slow", |_| {
+        std::thread::sleep(Duration::from_millis(50));
+        Response::text("slow")
+    });
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .router(router)
+            .access_log(access_log),
+    );
+
+    let started = Instant::now();
+    common::send_raw(&address, "GET /slow HTTP/1.1\r\n\r\n");
+    let elapsed = started.elapsed();
+
+    let line = sink.wait_for(1)[0].format(LogFormat::Common);
+    let phases: Vec<(&str, u128)> = line
+        .rsplit(' ')
+        .take(4)
+        .map(|phase| {
+            let (name, micros) = phase.split_once('=').expect("a timing phase");
+            (name, micros.strip_suffix("us").unwrap().parse().unwrap())
+        })
+        .collect();
+    let names: Vec<&str> = phases.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["total", "write", "handle", "parse"], "{}", line);
+
+    let (handle, total) = (phases[2].1, phases[0].1);
+    assert!(handle >= 50_000, "{}", line);
+    assert!(total >= handle, "{}", line);
+    assert!(total <= elapsed.as_micros(), "{} took {:?}", line, elapsed);
+}
+
+#[test]
+fn server_logs_every_answered_request() {
+    let sink = Collect::default();
+    let mut access_log = AccessLog::new(sink.clone());
+    access_log.detailed_timing = true;
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .access_log(access_log),
+    );
+
+    common::send_raw(
+        &address,
+        "GET /hello.html HTTP/1.1\r\nUser-Agent: probe/1\r\n\r\n",
+    );
+    common::send_raw(&address, "HEAD /missing?q=1 HTTP/1.1\r\n\r\n");
+    common::send_raw(&address, "GET / HTTP/9\r\n\r\n");
+
+    let entries = sink.wait_for(3);
+    let hello_size = std::fs::metadata(common::content_dir().join("hello.html"))
+        .unwrap()
+        .len();
+
+    assert_eq!(entries[0].method, Some(Method::Get));
+    assert_eq!(entries[0].target.as_deref(), Some("/hello.html"));
+    assert_eq!(entries[0].status, 200);
+    assert_eq!(entries[0].response_size, hello_size);
+    assert_eq!(entries[0].user_agent.as_deref(), Some("probe/1"));
+    assert_eq!(entries[0].peer.unwrap().ip().to_string(), "127.0.0.1");
+    assert!(entries[0].detailed_timing);
+
+    assert_eq!(entries[1].method, Some(Method::Head));
+    assert_eq!(entries[1].target.as_deref(), Some("/missing?q=1"));
+    assert_eq!(entries[1].status, 404);
+    assert_eq!(entries[1].response_size, 0);
+
+    assert_eq!(entries[2].method, None);
+    assert_eq!(entries[2].status, 505);
+    assert!(entries[2].format(LogFormat::Common).contains(" \"-\" 505 "));
+}
+
+#[test]
+fn file_sink_appends_lines() {
+    let dir = common::temp_dir("access_log_file");
+    let path = dir.join("access.log");
+    let sink = FileLogSink::open(&path, LogFormat::Common).unwrap();
+
+    sink.log(&entry());
+    sink.log(&entry());
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(content.lines().count(), 2);
+    assert!(content.ends_with(" 200 2326\n"), "{}", content);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use web_server::HttpServer;
+
+#[test]
+fn builder_serves_files_from_configured_content_dir() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .content_dir(common::content_dir())
+            .threads(2),
+    );
+
+    let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    assert!(response.contains("<p>Hi from Rust</p>"), "{}", response);
+}
+
+#[test]
+fn zero_threads_is_rejected() {
+    assert!(HttpServer::builder()
+        .threads(0)
+        .bind("127.0.0.1:0")
+        .start()
+        .is_err());
+}
+
+#[test]
+fn idle_connection_is_dropped_after_read_timeout() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .read_timeout(Duration::from_millis(100)),
+    );
+
+    let mut stream = common::connect(&address);
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let started = Instant::now();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+
+    assert!(response.is_empty());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn oversized_request_is_not_read() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .max_request_size(64),
+    );
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"POST /x HTTP/1.1\r\nContent-Length: 100\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    assert!(
+        response.starts_with("HTTP/1.1 413 PAYLOAD TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("\r\nConnection: close\r\n"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+}
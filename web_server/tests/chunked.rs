@@ -0,0 +1,222 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use web_server::{HttpServer, Response, Router};
+
+fn start_echo_server() -> String {
+    let mut router = Router::new();
+    router
+        .post("/echo", |request| {
+            Response::text(&String::from_utf8_lossy(request.body()))
+        })
+        .get("/stream", |_| {
+            let mut response = Response::streaming(|writer| {
+                for part in ["first ", "second ", "third"] {
+                    writer.write_all(part.as_bytes())?;
+                    writer.flush()?;
+                }
+                Ok(())
+            });
+            response.set_header("Content-Type", "text/plain");
+            response
+        })
+        .get("/reader", |_| {
+            let mut response = Response::new(200);
+            response.set_chunked_body(&b"from a reader"[..]);
+            response
+        });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .max_request_size(256)
+            .router(router),
+    );
+    address
+}
+
+/// Decodes a chunked response body, checking the framing on the way.
+fn decode_chunks(mut body: &str) -> String {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n").expect("chunk size line");
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            assert_eq!(rest, "\r\n");
+            return decoded;
+        }
+        decoded.push_str(&rest[..size]);
+        assert_eq!(&rest[size..size + 2], "\r\n");
+        body = &rest[size + 2..];
+    }
+}
+
+#[test]
+fn chunked_request_bodies_are_decoded() {
+    let address = start_echo_server();
+
+    // Extensions and trailer fields are accepted and dropped.
+    let response = common::send_raw(
+        &address,
+        "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+         5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\nX-Trailer: yes\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nhello, world"), "{}", response);
+
+    // The connection stays usable for the request after the body.
+    let response = common::send_raw(
+        &address,
+        "POST /echo HTTP/1.1\r\ntransfer-encoding: Chunked\r\n\r\n\
+         3\r\none\r\n0\r\n\r\n\
+         POST /echo HTTP/1.1\r\nContent-Length: 3\r\n\r\ntwo",
+    );
+    assert!(
+        response.contains("\r\n\r\noneHTTP/1.1 200 OK"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\ntwo"), "{}", response);
+}
+
+#[test]
+fn invalid_chunked_requests_are_rejected() {
+    let address = start_echo_server();
+
+    let cases = [
+        // Larger than max_request_size, announced in one chunk...
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1000\r\n",
+            "413",
+        ),
+        // ...or put together from many small ones.
+        (
+            &format!(
+                "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{}0\r\n\r\n",
+                "8\r\n12345678\r\n".repeat(40)
+            ),
+            "413",
+        ),
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n+5\r\nhello\r\n0\r\n\r\n",
+            "400",
+        ),
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nhello\r\n0\r\n\r\n",
+            "400",
+        ),
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            "400",
+        ),
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n\
+             5\r\nhello\r\n0\r\n\r\n",
+            "400",
+        ),
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+            "501",
+        ),
+        (
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+            "501",
+        ),
+    ];
+    for (request, status) in cases {
+        let response = common::send_raw(&address, request);
+        assert!(
+            response.starts_with(&format!("HTTP/1.1 {} ", status)),
+            "{}\n---\n{}",
+            request,
+            response
+        );
+        assert!(
+            response.contains("\r\nConnection: close\r\n"),
+            "{}",
+            response
+        );
+    }
+}
+
+#[test]
+fn long_framing_lines_are_rejected() {
+    let mut router = Router::new();
+    router.post("/echo", |request| {
+        Response::text(&String::from_utf8_lossy(request.body()))
+    });
+    // Well within the default size limits of a megabyte.
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    // The server gives up once it has read 4096 bytes without a line break,
+    // which is all that is sent so the connection is not reset with unread
+    // data. It must not wait for the rest of the line, so the connection
+    // stays open for writing.
+    for body in [
+        format!("5;ext={}", "x".repeat(4090)),
+        format!("5\r\nhello\r\n0\r\nX-Trailer: {}", "x".repeat(4085)),
+    ] {
+        let suffix = body.rsplit("\r\n").next().unwrap();
+        assert_eq!(suffix.len(), 4096);
+        let mut stream = common::connect(&address);
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(
+            stream,
+            "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+    }
+
+    // Short extensions are still fine.
+    let response = common::send_raw(
+        &address,
+        "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+         5;ext=value\r\nhello\r\n0\r\n\r\n",
+    );
+    assert!(response.ends_with("\r\n\r\nhello"), "{}", response);
+}
+
+#[test]
+fn streaming_responses_are_sent_in_chunks() {
+    let address = start_echo_server();
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /stream HTTP/1.1\r\n\r\nGET /reader HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (first, second) = response.split_at(response.rfind("HTTP/1.1 200 OK").unwrap());
+    let (head, body) = first.split_once("\r\n\r\n").unwrap();
+    assert!(head.contains("\r\nTransfer-Encoding: chunked"), "{}", head);
+    assert!(head.contains("\r\nConnection: keep-alive"), "{}", head);
+    assert!(!head.contains("Content-Length"), "{}", head);
+    assert_eq!(decode_chunks(body), "first second third");
+
+    let (head, body) = second.split_once("\r\n\r\n").unwrap();
+    assert!(head.contains("\r\nTransfer-Encoding: chunked"), "{}", head);
+    assert_eq!(decode_chunks(body), "from a reader");
+}
+
+#[test]
+fn head_requests_get_no_chunks() {
+    let address = start_echo_server();
+
+    let response = common::send_raw(&address, "HEAD /stream HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nTransfer-Encoding: chunked\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
@@ -0,0 +1,83 @@
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use web_server::{HttpServer, HttpServerBuilder, Router, ServerConfig};
+
+/// Connects to a server that may still be binding its listener.
+pub fn connect(address: &str) -> TcpStream {
+    for _ in 0..100 {
+        if let Ok(stream) = TcpStream::connect(address) {
+            return stream;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    panic!("Could not connect to {}", address);
+}
+
+/// Starts `builder` on a port picked by the system, so tests cannot collide,
+/// and returns the server with the address it listens on.
+pub fn start_server(builder: HttpServerBuilder) -> (Arc<Mutex<HttpServer>>, String) {
+    let server = builder.bind("127.0.0.1:0").start().unwrap();
+    let address = server.lock().unwrap().local_addr().to_string();
+    (server, address)
+}
+
+/// Runs `config` on a port picked by the system, like `start_server`.
+pub fn run_server(config: ServerConfig) -> (Arc<Mutex<HttpServer>>, String) {
+    run_server_with_router(config, Router::new())
+}
+
+/// Runs `config` with `router` on a port picked by the system, like
+/// `start_server`.
+pub fn run_server_with_router(
+    config: ServerConfig,
+    router: Router,
+) -> (Arc<Mutex<HttpServer>>, String) {
+    let config = ServerConfig {
+        address: "127.0.0.1:0".to_string(),
+        ..config
+    };
+    let server = web_server::run_server_with_router(config, router).unwrap();
+    let address = server.lock().unwrap().local_addr().to_string();
+    (server, address)
+}
+
+/// A port nothing listens on right now, for tests that need to know the
+/// port before the server binds it.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Sends a raw request and returns everything the server wrote back. The
+/// write half is closed afterwards, so the server ends a persistent
+/// connection as soon as it has answered.
+pub fn send_raw(address: &str, request: &str) -> String {
+    let mut stream = connect(address);
+    stream.write_all(request.as_bytes()).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// Creates an empty directory under the system temp dir, unique to `name`.
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("web_server_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The `content` directory shipped with the repository.
+pub fn content_dir() -> std::path::PathBuf {
+    std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../content")
+}
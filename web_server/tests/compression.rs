@@ -0,0 +1,239 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use web_server::{CompressionPolicy, HttpServer, Response, Router};
+
+/// Splits a response into its head and raw body bytes.
+fn exchange(address: &str, request: &str) -> (String, Vec<u8>) {
+    let mut stream = common::connect(address);
+    stream.write_all(request.as_bytes()).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let head_end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let body = response.split_off(head_end);
+    (String::from_utf8(response).unwrap(), body)
+}
+
+/// Returns the address of a new server with its own content directory.
+fn start() -> String {
+    static SERVERS: AtomicUsize = AtomicUsize::new(0);
+    let server = SERVERS.fetch_add(1, Ordering::Relaxed);
+    let dir = common::temp_dir(&format!("compression_{}", server));
+    std::fs::write(dir.join("page.html"), "<p>hello</p>\n".repeat(200)).unwrap();
+    std::fs::write(dir.join("image.png"), vec![7u8; 4096]).unwrap();
+
+    let mut router = Router::new();
+    router
+        .get("/small", |_| Response::text("short"))
+        .get("/large", |_| {
+            Response::text(&"generated line\n".repeat(500))
+        });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(dir)
+            .router(router)
+            .compression(CompressionPolicy {
+                min_size: 256,
+                ..CompressionPolicy::default()
+            }),
+    );
+    address
+}
+
+#[test]
+fn static_text_is_gzipped_when_accepted() {
+    let address = start();
+
+    let (head, body) = exchange(
+        &address,
+        "GET /page.html HTTP/1.1\r\nAccept-Encoding: gzip, deflate\r\n\r\n",
+    );
+    assert!(head.contains("\r\nContent-Encoding: gzip\r\n"), "{}", head);
+    assert!(head.contains("\r\nVary: Accept-Encoding\r\n"), "{}", head);
+    assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", body.len())));
+    assert!(head.contains("\r\nETag: W/\""), "{}", head);
+    assert!(!head.contains("Accept-Ranges"), "{}", head);
+    let mut decoded = String::new();
+    GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "<p>hello</p>\n".repeat(200));
+
+    let (head, body) = exchange(&address, "GET /page.html HTTP/1.1\r\n\r\n");
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    assert!(head.contains("\r\nVary: Accept-Encoding\r\n"), "{}", head);
+    assert_eq!(body.len(), "<p>hello</p>\n".len() * 200);
+
+    let (head, _) = exchange(
+        &address,
+        "HEAD /page.html HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(head.contains("\r\nContent-Encoding: gzip\r\n"), "{}", head);
+}
+
+#[test]
+fn deflate_is_used_when_gzip_is_refused() {
+    let address = start();
+
+    let (head, body) = exchange(
+        &address,
+        "GET /large HTTP/1.1\r\nAccept-Encoding: gzip;q=0, deflate;q=0.5\r\n\r\n",
+    );
+    assert!(
+        head.contains("\r\nContent-Encoding: deflate\r\n"),
+        "{}",
+        head
+    );
+    let mut decoded = String::new();
+    ZlibDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "generated line\n".repeat(500));
+
+    let (head, _) = exchange(
+        &address,
+        "GET /large HTTP/1.1\r\nAccept-Encoding: identity\r\n\r\n",
+    );
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+}
+
+#[test]
+fn small_and_binary_bodies_are_sent_as_is() {
+    let address = start();
+
+    let (head, body) = exchange(
+        &address,
+        "GET /small HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    assert!(head.contains("\r\nContent-Length: 5\r\n"), "{}", head);
+    assert_eq!(body, b"short");
+
+    let (head, body) = exchange(
+        &address,
+        "GET /image.png HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    assert!(!head.contains("Vary"), "{}", head);
+    assert_eq!(body, vec![7u8; 4096]);
+}
+
+#[test]
+fn compression_is_off_by_default() {
+    let mut router = Router::new();
+    router.get("/large", |_| {
+        Response::text(&"generated line\n".repeat(500))
+    });
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    let (head, _) = exchange(
+        &address,
+        "GET /large HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+}
+
+/// The data of a chunked body.
+fn decode_chunks(mut body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n").unwrap();
+        let size = std::str::from_utf8(&body[..line_end]).unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return decoded;
+        }
+        decoded.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+#[test]
+fn bodies_of_unknown_length_are_compressed_as_they_are_written() {
+    let mut router = Router::new();
+    router
+        .get("/streamed", |request| {
+            let lines = request.query().get("lines").unwrap_or("0").parse().unwrap();
+            let mut response = Response::streaming(move |writer| {
+                for _ in 0..lines {
+                    writer.write_all(b"streamed line\n")?;
+                }
+                Ok(())
+            });
+            response.set_header("Content-Type", "text/plain");
+            response
+        })
+        .get("/reader", |request| {
+            let lines = request.query().get("lines").unwrap_or("0").parse().unwrap();
+            let mut response = Response::new(200);
+            response.set_header("Content-Type", "text/plain");
+            response.set_body_reader(std::io::Cursor::new("reader line\n".repeat(lines)), None);
+            response
+        });
+    let (_server, address) =
+        common::start_server(HttpServer::builder().threads(1).router(router).compression(
+            CompressionPolicy {
+                min_size: 256,
+                ..CompressionPolicy::default()
+            },
+        ));
+
+    let (head, body) = exchange(
+        &address,
+        "GET /streamed?lines=500 HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(head.contains("\r\nContent-Encoding: gzip\r\n"), "{}", head);
+    assert!(
+        head.contains("\r\nTransfer-Encoding: chunked\r\n"),
+        "{}",
+        head
+    );
+    let mut decoded = String::new();
+    GzDecoder::new(&decode_chunks(&body)[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "streamed line\n".repeat(500));
+
+    // Either kind of body is looked at up to `min_size` before it is
+    // compressed.
+    let (head, body) = exchange(
+        &address,
+        "GET /streamed?lines=2 HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    assert!(!head.contains("Transfer-Encoding"), "{}", head);
+    assert!(head.contains("\r\nContent-Length: 28\r\n"), "{}", head);
+    assert_eq!(body, "streamed line\n".repeat(2).as_bytes());
+
+    let (head, body) = exchange(
+        &address,
+        "GET /reader?lines=500 HTTP/1.1\r\nAccept-Encoding: deflate\r\n\r\n",
+    );
+    assert!(
+        head.contains("\r\nContent-Encoding: deflate\r\n"),
+        "{}",
+        head
+    );
+    let mut decoded = String::new();
+    ZlibDecoder::new(&decode_chunks(&body)[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "reader line\n".repeat(500));
+
+    let (head, body) = exchange(
+        &address,
+        "GET /reader?lines=2 HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+    );
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    assert!(head.contains("\r\nContent-Length: 24\r\n"), "{}", head);
+    assert_eq!(body, "reader line\n".repeat(2).as_bytes());
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use web_server::{format_http_date, parse_http_date, HttpServer};
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response
+        .split("\r\n")
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+}
+
+#[test]
+fn http_dates_round_trip() {
+    let time = UNIX_EPOCH + Duration::from_secs(784111777);
+
+    assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+    assert_eq!(
+        format_http_date(UNIX_EPOCH + Duration::from_secs(951782400)),
+        "Tue, 29 Feb 2000 00:00:00 GMT"
+    );
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+}
+
+#[test]
+fn static_files_are_revalidated() {
+    let dir = common::temp_dir("conditional_static");
+    std::fs::create_dir_all(dir.join("assets")).unwrap();
+    std::fs::write(dir.join("index.html"), "<p>hi</p>").unwrap();
+    std::fs::write(dir.join("assets/app.css"), "p {}").unwrap();
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(dir)
+            .cache_control("/assets/*", "public, max-age=31536000")
+            .cache_control("*.html", "no-cache"),
+    );
+
+    let response = common::send_raw(&address, "GET /index.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(header(&response, "Cache-Control"), Some("no-cache"));
+    let etag = header(&response, "ETag").unwrap().to_string();
+    let last_modified = header(&response, "Last-Modified").unwrap().to_string();
+
+    let response = common::send_raw(
+        &address,
+        &format!(
+            "GET /index.html HTTP/1.1\r\nIf-None-Match: {}\r\n\r\n",
+            etag
+        ),
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 304 NOT MODIFIED\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(header(&response, "ETag"), Some(etag.as_str()));
+    assert_eq!(header(&response, "Content-Length"), None);
+    assert!(response.ends_with("\r\n\r\n"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /index.html HTTP/1.1\r\nIf-None-Match: \"other\"\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        &format!(
+            "GET /index.html HTTP/1.1\r\nIf-Modified-Since: {}\r\n\r\n",
+            last_modified
+        ),
+    );
+    assert!(response.starts_with("HTTP/1.1 304 "), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /index.html HTTP/1.1\r\nIf-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /assets/app.css HTTP/1.1\r\n\r\n");
+    assert_eq!(
+        header(&response, "Cache-Control"),
+        Some("public, max-age=31536000")
+    );
+}
+
+#[test]
+fn stale_if_range_serves_the_full_file() {
+    let dir = common::temp_dir("conditional_if_range");
+    std::fs::write(dir.join("clip.bin"), "0123456789").unwrap();
+
+    let (_server, address) =
+        common::start_server(HttpServer::builder().threads(1).content_dir(dir));
+
+    let response = common::send_raw(&address, "GET /clip.bin HTTP/1.1\r\n\r\n");
+    assert_eq!(header(&response, "Cache-Control"), None);
+    let etag = header(&response, "ETag").unwrap().to_string();
+
+    let response = common::send_raw(
+        &address,
+        &format!(
+            "GET /clip.bin HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: {}\r\n\r\n",
+            etag
+        ),
+    );
+    assert!(response.starts_with("HTTP/1.1 206 "), "{}", response);
+    assert!(response.ends_with("\r\n\r\n01"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /clip.bin HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"stale\"\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\n0123456789"), "{}", response);
+}
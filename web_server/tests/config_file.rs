@@ -0,0 +1,208 @@
+mod common;
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+use web_server::HttpServer;
+
+fn get(address: &str, host: &str, path: &str) -> String {
+    common::send_raw(
+        address,
+        &format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host),
+    )
+}
+
+#[test]
+fn settings_are_read_from_a_toml_file() {
+    let dir = common::temp_dir("config_file");
+    std::fs::create_dir_all(dir.join("www")).unwrap();
+    std::fs::create_dir_all(dir.join("other")).unwrap();
+    std::fs::write(dir.join("www/index.html"), "default site").unwrap();
+    std::fs::write(dir.join("www/notes.note"), "a note").unwrap();
+    std::fs::write(dir.join("other/index.html"), "other site").unwrap();
+    std::fs::write(
+        dir.join("server.toml"),
+        r#"
+# Paths are relative to this file.
+bind = "127.0.0.1:0"
+threads = 2
+root = "www"
+server_header = "config-file"
+
+[limits]
+max_uri_length = 64
+max_header_count = 8   # comments may follow values
+
+[timeouts]
+read = 5
+keep_alive = 0.5
+
+[cache]
+max_bytes = 1_048_576
+
+[mime_types]
+note = "text/x-note"
+
+[cache_control]
+"*.note" = "max-age=60"
+
+[[vhost]]
+host = "other.test"
+root = "other"
+
+[[vhost]]
+host = "*.default.test"
+"#,
+    )
+    .unwrap();
+
+    let server = HttpServer::builder()
+        .config_file(dir.join("server.toml"))
+        .unwrap()
+        .start()
+        .unwrap();
+
+    let address = server.lock().unwrap().local_addr().to_string();
+    let response = get(&address, "localhost", "/");
+    assert!(response.ends_with("\r\n\r\ndefault site"), "{}", response);
+    assert!(
+        response.contains("\r\nServer: config-file\r\n"),
+        "{}",
+        response
+    );
+    let response = get(&address, "other.test", "/");
+    assert!(response.ends_with("\r\n\r\nother site"), "{}", response);
+    let response = get(&address, "a.default.test", "/");
+    assert!(response.ends_with("\r\n\r\ndefault site"), "{}", response);
+
+    let response = get(&address, "localhost", "/notes.note");
+    assert!(
+        response.contains("\r\nContent-Type: text/x-note\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nCache-Control: max-age=60\r\n"),
+        "{}",
+        response
+    );
+    let response = get(&address, "localhost", &format!("/{}", "a".repeat(64)));
+    assert!(response.starts_with("HTTP/1.1 414 "), "{}", response);
+}
+
+#[test]
+fn mistakes_are_reported_with_file_and_line() {
+    let dir = common::temp_dir("config_file_errors");
+    let error_for = |text: &str| {
+        let path = dir.join("server.toml");
+        std::fs::write(&path, text).unwrap();
+        let error = HttpServer::builder().config_file(&path).err().unwrap();
+        let message = error.to_string();
+        let prefix = format!("Invalid configuration: {}, ", path.display());
+        assert!(message.starts_with(&prefix), "{}", message);
+        message[prefix.len()..].to_string()
+    };
+
+    assert_eq!(
+        error_for("threads = 4\nthreds = 4\n"),
+        "line 2: unknown key `threds`"
+    );
+    assert_eq!(
+        error_for("[limits]\nmax_body_size = \"1M\"\n"),
+        "line 2: `max_body_size` must be an integer, not a string"
+    );
+    assert_eq!(error_for("\n[limit]\n"), "line 2: unknown table `[limit]`");
+    assert_eq!(
+        error_for("[cache]\nmax_bytes = 1\n[cache]\n"),
+        "line 3: `cache` is already defined on line 1"
+    );
+    assert_eq!(
+        error_for("bind = \"127.0.0.1:80\nthreads = 2\n"),
+        "line 1: unterminated string"
+    );
+    assert_eq!(
+        error_for("root = \"\\u+041\"\n"),
+        "line 1: invalid unicode escape"
+    );
+    assert_eq!(
+        error_for("index_files = [\"a.html\",\n  \"b.html\" 1]\n"),
+        "line 2: expected `]`, found `1`"
+    );
+    assert_eq!(
+        error_for("[[vhost]]\nroot = \"site\"\n"),
+        "line 1: `[[vhost]]` needs a `host`"
+    );
+
+    let missing = dir.join("missing.toml");
+    let error = HttpServer::builder().config_file(&missing).err().unwrap();
+    assert!(
+        error.to_string().contains(&missing.display().to_string()),
+        "{}",
+        error
+    );
+}
+
+/// Kills the server binary when the test ends, also on failure.
+struct ServerProcess(Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn command_line_options_override_the_file() {
+    let dir = common::temp_dir("config_file_cli");
+    std::fs::create_dir_all(dir.join("from_file")).unwrap();
+    std::fs::create_dir_all(dir.join("from_flag")).unwrap();
+    std::fs::write(dir.join("from_file/index.html"), "file root").unwrap();
+    std::fs::write(dir.join("from_flag/index.html"), "flag root").unwrap();
+    std::fs::write(
+        dir.join("server.toml"),
+        "bind = \"127.0.0.1:1\"\nroot = \"from_file\"\nthreads = 1\n",
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_web_server"))
+        .arg("--config")
+        .arg(dir.join("server.toml"))
+        .arg("--bind=127.0.0.1:0")
+        .arg("-r")
+        .arg(dir.join("from_flag"))
+        .arg("--log")
+        .arg(dir.join("access.log"))
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let _server = ServerProcess(child);
+
+    // The port picked by the system is printed once the server listens.
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let address = line
+        .trim_end()
+        .strip_prefix("web_server: listening on ")
+        .unwrap_or_else(|| panic!("{:?}", line));
+
+    let response = common::send_raw(address, "GET / HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nflag root"), "{}", response);
+    let log = std::fs::read_to_string(dir.join("access.log")).unwrap();
+    assert!(log.contains("\"GET / HTTP/1.1\" 200 "), "{}", log);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_web_server"))
+        .arg("--threads")
+        .arg("none")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("web_server: invalid thread count `none`\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Usage: web_server"), "{}", stderr);
+}
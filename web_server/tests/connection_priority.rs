@@ -0,0 +1,113 @@
+mod common;
+
+use std::net::TcpStream;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use web_server::{HttpServer, Response, Router};
+
+#[test]
+fn small_static_files_overtake_queued_handlers() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let (entered, blocked) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let released = Mutex::new(released);
+
+    let mut router = Router::new();
+    router
+        .get("/block", move |_| {
+            entered.send(()).unwrap();
+            released.lock().unwrap().recv().unwrap();
+            Response::text("unblocked")
+        })
+        .get("/dynamic", |_| Response::text("dynamic"));
+    let recorded = Arc::clone(&order);
+    router.middleware(move |request, next| {
+        recorded.lock().unwrap().push(request.path().to_string());
+        next(request)
+    });
+    let (server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .router(router),
+    );
+
+    // The only worker is busy, so everything else queues up behind it.
+    let send = |target: &'static str| {
+        let address = address.clone();
+        std::thread::spawn(move || {
+            common::send_raw(&address, &format!("GET {} HTTP/1.1\r\n\r\n", target))
+        })
+    };
+    let mut clients = vec![send("/block")];
+    blocked.recv().unwrap();
+    let queued = |count| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.lock().unwrap().queued_connections() < count {
+            assert!(Instant::now() < deadline, "connections were not queued");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    };
+    for count in 1..=3 {
+        clients.push(send("/dynamic"));
+        queued(count);
+    }
+    clients.push(send("/hello.html"));
+    queued(4);
+
+    release.send(()).unwrap();
+    for client in clients {
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    }
+    assert_eq!(
+        *order.lock().unwrap(),
+        ["/block", "/hello.html", "/dynamic", "/dynamic", "/dynamic"]
+    );
+}
+
+#[test]
+fn silent_connections_do_not_hold_up_the_accept_loop() {
+    let (entered, blocked) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let released = Mutex::new(released);
+    let mut router = Router::new();
+    router.get("/block", move |_| {
+        entered.send(()).unwrap();
+        released.lock().unwrap().recv().unwrap();
+        Response::text("unblocked")
+    });
+    let (server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    // With the only worker busy, every connection gets looked at before it
+    // is queued.
+    let blocking = {
+        let address = address.clone();
+        std::thread::spawn(move || common::send_raw(&address, "GET /block HTTP/1.1\r\n\r\n"))
+    };
+    blocked.recv().unwrap();
+
+    // Clients that have not sent anything yet are queued right away.
+    let started = Instant::now();
+    let silent: Vec<TcpStream> = (0..20).map(|_| common::connect(&address)).collect();
+    let queued = common::connect(&address);
+    while server.lock().unwrap().queued_connections() < 21 {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "connections were not queued"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(
+        started.elapsed() < Duration::from_millis(200),
+        "queueing took {:?}",
+        started.elapsed()
+    );
+
+    drop(silent);
+    drop(queued);
+    release.send(()).unwrap();
+    let response = blocking.join().unwrap();
+    assert!(response.ends_with("\r\n\r\nunblocked"), "{}", response);
+}
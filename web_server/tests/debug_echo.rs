@@ -0,0 +1,52 @@
+mod common;
+
+use web_server::ServerConfig;
+
+#[test]
+fn echo_endpoint_reflects_parsed_request() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        debug_echo_path: Some("/debug/echo".to_string()),
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(
+        &address,
+        "GET /debug/echo?name=value&flag HTTP/1.1\r\n\
+         Host: localhost\r\n\
+         X-Second: \"quoted\"\r\n\
+         X-First: 1\r\n\r\n",
+    );
+
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    assert!(response.contains("\r\nContent-Type: application/json\r\n"));
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    assert!(body.contains("\"method\":\"GET\""), "{}", body);
+    assert!(body.contains("\"path\":\"/debug/echo\""), "{}", body);
+    assert!(
+        body.contains("\"query\":[[\"name\",\"value\"],[\"flag\",\"\"]]"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains(
+            "\"headers\":[[\"Host\",\"localhost\"],[\"X-Second\",\"\\\"quoted\\\"\"],[\"X-First\",\"1\"]]"
+        ),
+        "{}",
+        body
+    );
+    assert!(body.contains("\"peer\":\"127.0.0.1:"), "{}", body);
+    assert!(body.contains("\"request_id\":"), "{}", body);
+}
+
+#[test]
+fn echo_endpoint_is_disabled_by_default() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /debug/echo HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use web_server::HttpServer;
+
+fn site(name: &str) -> std::path::PathBuf {
+    let root = common::temp_dir(name);
+    std::fs::create_dir_all(root.join("docs/guide")).unwrap();
+    std::fs::create_dir_all(root.join("blog")).unwrap();
+    std::fs::write(root.join("index.html"), "<p>home</p>").unwrap();
+    std::fs::write(root.join("blog/index.html"), "<p>blog</p>").unwrap();
+    std::fs::write(root.join("docs/a & b.txt"), "12345").unwrap();
+    std::fs::write(root.join("docs/<z>.txt"), "").unwrap();
+    root
+}
+
+#[test]
+fn directories_serve_their_index_file() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(site("directory_index")),
+    );
+
+    let response = common::send_raw(&address, "GET / HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nContent-Type: text/html"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\n<p>home</p>"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /blog/ HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\n<p>blog</p>"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /blog?page=2 HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 301 MOVED PERMANENTLY\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nLocation: /blog/?page=2\r\n"),
+        "{}",
+        response
+    );
+
+    // Without an index and with listings off, a directory does not exist.
+    let response = common::send_raw(&address, "GET /docs/ HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+}
+
+#[test]
+fn listing_shows_directory_content_when_enabled() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(site("directory_listing"))
+            .directory_listing(true),
+    );
+
+    let response = common::send_raw(&address, "GET /docs/ HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("<title>Index of /docs/</title>"),
+        "{}",
+        response
+    );
+    assert!(response.contains("<a href=\"../\">../</a>"), "{}", response);
+
+    let guide = response.find("href=\"guide/\">guide/</a>").unwrap();
+    let file = response
+        .find("<a href=\"a%20%26%20b.txt\">a &amp; b.txt</a></td><td>5</td>")
+        .unwrap();
+    let escaped = response
+        .find("<a href=\"%3Cz%3E.txt\">&lt;z&gt;.txt</a>")
+        .unwrap();
+    assert!(guide < escaped && escaped < file, "{}", response);
+
+    // Directories with an index still serve it.
+    let response = common::send_raw(&address, "GET /blog/ HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\n<p>blog</p>"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /docs/a%20%26%20b.txt HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\n12345"), "{}", response);
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use web_server::{HttpServer, Response, Router};
+
+#[test]
+fn registered_pages_replace_the_built_in_ones() {
+    let content = common::temp_dir("error_pages");
+    std::fs::create_dir_all(content.join("errors")).unwrap();
+    std::fs::write(
+        content.join("errors/404.html"),
+        "<h1>{{status}} {{reason}}</h1><p>No {{path}} here</p>",
+    )
+    .unwrap();
+
+    let mut router = Router::new();
+    router
+        .get("/forbidden", |_| Response::new(403))
+        .get("/gone", |_| {
+            let mut response = Response::json("{\"error\":\"gone\"}");
+            response.set_status(410);
+            response
+        });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(&content)
+            .error_page(404, "errors/404.html")
+            .error_handler(403, |request, status| {
+                Response::text(&format!("{} for {}", status, request.path()))
+            })
+            .router(router),
+    );
+
+    let response = common::send_raw(&address, "GET /a<b> HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 404 NOT FOUND\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nContent-Type: text/html; charset=utf-8\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.ends_with("\r\n\r\n<h1>404 Not Found</h1><p>No /a&lt;b&gt; here</p>"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "GET /forbidden HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 403 FORBIDDEN\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.ends_with("\r\n\r\n403 for /forbidden"),
+        "{}",
+        response
+    );
+
+    // A body of the handler's own is kept.
+    let response = common::send_raw(&address, "GET /gone HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 410 GONE\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.ends_with("\r\n\r\n{\"error\":\"gone\"}"),
+        "{}",
+        response
+    );
+
+    // HEAD gets the headers of the page, but no body.
+    let response = common::send_raw(&address, "HEAD /missing HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    assert!(
+        response.contains("\r\nContent-Length: 45\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
+
+#[test]
+fn bare_error_responses_get_a_default_page() {
+    let mut router = Router::new();
+    router
+        .post("/post-only", |_| Response::ok())
+        .get("/panic", |_| panic!("handler failure"));
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            // A template that does not exist falls back to the default page.
+            .error_page(500, "errors/missing.html")
+            .router(router),
+    );
+
+    let response = common::send_raw(&address, "GET /post-only HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 405 METHOD NOT ALLOWED\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nAllow: POST, OPTIONS\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("<title>405 Method Not Allowed</title>"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "GET /panic HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 500 "), "{}", response);
+    assert!(
+        response.contains("<h1>Internal Server Error</h1>"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "GET /missing.html HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("<p>Could not find /missing.html</p>"),
+        "{}",
+        response
+    );
+}
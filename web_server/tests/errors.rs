@@ -0,0 +1,132 @@
+mod common;
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use web_server::{HttpServer, ServerConfig, WebServerError};
+
+#[test]
+fn errors_map_to_status_codes() {
+    let cases = [
+        (WebServerError::BadRequest(String::new()), 400),
+        (WebServerError::Forbidden(String::new()), 403),
+        (WebServerError::NotFound(String::new()), 404),
+        (WebServerError::Timeout(String::new()), 408),
+        (WebServerError::PayloadTooLarge(String::new()), 413),
+        (WebServerError::UriTooLong(String::new()), 414),
+        (WebServerError::HeaderFieldsTooLarge(String::new()), 431),
+        (WebServerError::NotImplemented(String::new()), 501),
+        (WebServerError::VersionNotSupported(String::new()), 505),
+        (WebServerError::Internal(String::new()), 500),
+    ];
+    for (error, status) in cases {
+        assert_eq!(error.status_code(), status, "{:?}", error);
+    }
+}
+
+#[test]
+fn io_errors_keep_their_kind() {
+    let error = WebServerError::from(std::io::Error::new(
+        std::io::ErrorKind::ConnectionReset,
+        "reset",
+    ));
+    match &error {
+        WebServerError::Io(io) => assert_eq!(io.kind(), std::io::ErrorKind::ConnectionReset),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(error.source().is_some());
+    assert_eq!(error.status_code(), 500);
+
+    let error = WebServerError::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+    assert!(matches!(error, WebServerError::Timeout(_)), "{:?}", error);
+
+    let error = match web_server::ThreadPool::new(0) {
+        Err(error) => error,
+        Ok(_) => panic!("a pool without threads was created"),
+    };
+    assert!(matches!(error, WebServerError::Config(_)), "{:?}", error);
+}
+
+#[test]
+fn malformed_requests_get_an_error_status() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        max_request_size: 256,
+        ..ServerConfig::default()
+    });
+
+    let cases = [
+        ("GET /\r\n\r\n", "400 BAD REQUEST"),
+        ("GET / HTTP/1.1 extra\r\n\r\n", "400 BAD REQUEST"),
+        ("GET / HTTP/1.1\r\nno colon here\r\n\r\n", "400 BAD REQUEST"),
+        ("GET / HTTP/1.1\r\n: no name\r\n\r\n", "400 BAD REQUEST"),
+        (
+            "GET / HTTP/1.1\r\nContent-Length : 0\r\n\r\n",
+            "400 BAD REQUEST",
+        ),
+        ("GET / HTTP/1.1\r\nHost\t: a\r\n\r\n", "400 BAD REQUEST"),
+        (
+            "GET / HTTP/1.1\r\nX-Long: a\r\n folded: b\r\n\r\n",
+            "400 BAD REQUEST",
+        ),
+        (
+            "POST / HTTP/1.1\r\nContent-Length: ten\r\n\r\n",
+            "400 BAD REQUEST",
+        ),
+        ("GET / HTTP/1.1\r\nHost: a", "400 BAD REQUEST"),
+        ("BREW /pot HTTP/1.1\r\n\r\n", "501 NOT IMPLEMENTED"),
+        ("GET / HTTP/2.0\r\n\r\n", "505 HTTP VERSION NOT SUPPORTED"),
+    ];
+    for (request, status) in cases {
+        let response = common::send_raw(&address, request);
+        assert!(
+            response.starts_with(&format!("HTTP/1.1 {}\r\n", status)),
+            "{:?}: {}",
+            request,
+            response
+        );
+        assert!(
+            response.contains("\r\nConnection: close\r\n"),
+            "{}",
+            response
+        );
+    }
+
+    // The body names the status, not what the parser found.
+    let response = common::send_raw(&address, "GET / HTTP/1.1\r\nno colon here\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nBAD REQUEST"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        &format!("GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(300)),
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn stalled_request_gets_request_timeout() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .read_timeout(Duration::from_millis(100)),
+    );
+
+    let mut stream = common::connect(&address);
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: a").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(
+        response.starts_with("HTTP/1.1 408 REQUEST TIMEOUT\r\n"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use web_server::{FileCache, HttpServer};
+
+fn body(response: &str) -> &str {
+    response.split_once("\r\n\r\n").unwrap().1
+}
+
+#[test]
+fn files_are_served_from_memory_until_they_change() {
+    let dir = common::temp_dir("file_cache");
+    std::fs::write(dir.join("a.txt"), "aaaaaa").unwrap();
+    std::fs::write(dir.join("b.txt"), "bbbbbb").unwrap();
+    std::fs::write(dir.join("big.txt"), "0123456789").unwrap();
+
+    let cache = FileCache::new(12).max_file_size(8);
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(&dir)
+            .file_cache(cache.clone()),
+    );
+
+    let first = common::send_raw(&address, "GET /a.txt HTTP/1.1\r\n\r\n");
+    assert_eq!(body(&first), "aaaaaa");
+    let second = common::send_raw(&address, "GET /a.txt HTTP/1.1\r\n\r\n");
+    assert_eq!(body(&second), "aaaaaa");
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    assert_eq!((cache.len(), cache.size()), (1, 6));
+
+    // Served the same way as from disk, validators included.
+    let etag = |response: &str| {
+        response
+            .lines()
+            .find(|line| line.starts_with("ETag: "))
+            .map(str::to_string)
+    };
+    assert_eq!(etag(&first), etag(&second));
+    assert!(etag(&second).is_some());
+    let response = common::send_raw(&address, "GET /a.txt HTTP/1.1\r\nRange: bytes=1-2\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 206 "), "{}", response);
+    assert_eq!(body(&response), "aa");
+
+    // A different modification time invalidates the entry.
+    std::fs::write(dir.join("a.txt"), "AAAAAA").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("a.txt"))
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(10))
+        .unwrap();
+    let response = common::send_raw(&address, "GET /a.txt HTTP/1.1\r\n\r\n");
+    assert_eq!(body(&response), "AAAAAA");
+    assert_ne!(etag(&response), etag(&first));
+    assert_eq!((cache.hits(), cache.misses()), (2, 2));
+
+    // Both files do not fit, so the least recently used one goes.
+    let response = common::send_raw(&address, "GET /b.txt HTTP/1.1\r\n\r\n");
+    assert_eq!(body(&response), "bbbbbb");
+    assert_eq!((cache.len(), cache.size()), (2, 12));
+    common::send_raw(&address, "GET /b.txt HTTP/1.1\r\n\r\n");
+    std::fs::write(dir.join("c.txt"), "cccccc").unwrap();
+    common::send_raw(&address, "GET /c.txt HTTP/1.1\r\n\r\n");
+    assert_eq!((cache.hits(), cache.misses()), (3, 4));
+    common::send_raw(&address, "GET /b.txt HTTP/1.1\r\n\r\n");
+    common::send_raw(&address, "GET /a.txt HTTP/1.1\r\n\r\n");
+    assert_eq!((cache.hits(), cache.misses()), (4, 5));
+    assert_eq!((cache.len(), cache.size()), (2, 12));
+
+    // Too big to be cached: streamed and not counted.
+    let response = common::send_raw(&address, "GET /big.txt HTTP/1.1\r\n\r\n");
+    assert_eq!(body(&response), "0123456789");
+    assert_eq!((cache.hits(), cache.misses()), (4, 5));
+}
+
+#[test]
+fn cache_counters_are_part_of_the_metrics() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .file_cache(FileCache::new(1024 * 1024))
+            .metrics("/metrics"),
+    );
+
+    common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+
+    let response = common::send_raw(&address, "GET /metrics HTTP/1.1\r\n\r\n");
+    let metrics = body(&response);
+    assert!(
+        metrics.contains("\nfile_cache_hits_total 1\n"),
+        "{}",
+        metrics
+    );
+    assert!(
+        metrics.contains("\nfile_cache_misses_total 1\n"),
+        "{}",
+        metrics
+    );
+    assert!(metrics.contains("\nfile_cache_entries 1\n"), "{}", metrics);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::io::Read;
+
+use web_server::{Response, Router, ServerConfig};
+
+#[test]
+fn file_response_takes_length_from_metadata() {
+    let dir = common::temp_dir("file_response_length");
+    let path = dir.join("data.bin");
+    std::fs::write(&path, b"0123456789").unwrap();
+
+    let response = Response::from_file(&path).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header("Content-Length"), Some("10"));
+    assert_eq!(response.body(), None);
+    let wire = String::from_utf8(response.to_bytes().unwrap()).unwrap();
+    assert!(wire.ends_with("\r\n\r\n0123456789"), "{}", wire);
+}
+
+#[test]
+fn directories_are_not_file_responses() {
+    let dir = common::temp_dir("file_response_directory");
+
+    assert!(Response::from_file(&dir).is_err());
+    assert!(Response::from_file(dir.join("missing")).is_err());
+}
+
+#[test]
+fn large_static_file_is_streamed_completely() {
+    let dir = common::temp_dir("file_response_static");
+    let content: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    std::fs::write(dir.join("video.bin"), &content).unwrap();
+
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        content_dir: dir,
+        ..ServerConfig::default()
+    });
+
+    let mut stream = common::connect(&address);
+    std::io::Write::write_all(
+        &mut stream,
+        b"GET /video.bin HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let head_end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8_lossy(&response[..head_end]);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("\r\nContent-Length: 3145728\r\n"), "{}", head);
+    assert!(response[head_end..] == content[..]);
+}
+
+#[test]
+fn handlers_can_return_file_responses() {
+    let dir = common::temp_dir("file_response_handler");
+    let path = dir.join("report.txt");
+    std::fs::write(&path, "report").unwrap();
+
+    let mut router = Router::new();
+    router.get("/report", move |_| Response::from_file(&path).unwrap());
+
+    let (_server, address) = common::run_server_with_router(
+        ServerConfig {
+            threads_count: 1,
+            ..ServerConfig::default()
+        },
+        router,
+    );
+
+    let response = common::send_raw(&address, "GET /report HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Length: 6\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nreport"), "{}", response);
+
+    let response = common::send_raw(&address, "HEAD /report HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Length: 6\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
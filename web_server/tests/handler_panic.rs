@@ -0,0 +1,31 @@
+mod common;
+
+use web_server::{HttpServer, Response, Router};
+
+#[test]
+fn panicking_handler_answers_500_and_keeps_the_worker() {
+    let mut router = Router::new();
+    router
+        .get("/boom", |_| panic!("handler failed"))
+        .get("/ok", |_| Response::text("still here"));
+
+    let (server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    // Both requests on one connection: the panic must not cost the
+    // connection either.
+    let response = common::send_raw(
+        &address,
+        "GET /boom HTTP/1.1\r\n\r\nGET /ok HTTP/1.1\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 500 INTERNAL SERVER ERROR\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nstill here"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /ok HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+
+    assert_eq!(server.lock().unwrap().panic_count(), 1);
+}
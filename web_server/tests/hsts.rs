@@ -0,0 +1,46 @@
+mod common;
+
+use web_server::{HstsPolicy, ServerConfig};
+
+#[test]
+fn hsts_header_value_lists_enabled_flags() {
+    let policy = HstsPolicy {
+        max_age: 31536000,
+        include_subdomains: true,
+        preload: true,
+    };
+    assert_eq!(
+        policy.header_value(),
+        "max-age=31536000; includeSubDomains; preload"
+    );
+
+    let policy = HstsPolicy {
+        max_age: 60,
+        include_subdomains: false,
+        preload: false,
+    };
+    assert_eq!(policy.header_value(), "max-age=60");
+}
+
+#[test]
+fn hsts_is_not_sent_over_plaintext() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        debug_echo_path: Some("/echo".to_string()),
+        hsts: Some(HstsPolicy {
+            max_age: 31536000,
+            include_subdomains: true,
+            preload: false,
+        }),
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /echo HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    assert!(
+        !response.contains("Strict-Transport-Security"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use web_server::{HttpServer, Response, Router};
+
+/// Reads one response head, byte by byte so nothing after it is consumed.
+fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0; 1];
+        if stream.read(&mut byte).unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+fn router() -> Router {
+    let mut router = Router::new();
+    router.post("/upload", |request| {
+        Response::text(&format!("{} bytes", request.body().len()))
+    });
+    router.get("/stream", |_| {
+        Response::streaming(|writer| {
+            writer.write_all(b"first ")?;
+            writer.write_all(b"second")
+        })
+    });
+    router
+}
+
+#[test]
+fn continue_is_sent_before_the_body_is_read() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .max_body_size(16)
+            .router(router()),
+    );
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n")
+        .unwrap();
+    assert_eq!(read_head(&mut stream), "HTTP/1.1 100 CONTINUE\r\n\r\n");
+    stream.write_all(b"hello").unwrap();
+    let head = read_head(&mut stream);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    let mut body = [0; 7];
+    stream.read_exact(&mut body).unwrap();
+    assert_eq!(&body, b"5 bytes");
+
+    drop(stream);
+
+    // A body that is refused anyway is not asked for.
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 17\r\n\r\n")
+        .unwrap();
+    let head = read_head(&mut stream);
+    assert!(
+        head.starts_with("HTTP/1.1 413 PAYLOAD TOO LARGE\r\n"),
+        "{}",
+        head
+    );
+
+    // Nor is a missing one, and HTTP/1.0 clients cannot ask.
+    let response = common::send_raw(
+        &address,
+        "GET /hello.html HTTP/1.1\r\nExpect: 100-continue\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    let response = common::send_raw(
+        &address,
+        "POST /upload HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 2\r\n\r\nhi",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\n2 bytes"), "{}", response);
+}
+
+#[test]
+fn http_1_0_connections_close_unless_kept_alive() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .router(router()),
+    );
+
+    // Closed by the server, without the client half-closing first.
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /hello.html HTTP/1.0\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("<p>Hi from Rust</p>"), "{}", response);
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"HEAD /hello.html HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+    let head = read_head(&mut stream);
+    assert!(head.contains("\r\nConnection: keep-alive\r\n"), "{}", head);
+    stream
+        .write_all(b"GET /hello1.html HTTP/1.0\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+
+    // Chunks are unknown to HTTP/1.0, so the body ends with the connection.
+    let mut stream = common::connect(&address);
+    stream.write_all(b"GET /stream HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(!response.contains("Transfer-Encoding"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nfirst second"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /stream HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nTransfer-Encoding: chunked\r\n"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,40 @@
+mod common;
+
+#[test]
+fn plaintext_request_is_redirected_to_https() {
+    let server = web_server::run_https_redirect_server(1, "127.0.0.1:0".to_string(), 8443).unwrap();
+    let address = server.lock().unwrap().local_addr().to_string();
+
+    let response = common::send_raw(
+        &address,
+        "GET /docs/page.html?x=1 HTTP/1.1\r\nHost: example.com:8080\r\n\r\n",
+    );
+
+    assert!(response.starts_with("HTTP/1.1 301 "), "{}", response);
+    assert!(response.contains("\r\nLocation: https://example.com:8443/docs/page.html?x=1\r\n"));
+}
+
+#[test]
+fn default_https_port_is_omitted_from_location() {
+    let server = web_server::run_https_redirect_server(1, "127.0.0.1:0".to_string(), 443).unwrap();
+    let address = server.lock().unwrap().local_addr().to_string();
+
+    let response = common::send_raw(&address, "GET / HTTP/1.1\r\nHost: [::1]:8080\r\n\r\n");
+
+    assert!(
+        response.contains("\r\nLocation: https://[::1]/\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn request_without_host_gets_upgrade_required() {
+    let server = web_server::run_https_redirect_server(1, "127.0.0.1:0".to_string(), 443).unwrap();
+    let address = server.lock().unwrap().local_addr().to_string();
+
+    let response = common::send_raw(&address, "GET / HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 426 "), "{}", response);
+    assert!(response.contains("\r\nUpgrade: TLS/1.2, HTTP/1.1\r\n"));
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use web_server::{HttpServer, Method, Request, Response, Router};
+
+/// A server on a port picked by the system, so tests cannot collide.
+fn start_on_ephemeral_port(router: Router) -> (Arc<Mutex<HttpServer>>, String) {
+    let server = HttpServer::builder()
+        .threads(4)
+        .bind("127.0.0.1:0")
+        .content_dir(common::content_dir())
+        .router(router)
+        .start()
+        .unwrap();
+    let local_addr = server.lock().unwrap().local_addr();
+    assert_ne!(local_addr.port(), 0);
+    (server, local_addr.to_string())
+}
+
+#[test]
+fn ephemeral_port_instance_serves_files() {
+    let (server, address) = start_on_ephemeral_port(Router::new());
+
+    let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("<p>Hi from Rust</p>"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /missing.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+
+    // The repository root, with `web_server/Cargo.toml`, is right above the
+    // content directory.
+    for target in [
+        "/../web_server/Cargo.toml",
+        "/%2e%2e/web_server/Cargo.toml",
+        "/..%2fweb_server%2fCargo.toml",
+        "/a/../../web_server/Cargo.toml",
+    ] {
+        let response = common::send_raw(&address, &format!("GET {} HTTP/1.1\r\n\r\n", target));
+        assert!(
+            response.starts_with("HTTP/1.1 403 "),
+            "{}: {}",
+            target,
+            response
+        );
+        assert!(!response.contains("[package]"), "{}: {}", target, response);
+    }
+
+    server.lock().unwrap().shutdown().unwrap();
+    web_server::join_server(server).unwrap();
+}
+
+#[test]
+fn concurrent_requests_are_all_answered() {
+    let mut router = Router::new();
+    router.get("/slow", |request| {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        Response::text(request.query().get("n").unwrap_or(""))
+    });
+    let (server, address) = start_on_ephemeral_port(router);
+
+    let clients: Vec<_> = (0..16)
+        .map(|client| {
+            let address = address.clone();
+            std::thread::spawn(move || {
+                for request in 0..5 {
+                    let n = client * 100 + request;
+                    let response =
+                        common::send_raw(&address, &format!("GET /slow?n={} HTTP/1.1\r\n\r\n", n));
+                    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+                    assert!(
+                        response.ends_with(&format!("\r\n\r\n{}", n)),
+                        "{}",
+                        response
+                    );
+                }
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().unwrap();
+    }
+
+    server.lock().unwrap().shutdown().unwrap();
+    web_server::join_server(server).unwrap();
+}
+
+#[test]
+fn requests_are_handled_without_a_connection() {
+    let mut router = Router::new();
+    router.post("/echo", |request| {
+        Response::text(&String::from_utf8_lossy(request.body()))
+    });
+    router.middleware(|request, next| {
+        let mut response = next(request);
+        response.set_header("X-Middleware", "ran");
+        response
+    });
+    let (server, _) = start_on_ephemeral_port(router);
+    let server = server.lock().unwrap();
+
+    let response = server
+        .handle(Request::new(Method::Get, "/hello.html").unwrap())
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header("X-Middleware"), Some("ran"));
+    assert_eq!(
+        response.header("Content-Type"),
+        Some("text/html; charset=utf-8")
+    );
+    let bytes = String::from_utf8(response.to_bytes().unwrap()).unwrap();
+    assert!(bytes.contains("<p>Hi from Rust</p>"), "{}", bytes);
+
+    let mut request = Request::new(Method::Post, "/echo?x=1").unwrap();
+    request.headers_mut().set("Content-Type", "text/plain");
+    request.set_body(b"posted".to_vec());
+    let response = server.handle(request).unwrap();
+    assert_eq!(response.body(), Some(&b"posted"[..]));
+
+    let response = server
+        .handle(Request::new(Method::Get, "/missing.html").unwrap())
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let response = server
+        .handle(Request::new(Method::Delete, "/echo").unwrap())
+        .unwrap();
+    assert_eq!(response.status(), 405);
+
+    let error = Request::new(Method::Get, "/../web_server/Cargo.toml").err();
+    assert_eq!(error.map(|error| error.status_code()), Some(403));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use web_server::{HttpServer, Response, Router};
+
+fn start(idle_timeout: Duration, max_requests: usize) -> String {
+    let mut router = Router::new();
+    router.get("/a", |_| Response::text("first"));
+    router.get("/b", |_| Response::text("second"));
+    router.get("/no-length", |_| Response::new(200));
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(2)
+            .router(router)
+            .keep_alive(idle_timeout, max_requests),
+    );
+    address
+}
+
+/// Reads one response with a `Content-Length` body from `reader`.
+fn read_response(reader: &mut BufReader<TcpStream>) -> String {
+    let mut head = String::new();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if let Some(value) = line.strip_prefix("Content-Length: ") {
+            content_length = value.trim().parse().unwrap();
+        }
+        head.push_str(&line);
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    head + &String::from_utf8(body).unwrap()
+}
+
+#[test]
+fn several_requests_share_one_connection() {
+    let address = start(Duration::from_secs(5), 100);
+
+    let stream = common::connect(&address);
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    writer.write_all(b"GET /a HTTP/1.1\r\n\r\n").unwrap();
+    let first = read_response(&mut reader);
+    assert!(
+        first.contains("\r\nConnection: keep-alive\r\n"),
+        "{}",
+        first
+    );
+    assert!(first.ends_with("first"), "{}", first);
+
+    writer.write_all(b"GET /b HTTP/1.1\r\n\r\n").unwrap();
+    let second = read_response(&mut reader);
+    assert!(second.ends_with("second"), "{}", second);
+}
+
+#[test]
+fn pipelined_requests_are_answered_in_order() {
+    let address = start(Duration::from_secs(5), 100);
+
+    let stream = common::connect(&address);
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    writer
+        .write_all(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n")
+        .unwrap();
+
+    assert!(read_response(&mut reader).ends_with("first"));
+    assert!(read_response(&mut reader).ends_with("second"));
+}
+
+#[test]
+fn connection_close_is_honored() {
+    let address = start(Duration::from_secs(5), 100);
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /a HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("first"), "{}", response);
+}
+
+#[test]
+fn requests_per_connection_are_capped() {
+    let address = start(Duration::from_secs(5), 2);
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /a HTTP/1.1\r\n\r\nGET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert_eq!(response.matches("HTTP/1.1 200 ").count(), 2, "{}", response);
+    assert!(
+        response.ends_with("Connection: close\r\n\r\nfirst"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn idle_connection_is_closed_after_timeout() {
+    let address = start(Duration::from_millis(100), 100);
+
+    let stream = common::connect(&address);
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    writer.write_all(b"GET /a HTTP/1.1\r\n\r\n").unwrap();
+    read_response(&mut reader);
+
+    let started = Instant::now();
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn response_without_length_forces_close() {
+    let address = start(Duration::from_secs(5), 100);
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /no-length HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::net::{TcpListener, ToSocketAddrs};
+
+use web_server::{bind_listener, IpPreference, ServerConfig};
+
+#[test]
+fn ipv4_first_binds_ipv4_for_localhost() {
+    let listener = bind_listener("localhost:0", IpPreference::Ipv4First).unwrap();
+    assert!(listener.local_addr().unwrap().is_ipv4());
+}
+
+#[test]
+fn ipv6_first_binds_ipv6_when_localhost_resolves_to_it() {
+    let resolves_to_ipv6 = "localhost:0"
+        .to_socket_addrs()
+        .unwrap()
+        .any(|candidate| candidate.is_ipv6() && TcpListener::bind(candidate).is_ok());
+
+    let listener = bind_listener("localhost:0", IpPreference::Ipv6First).unwrap();
+    assert_eq!(listener.local_addr().unwrap().is_ipv6(), resolves_to_ipv6);
+}
+
+#[test]
+fn error_is_reported_when_all_candidates_fail() {
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+
+    assert!(bind_listener(&address, IpPreference::AsResolved).is_err());
+}
+
+#[test]
+fn both_listens_on_every_address_of_a_name() {
+    let bindable: Vec<_> = "localhost:0"
+        .to_socket_addrs()
+        .unwrap()
+        .filter(|candidate| TcpListener::bind(candidate).is_ok())
+        .map(|candidate| candidate.ip())
+        .collect();
+
+    let server = web_server::run_server_with_config(ServerConfig {
+        threads_count: 1,
+        address: "localhost:0".to_string(),
+        ip_preference: IpPreference::Both,
+        ..ServerConfig::default()
+    })
+    .unwrap();
+
+    let local_addrs = server.lock().unwrap().local_addrs().to_vec();
+    let ips: Vec<_> = local_addrs.iter().map(|address| address.ip()).collect();
+    assert_eq!(ips, bindable);
+    let port = local_addrs[0].port();
+    for address in &local_addrs {
+        assert_eq!(address.port(), port);
+        let response = common::send_raw(&address.to_string(), "GET /missing.html HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    }
+}
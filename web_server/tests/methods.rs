@@ -0,0 +1,149 @@
+mod common;
+
+use web_server::{HttpServer, Method, Response, Router, ServerConfig};
+
+fn router() -> Router {
+    let mut router = Router::new();
+    router
+        .get("/items", |_| Response::text("listing"))
+        .post("/items", |request| {
+            Response::text(&format!(
+                "created {}",
+                String::from_utf8_lossy(request.body())
+            ))
+        })
+        .put("/items/1", |request| {
+            Response::text(&format!(
+                "{} {}",
+                request.method().as_str(),
+                request.body().len()
+            ))
+        })
+        .delete("/items/1", |request| {
+            assert_eq!(request.method(), Method::Delete);
+            Response::text("deleted")
+        });
+    router
+}
+
+fn start() -> String {
+    let (_server, address) = common::run_server_with_router(
+        ServerConfig {
+            threads_count: 2,
+            ..ServerConfig::default()
+        },
+        router(),
+    );
+    address
+}
+
+#[test]
+fn handlers_receive_request_bodies() {
+    let address = start();
+
+    let response = common::send_raw(
+        &address,
+        "POST /items HTTP/1.1\r\nContent-Length: 6\r\n\r\napples",
+    );
+    assert!(response.ends_with("\r\n\r\ncreated apples"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "PUT /items/1 HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc",
+    );
+    assert!(response.ends_with("\r\n\r\nPUT 3"), "{}", response);
+
+    let response = common::send_raw(&address, "DELETE /items/1 HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\ndeleted"), "{}", response);
+}
+
+#[test]
+fn head_uses_get_handler_without_body() {
+    let address = start();
+
+    let response = common::send_raw(&address, "HEAD /items HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    assert!(
+        response.contains("\r\nContent-Length: 7\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
+
+#[test]
+fn unregistered_method_gets_405_with_allow() {
+    let address = start();
+
+    let response = common::send_raw(&address, "DELETE /items HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 405 "), "{}", response);
+    assert!(
+        response.contains("\r\nAllow: GET, HEAD, POST, OPTIONS\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn static_files_only_accept_get_and_head() {
+    let address = start();
+
+    let response = common::send_raw(
+        &address,
+        "POST /hello.html HTTP/1.1\r\nContent-Length: 0\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 405 "), "{}", response);
+    assert!(
+        response.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "HEAD /missing.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
+
+#[test]
+fn options_lists_the_allowed_methods() {
+    let mut router = router();
+    router.route(Method::Options, "/custom", |_| Response::text("custom"));
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .router(router),
+    );
+
+    for (path, allowed, other) in [
+        ("/items", "GET, HEAD, POST, OPTIONS", "DELETE"),
+        ("/items/1", "PUT, DELETE, OPTIONS", "GET"),
+        ("/hello.html", "GET, HEAD, OPTIONS", "DELETE"),
+    ] {
+        let response = common::send_raw(&address, &format!("OPTIONS {} HTTP/1.1\r\n\r\n", path));
+        assert!(
+            response.starts_with("HTTP/1.1 204 NO CONTENT\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.contains(&format!("\r\nAllow: {}\r\n", allowed)),
+            "{}",
+            response
+        );
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+
+        // A 405 for the same path lists the same methods.
+        let response = common::send_raw(&address, &format!("{} {} HTTP/1.1\r\n\r\n", other, path));
+        assert!(response.starts_with("HTTP/1.1 405 "), "{}", response);
+        assert!(
+            response.contains(&format!("\r\nAllow: {}\r\n", allowed)),
+            "{}",
+            response
+        );
+    }
+
+    // A route of its own takes over.
+    let response = common::send_raw(&address, "OPTIONS /custom HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\ncustom"), "{}", response);
+}
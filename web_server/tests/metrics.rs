@@ -0,0 +1,139 @@
+mod common;
+
+use web_server::{HttpServer, Response, Router};
+
+fn metric_line<'a>(metrics: &'a str, name: &str) -> &'a str {
+    metrics
+        .lines()
+        .find(|line| line.starts_with(name) && line[name.len()..].starts_with(' '))
+        .unwrap_or_else(|| panic!("{} missing in\n{}", name, metrics))
+}
+
+#[test]
+fn requests_are_counted_by_status() {
+    let mut router = Router::new();
+    router.get("/hello", |_| Response::text("hello"));
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .health_check("/healthz")
+            .metrics("/metrics")
+            .router(router),
+    );
+
+    let response = common::send_raw(&address, "GET /healthz HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nok\n"), "{}", response);
+
+    common::send_raw(
+        &address,
+        "GET /hello HTTP/1.1\r\n\r\nGET /hello HTTP/1.1\r\n\r\n",
+    );
+    common::send_raw(&address, "GET /missing HTTP/1.1\r\n\r\n");
+    common::send_raw(&address, "GET /%zz HTTP/1.1\r\n\r\n");
+
+    let response = common::send_raw(&address, "GET /metrics HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n"),
+        "{}",
+        response
+    );
+    let metrics = response.split_once("\r\n\r\n").unwrap().1;
+    assert_eq!(
+        metric_line(metrics, "http_requests_total{code=\"200\"}"),
+        "http_requests_total{code=\"200\"} 3"
+    );
+    assert_eq!(
+        metric_line(metrics, "http_requests_total{code=\"404\"}"),
+        "http_requests_total{code=\"404\"} 1"
+    );
+    assert_eq!(
+        metric_line(metrics, "http_requests_total{code=\"400\"}"),
+        "http_requests_total{code=\"400\"} 1"
+    );
+    // The metrics request itself is still being handled.
+    assert_eq!(
+        metric_line(metrics, "http_requests_in_flight"),
+        "http_requests_in_flight 1"
+    );
+    assert_eq!(
+        metric_line(metrics, "http_request_duration_seconds_count"),
+        "http_request_duration_seconds_count 5"
+    );
+    assert_eq!(
+        metric_line(metrics, "http_request_duration_seconds_bucket{le=\"+Inf\"}"),
+        "http_request_duration_seconds_bucket{le=\"+Inf\"} 5"
+    );
+    assert_eq!(
+        metric_line(metrics, "thread_pool_workers"),
+        "thread_pool_workers 1"
+    );
+    let bytes: u64 = metric_line(metrics, "http_response_bytes_total")
+        .rsplit(' ')
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(
+        bytes > 5 * "HTTP/1.1 200 OK\r\n".len() as u64,
+        "{}",
+        metrics
+    );
+
+    let response = common::send_raw(&address, "GET /metrics?format=json HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Type: application/json\r\n"),
+        "{}",
+        response
+    );
+    let json = response.split_once("\r\n\r\n").unwrap().1;
+    assert!(json.starts_with("{\"requests_total\":6,"), "{}", json);
+    assert!(
+        json.contains("\"statuses\":{\"200\":4,\"400\":1,\"404\":1}"),
+        "{}",
+        json
+    );
+    assert!(json.contains("\"http_requests_in_flight\":1"), "{}", json);
+    assert!(json.contains("{\"le\":null,\"count\":6}"), "{}", json);
+}
+
+#[test]
+fn metrics_can_have_a_listener_of_their_own() {
+    let (server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .health_check("/healthz")
+            .metrics("/metrics")
+            .metrics_address("127.0.0.1:0"),
+    );
+    let metrics_address = server.lock().unwrap().companion_addrs()[0].to_string();
+
+    let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    let response = common::send_raw(&address, "GET /metrics HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+
+    let response = common::send_raw(&metrics_address, "GET /healthz HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nok\n"), "{}", response);
+    let response = common::send_raw(&metrics_address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+
+    // Only the requests of the main listener are counted.
+    let response = common::send_raw(&metrics_address, "GET /metrics HTTP/1.1\r\n\r\n");
+    let metrics = response.split_once("\r\n\r\n").unwrap().1;
+    assert_eq!(
+        metric_line(metrics, "http_requests_total{code=\"200\"}"),
+        "http_requests_total{code=\"200\"} 1"
+    );
+    assert_eq!(
+        metric_line(metrics, "http_requests_total{code=\"404\"}"),
+        "http_requests_total{code=\"404\"} 1"
+    );
+    assert_eq!(
+        metric_line(metrics, "http_requests_in_flight"),
+        "http_requests_in_flight 0"
+    );
+}
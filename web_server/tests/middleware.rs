@@ -0,0 +1,115 @@
+mod common;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use web_server::{HttpServer, Response, Router};
+
+#[test]
+fn middleware_wraps_handlers_in_order() {
+    let mut router = Router::new();
+    router
+        .middleware(|request, next| {
+            let mut response = next(request);
+            response.append_header("X-Trace", "outer");
+            response
+        })
+        .middleware(|request, next| {
+            let mut response = next(request);
+            response.append_header("X-Trace", "inner");
+            response
+        })
+        .get("/hello", |_| Response::text("hello"));
+
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    let response = common::send_raw(&address, "GET /hello HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nX-Trace: inner\r\nX-Trace: outer\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nhello"), "{}", response);
+
+    // Static files and error responses pass through the chain as well.
+    let response = common::send_raw(&address, "GET /missing HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    assert!(response.contains("\r\nX-Trace: inner\r\n"), "{}", response);
+}
+
+#[test]
+fn middleware_can_short_circuit() {
+    let mut router = Router::new();
+    router.get("/secret", |_| Response::text("the secret"));
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .router(router)
+            .use_middleware(|request, next| {
+                if request.header("Authorization") == Some("Bearer letmein") {
+                    next(request)
+                } else {
+                    let mut response = Response::text("denied");
+                    response.set_status(401);
+                    response.set_header("WWW-Authenticate", "Bearer");
+                    response
+                }
+            }),
+    );
+
+    let response = common::send_raw(&address, "GET /secret HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 401 UNAUTHORIZED\r\n"),
+        "{}",
+        response
+    );
+    assert!(!response.contains("the secret"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /secret HTTP/1.1\r\nAuthorization: Bearer letmein\r\n\r\n",
+    );
+    assert!(response.ends_with("\r\n\r\nthe secret"), "{}", response);
+}
+
+#[test]
+fn middleware_can_modify_the_request() {
+    let next_id = Arc::new(AtomicU64::new(100));
+    let mut router = Router::new();
+    router.get("/new", |request| {
+        Response::text(&format!(
+            "id={} page={}",
+            request.header("X-Request-Id").unwrap_or("-"),
+            request.query().get("page").unwrap_or("-")
+        ))
+    });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .router(router)
+            .use_middleware(move |request, next| {
+                let id = next_id.fetch_add(1, Ordering::SeqCst).to_string();
+                request.headers_mut().set("X-Request-Id", &id);
+                let mut response = next(request);
+                response.set_header("X-Request-Id", &id);
+                response
+            })
+            .use_middleware(|request, next| {
+                if let Some(query) = request.target().strip_prefix("/old?") {
+                    let target = format!("/new?{}", query);
+                    request.set_target(&target);
+                }
+                next(request)
+            }),
+    );
+
+    let response = common::send_raw(&address, "GET /old?page=3 HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nX-Request-Id: 100\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nid=100 page=3"), "{}", response);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::path::Path;
+
+use web_server::{HttpServer, MimeTypes};
+
+#[test]
+fn extensions_map_to_content_types() {
+    let mime_types = MimeTypes::new();
+    assert_eq!(
+        mime_types.for_path(Path::new("a/style.CSS")),
+        "text/css; charset=utf-8"
+    );
+    assert_eq!(
+        mime_types.for_path(Path::new("app.wasm")),
+        "application/wasm"
+    );
+    assert_eq!(mime_types.for_path(Path::new("font.woff2")), "font/woff2");
+    assert_eq!(mime_types.for_path(Path::new("image.svg")), "image/svg+xml");
+    assert_eq!(
+        mime_types.for_path(Path::new("data.bin")),
+        "application/octet-stream"
+    );
+    assert_eq!(
+        mime_types.for_path(Path::new("Makefile")),
+        "application/octet-stream"
+    );
+}
+
+#[test]
+fn custom_mappings_override_defaults() {
+    let mut mime_types = MimeTypes::new();
+    mime_types.insert(".JS", "application/javascript");
+    mime_types.insert("webmanifest", "application/manifest+json");
+
+    assert_eq!(mime_types.get("js"), Some("application/javascript"));
+    assert_eq!(
+        mime_types.for_path(Path::new("site.webmanifest")),
+        "application/manifest+json"
+    );
+}
+
+#[test]
+fn static_files_are_served_with_content_type() {
+    let dir = common::temp_dir("mime");
+    std::fs::write(dir.join("style.css"), "body {}").unwrap();
+    std::fs::write(dir.join("blob.xyz"), "?").unwrap();
+    std::fs::write(dir.join("app.custom"), "!").unwrap();
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(&dir)
+            .mime_type("custom", "application/x-custom"),
+    );
+
+    let response = common::send_raw(&address, "GET /style.css HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Type: text/css; charset=utf-8\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "GET /blob.xyz HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Type: application/octet-stream\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "GET /app.custom HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nContent-Type: application/x-custom\r\n"),
+        "{}",
+        response
+    );
+}
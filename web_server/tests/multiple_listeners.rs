@@ -0,0 +1,61 @@
+mod common;
+
+use std::net::{TcpListener, TcpStream};
+
+use web_server::HttpServer;
+
+#[test]
+fn every_bound_address_serves_and_shutdown_stops_all() {
+    let server = HttpServer::builder()
+        .threads(2)
+        .bind("127.0.0.1:0")
+        .also_bind("127.0.0.1:0")
+        .content_dir(common::content_dir())
+        .start()
+        .unwrap();
+    let [first, second] = <[_; 2]>::try_from(server.lock().unwrap().local_addrs()).unwrap();
+
+    for address in [first, second] {
+        let address = address.to_string();
+        let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    }
+
+    server.lock().unwrap().shutdown().unwrap();
+    web_server::join_server(server).unwrap();
+    assert!(TcpStream::connect(first).is_err());
+    assert!(TcpStream::connect(second).is_err());
+}
+
+#[test]
+fn ipv4_and_ipv6_listeners_share_a_port() {
+    // Wildcards on both families collide unless the IPv6 listener leaves
+    // IPv4 to the other one.
+    let port = common::free_port();
+    let server =
+        web_server::run_server_on(1, &[format!("0.0.0.0:{}", port), format!("[::]:{}", port)])
+            .unwrap();
+
+    for address in [format!("127.0.0.1:{}", port), format!("[::1]:{}", port)] {
+        let response = common::send_raw(&address, "GET /missing HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    }
+
+    server.lock().unwrap().shutdown().unwrap();
+    web_server::join_server(server).unwrap();
+}
+
+#[test]
+fn failing_address_releases_the_others() {
+    let address = format!("127.0.0.1:{}", common::free_port());
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let result = HttpServer::builder()
+        .threads(1)
+        .bind(&address)
+        .also_bind(taken.local_addr().unwrap().to_string())
+        .start();
+    assert!(result.is_err());
+
+    assert!(TcpListener::bind(&address).is_ok());
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use web_server::ServerConfig;
+
+#[test]
+fn not_found_page_contains_escaped_path() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /foo<script>&\"x\"?q=1 HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    assert!(response.contains("\r\nContent-Type: text/html; charset=utf-8\r\n"));
+    assert!(
+        response.contains("Could not find /foo&lt;script&gt;&amp;&quot;x&quot;<"),
+        "{}",
+        response
+    );
+    assert!(!response.contains("<script>"), "{}", response);
+}
+
+#[test]
+fn path_reflection_can_be_disabled() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        not_found_reflects_path: false,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /secret-name HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    assert!(!response.contains("secret-name"), "{}", response);
+}
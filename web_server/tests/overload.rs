@@ -0,0 +1,58 @@
+mod common;
+
+use std::io::{Read, Write};
+
+use web_server::{HttpServer, Response, Router};
+
+#[test]
+fn connections_beyond_the_queue_get_503() {
+    let mut router = Router::new();
+    router.get("/hi", |_| Response::text("hi"));
+
+    let (server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .max_queued_connections(1)
+            .router(router),
+    );
+
+    // The only worker keeps this connection open waiting for the next
+    // request once it has answered the first.
+    let mut busy = common::connect(&address);
+    busy.write_all(b"GET /hi HTTP/1.1\r\n\r\n").unwrap();
+    let mut received = Vec::new();
+    let mut buffer = [0; 256];
+    while !received.ends_with(b"hi") {
+        let read = busy.read(&mut buffer).unwrap();
+        assert!(read > 0);
+        received.extend_from_slice(&buffer[..read]);
+    }
+
+    let mut queued = common::connect(&address);
+    queued
+        .write_all(b"GET /hi HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    // Answered without reading anything, so there is nothing to send.
+    let mut rejected = common::connect(&address);
+    let mut response = String::new();
+    rejected.read_to_string(&mut response).unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 503 SERVICE UNAVAILABLE\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("\r\nRetry-After: 10\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+
+    drop(busy);
+    let mut response = String::new();
+    queued.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+
+    assert_eq!(server.lock().unwrap().rejected_count(), 1);
+}
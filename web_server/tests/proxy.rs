@@ -0,0 +1,193 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+
+use web_server::{HttpServer, ProxyHandler, Response, Router};
+
+/// Accepts one connection on the returned address, answers it with
+/// `response` and sends the request it received, body included, through the
+/// returned channel.
+fn fake_upstream(response: &'static str) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(len) = line.strip_prefix("Content-Length: ") {
+                content_length = len.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        sender.send(request).unwrap();
+    });
+    (address, receiver)
+}
+
+#[test]
+fn requests_under_the_prefix_are_forwarded() {
+    let (upstream_address, upstream) = fake_upstream(
+        "HTTP/1.1 201 Created\r\nContent-Length: 7\r\nX-Upstream: yes\r\n\
+         Keep-Alive: timeout=5\r\nConnection: close\r\n\r\ncreated",
+    );
+
+    let mut router = Router::new();
+    router
+        .proxy("/api/*", ProxyHandler::new(&upstream_address))
+        .get("/api/local", |_| Response::text("local"));
+
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    // Exact routes still win over the proxy prefix.
+    let response = common::send_raw(&address, "GET /api/local HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nlocal"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "POST /api/items?x=1 HTTP/1.1\r\nHost: example.test\r\nX-Forwarded-For: 10.0.0.1\r\n\
+         Content-Length: 4\r\nConnection: keep-alive\r\n\r\nitem",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 201 CREATED\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("\r\nX-Upstream: yes\r\n"), "{}", response);
+    assert!(!response.contains("Keep-Alive"), "{}", response);
+    assert!(response.ends_with("\r\n\r\ncreated"), "{}", response);
+
+    let forwarded = upstream.recv().unwrap();
+    assert!(
+        forwarded.starts_with("POST /api/items?x=1 HTTP/1.1\r\n"),
+        "{}",
+        forwarded
+    );
+    assert!(
+        forwarded.contains("\r\nHost: example.test\r\n"),
+        "{}",
+        forwarded
+    );
+    assert!(
+        forwarded.contains("\r\nX-Forwarded-For: 10.0.0.1, 127.0.0.1\r\n"),
+        "{}",
+        forwarded
+    );
+    assert!(
+        forwarded.contains("\r\nX-Forwarded-Proto: http\r\n"),
+        "{}",
+        forwarded
+    );
+    assert!(
+        forwarded.contains("\r\nConnection: close\r\n"),
+        "{}",
+        forwarded
+    );
+    assert!(!forwarded.contains("keep-alive"), "{}", forwarded);
+    assert!(forwarded.ends_with("\r\n\r\nitem"), "{}", forwarded);
+}
+
+#[test]
+fn upstream_response_without_length_is_streamed_to_its_end() {
+    let (upstream_address, _upstream) =
+        fake_upstream("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nuntil the end");
+
+    let mut router = Router::new();
+    router.proxy("/*", ProxyHandler::new(&upstream_address));
+
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    let response = common::send_raw(&address, "GET / HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nuntil the end"), "{}", response);
+}
+
+#[test]
+fn unreachable_upstream_is_a_bad_gateway() {
+    let mut router = Router::new();
+    // Nothing listens there.
+    let upstream_address = format!("127.0.0.1:{}", common::free_port());
+    router.proxy("/api/*", ProxyHandler::new(&upstream_address));
+
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    let response = common::send_raw(&address, "GET /api HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 502 BAD GATEWAY\r\n"),
+        "{}",
+        response
+    );
+
+    // Paths that only share the prefix text are not forwarded.
+    let response = common::send_raw(&address, "GET /apis HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+}
+
+#[test]
+fn chunked_upstream_bodies_are_decoded_for_http_1_0_clients() {
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_address = upstream.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        for stream in upstream.incoming().take(2) {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            reader
+                .get_mut()
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                      5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nX-Trailer: yes\r\n\r\n",
+                )
+                .unwrap();
+        }
+    });
+
+    let mut router = Router::new();
+    router.proxy("/*", ProxyHandler::new(upstream_address));
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    let response = common::send_raw(&address, "GET /chunks HTTP/1.0\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(!response.contains("Transfer-Encoding"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nhello, world"), "{}", response);
+
+    // HTTP/1.1 clients get the chunks as the upstream sent them.
+    let response = common::send_raw(&address, "GET /chunks HTTP/1.1\r\n\r\n");
+    assert!(
+        response.contains("\r\nTransfer-Encoding: chunked\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.ends_with("\r\n0\r\nX-Trailer: yes\r\n\r\n"),
+        "{}",
+        response
+    );
+}
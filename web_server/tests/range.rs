@@ -0,0 +1,115 @@
+mod common;
+
+use web_server::{parse_range, RangeRequest, ServerConfig};
+
+#[test]
+fn single_ranges_are_parsed() {
+    assert_eq!(
+        parse_range("bytes=0-4", 10),
+        RangeRequest::Partial { start: 0, end: 4 }
+    );
+    assert_eq!(
+        parse_range("bytes=6-", 10),
+        RangeRequest::Partial { start: 6, end: 9 }
+    );
+    assert_eq!(
+        parse_range("bytes=-3", 10),
+        RangeRequest::Partial { start: 7, end: 9 }
+    );
+    assert_eq!(
+        parse_range("bytes=-30", 10),
+        RangeRequest::Partial { start: 0, end: 9 }
+    );
+    assert_eq!(
+        parse_range("bytes=8-100", 10),
+        RangeRequest::Partial { start: 8, end: 9 }
+    );
+}
+
+#[test]
+fn ranges_past_the_end_are_unsatisfiable() {
+    assert_eq!(parse_range("bytes=10-", 10), RangeRequest::Unsatisfiable);
+    assert_eq!(parse_range("bytes=20-30", 10), RangeRequest::Unsatisfiable);
+    assert_eq!(parse_range("bytes=-0", 10), RangeRequest::Unsatisfiable);
+    assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+}
+
+#[test]
+fn unusable_headers_select_the_full_body() {
+    for header in [
+        "items=0-4",
+        "bytes=5-2",
+        "bytes=a-b",
+        "bytes=0-1,4-5",
+        "bytes=4",
+    ] {
+        assert_eq!(parse_range(header, 10), RangeRequest::Full, "{}", header);
+    }
+}
+
+#[test]
+fn static_files_answer_range_requests() {
+    let dir = common::temp_dir("range_static");
+    std::fs::write(dir.join("clip.bin"), "0123456789").unwrap();
+
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        content_dir: dir,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /clip.bin HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nAccept-Ranges: bytes\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        "GET /clip.bin HTTP/1.1\r\nRange: bytes=2-5\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 206 PARTIAL CONTENT\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nContent-Range: bytes 2-5/10\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nContent-Length: 4\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\n2345"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /clip.bin HTTP/1.1\r\nRange: bytes=-3\r\n\r\n",
+    );
+    assert!(response.ends_with("\r\n\r\n789"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /clip.bin HTTP/1.1\r\nRange: bytes=10-\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 416 RANGE NOT SATISFIABLE\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nContent-Range: bytes */10\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nContent-Length: 0\r\n"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use web_server::{HttpServer, Response, Router};
+
+fn read_response(stream: &mut std::net::TcpStream) -> String {
+    let mut buffer = [0; 4096];
+    let read = stream.read(&mut buffer).unwrap();
+    String::from_utf8_lossy(&buffer[..read]).into_owned()
+}
+
+#[test]
+fn connections_per_ip_are_capped() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(4)
+            .content_dir(common::content_dir())
+            .max_connections_per_ip(2),
+    );
+
+    let mut open = Vec::new();
+    for _ in 0..2 {
+        let mut stream = common::connect(&address);
+        stream
+            .write_all(b"GET /hello.html HTTP/1.1\r\n\r\n")
+            .unwrap();
+        let response = read_response(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        open.push(stream);
+    }
+
+    // Answered without reading the request.
+    let mut stream = common::connect(&address);
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 429 TOO MANY REQUESTS\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("\r\nRetry-After: 1\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nConnection: close\r\n"),
+        "{}",
+        response
+    );
+
+    // Closing a connection frees its slot again.
+    drop(open.pop());
+    let mut served = false;
+    for _ in 0..50 {
+        // A rejected attempt may be closed before the request is written.
+        let mut stream = common::connect(&address);
+        let _ = stream.write_all(b"GET /hello.html HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        if response.starts_with("HTTP/1.1 200 OK\r\n") {
+            served = true;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(served);
+}
+
+#[test]
+fn requests_beyond_the_rate_get_429() {
+    let mut router = Router::new();
+    router.get("/", |_| Response::text("ok"));
+
+    let (server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .requests_per_second(1.0, 2)
+            .router(router),
+    );
+
+    let response = common::send_raw(
+        &address,
+        "GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+    );
+    let responses: Vec<&str> = response.split("HTTP/1.1 ").skip(1).collect();
+    assert_eq!(responses.len(), 3, "{}", response);
+    assert!(responses[0].starts_with("200 "), "{}", response);
+    assert!(responses[1].starts_with("200 "), "{}", response);
+    assert!(
+        responses[2].starts_with("429 TOO MANY REQUESTS\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        responses[2].contains("\r\nRetry-After: 1\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(server.lock().unwrap().rate_limited_count(), 1);
+
+    // The bucket refills over time, also for new connections.
+    std::thread::sleep(Duration::from_millis(1100));
+    let response = common::send_raw(&address, "GET / HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+}
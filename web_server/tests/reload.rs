@@ -0,0 +1,172 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use web_server::{ErrorPages, HttpServer, Response, Router, ServerConfig};
+
+/// Reads one response off a persistent connection, going by its
+/// `Content-Length`.
+fn read_response(stream: &mut std::net::TcpStream) -> String {
+    let mut response = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let read = stream.read(&mut buffer).unwrap();
+        assert!(read > 0, "{}", String::from_utf8_lossy(&response));
+        response.extend_from_slice(&buffer[..read]);
+
+        let text = String::from_utf8_lossy(&response).into_owned();
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .map_or(0, |length| length.parse().unwrap());
+            if body.len() >= length {
+                return text;
+            }
+        }
+    }
+}
+
+#[test]
+fn open_connections_pick_up_a_reload_between_requests() {
+    let old_dir = common::temp_dir("reload_old");
+    std::fs::write(old_dir.join("page.html"), "old page").unwrap();
+    let new_dir = common::temp_dir("reload_new");
+    std::fs::write(new_dir.join("page.html"), "new page").unwrap();
+    std::fs::write(new_dir.join("404.html"), "no {{path}} here").unwrap();
+
+    let (server, address) =
+        common::start_server(HttpServer::builder().threads(1).content_dir(&old_dir));
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /page.html HTTP/1.1\r\n\r\n")
+        .unwrap();
+    let response = read_response(&mut stream);
+    assert!(response.ends_with("\r\n\r\nold page"), "{}", response);
+
+    server
+        .lock()
+        .unwrap()
+        .reload(ServerConfig {
+            content_dir: new_dir,
+            error_pages: ErrorPages::new().template(404, "404.html"),
+            ..ServerConfig::default()
+        })
+        .unwrap();
+
+    stream
+        .write_all(b"GET /page.html HTTP/1.1\r\n\r\n")
+        .unwrap();
+    let response = read_response(&mut stream);
+    assert!(response.ends_with("\r\n\r\nnew page"), "{}", response);
+
+    stream.write_all(b"GET /gone HTTP/1.1\r\n\r\n").unwrap();
+    let response = read_response(&mut stream);
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    assert!(response.ends_with("\r\n\r\nno /gone here"), "{}", response);
+}
+
+#[test]
+fn requests_in_flight_finish_with_the_old_settings() {
+    let mut router = Router::new();
+    router.get("/slow", |_| {
+        std::thread::sleep(Duration::from_millis(300));
+        Response::text("slow")
+    });
+    router.get("/fast", |_| Response::text("fast"));
+
+    let (server, address) = common::start_server(
+        HttpServer::builder()
+            .config(ServerConfig {
+                server_header: Some("before".to_string()),
+                ..ServerConfig::default()
+            })
+            .threads(2)
+            .health_check("/healthz")
+            .router(router),
+    );
+
+    let slow_address = address.clone();
+    let slow =
+        std::thread::spawn(move || common::send_raw(&slow_address, "GET /slow HTTP/1.1\r\n\r\n"));
+    std::thread::sleep(Duration::from_millis(100));
+    server
+        .lock()
+        .unwrap()
+        .reload(ServerConfig {
+            server_header: Some("after".to_string()),
+            ..ServerConfig::default()
+        })
+        .unwrap();
+
+    let response = slow.join().unwrap();
+    assert!(response.contains("\r\nServer: before\r\n"), "{}", response);
+    let response = common::send_raw(&address, "GET /fast HTTP/1.1\r\n\r\n");
+    assert!(response.contains("\r\nServer: after\r\n"), "{}", response);
+    // The router, monitoring routes included, is kept.
+    let response = common::send_raw(&address, "GET /healthz HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nok\n"), "{}", response);
+
+    let mut router = Router::new();
+    router.get("/fast", |_| Response::text("replaced"));
+    server
+        .lock()
+        .unwrap()
+        .reload_with_router(
+            ServerConfig {
+                health_path: Some("/health".to_string()),
+                ..ServerConfig::default()
+            },
+            router,
+        )
+        .unwrap();
+
+    let response = common::send_raw(&address, "GET /fast HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nreplaced"), "{}", response);
+    let response = common::send_raw(&address, "GET /slow HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    let response = common::send_raw(&address, "GET /health HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nok\n"), "{}", response);
+}
+
+#[cfg(all(unix, feature = "signals"))]
+#[test]
+fn sighup_reloads_the_config() {
+    let dir = common::temp_dir("reload_sighup");
+    std::fs::write(dir.join("page.html"), "reloaded").unwrap();
+
+    let (server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir()),
+    );
+    web_server::reload_on_sighup(server, move || {
+        Ok(ServerConfig {
+            content_dir: dir.clone(),
+            ..ServerConfig::default()
+        })
+    })
+    .unwrap();
+
+    let response = common::send_raw(&address, "GET /page.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut reloaded = false;
+    for _ in 0..50 {
+        let response = common::send_raw(&address, "GET /page.html HTTP/1.1\r\n\r\n");
+        if response.ends_with("\r\n\r\nreloaded") {
+            reloaded = true;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(reloaded);
+}
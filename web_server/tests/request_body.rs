@@ -0,0 +1,72 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::Shutdown;
+
+use web_server::{HttpServer, Response, Router, ServerConfig};
+
+#[test]
+fn body_sent_in_the_same_write_as_headers_is_not_lost() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        debug_echo_path: Some("/echo".to_string()),
+        ..ServerConfig::default()
+    });
+
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"GET /echo HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world")
+        .unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    assert!(
+        response.contains("\"body\":\"hello world\""),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn content_length_must_be_digits_and_agree() {
+    let mut router = Router::new();
+    router.post("/echo", |request| {
+        Response::text(&String::from_utf8_lossy(request.body()))
+    });
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    // Repeated fields and lists are fine while they agree.
+    for lengths in [
+        "Content-Length: 5\r\nContent-Length: 5",
+        "Content-Length: 5, 5",
+    ] {
+        let response = common::send_raw(
+            &address,
+            &format!("POST /echo HTTP/1.1\r\n{}\r\n\r\nhello", lengths),
+        );
+        assert!(response.ends_with("\r\n\r\nhello"), "{}", response);
+    }
+
+    for lengths in [
+        "Content-Length: +5",
+        "Content-Length: -5",
+        "Content-Length: 0x5",
+        "Content-Length: ",
+        "Content-Length: 5\r\nContent-Length: 6",
+        "Content-Length: 5, 6",
+        "Content-Length: 5\r\nContent-Length: +5",
+    ] {
+        let response = common::send_raw(
+            &address,
+            &format!("POST /echo HTTP/1.1\r\n{}\r\n\r\nhello!", lengths),
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 400 "),
+            "{}\n---\n{}",
+            lengths,
+            response
+        );
+    }
+}
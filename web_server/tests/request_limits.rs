@@ -0,0 +1,91 @@
+mod common;
+
+use std::io::{Read, Write};
+
+use web_server::{HttpServer, Response, Router};
+
+#[test]
+fn oversized_requests_are_refused_while_reading() {
+    let mut router = Router::new();
+    router.post("/upload", |request| {
+        Response::text(&format!("{} bytes", request.body().len()))
+    });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .max_body_size(16)
+            .max_uri_length(64)
+            .max_headers(1024, 4)
+            .router(router),
+    );
+
+    let response = common::send_raw(
+        &address,
+        "POST /upload HTTP/1.1\r\nContent-Length: 16\r\n\r\n0123456789abcdef",
+    );
+    assert!(response.ends_with("\r\n\r\n16 bytes"), "{}", response);
+
+    // Refused on the announced length alone; the body is never sent.
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 1000000000\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 413 PAYLOAD TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+         10\r\n0123456789abcdef\r\n1\r\n!\r\n0\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 413 PAYLOAD TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        &format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(63)),
+    );
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+
+    // Refused before the end of the line arrives.
+    let mut stream = common::connect(&address);
+    stream
+        .write_all(format!("GET /{}", "a".repeat(200)).as_bytes())
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 414 URI TOO LONG\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        &format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(64)),
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 414 URI TOO LONG\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        "GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use web_server::{Headers, QueryParams, Response, Router, ServerConfig};
+
+#[test]
+fn handlers_see_headers_and_query_parameters() {
+    let mut router = Router::new();
+    router.get("/search", |request| {
+        let headers = request.headers();
+        let query = request.query();
+        Response::text(&format!(
+            "host={} agent={} accept={} q={} page={} tags={}",
+            headers.get("host").unwrap_or("-"),
+            headers.get("USER-AGENT").unwrap_or("-"),
+            headers.get_all("Accept").collect::<Vec<_>>().join("|"),
+            query.get("q").unwrap_or("-"),
+            query.get("page").unwrap_or("-"),
+            query.get_all("tag").collect::<Vec<_>>().join("|"),
+        ))
+    });
+    let (_server, address) = common::run_server_with_router(
+        ServerConfig {
+            threads_count: 1,
+            ..ServerConfig::default()
+        },
+        router,
+    );
+
+    let response = common::send_raw(
+        &address,
+        "GET /search?q=rust+web%20server&page=2&tag=a&tag=b HTTP/1.1\r\n\
+         Host: example.com\r\n\
+         User-Agent: test-agent/1.0\r\n\
+         Accept: text/html\r\n\
+         accept: application/json\r\n\r\n",
+    );
+
+    assert!(
+        response.ends_with(
+            "\r\n\r\nhost=example.com agent=test-agent/1.0 \
+             accept=text/html|application/json q=rust web server page=2 tags=a|b"
+        ),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn query_string_is_not_part_of_the_static_file_path() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /missing.html?v=3 HTTP/1.1\r\n\r\n");
+
+    assert!(
+        response.contains("Could not find /missing.html<"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn query_params_parsing() {
+    let query = QueryParams::parse("a=1&&b&c=%3D%26&d=x=y&bad=%zz");
+    assert_eq!(
+        query.iter().collect::<Vec<_>>(),
+        vec![
+            ("a", "1"),
+            ("b", ""),
+            ("c", "=&"),
+            ("d", "x=y"),
+            ("bad", "%zz")
+        ]
+    );
+    assert_eq!(query.get("missing"), None);
+}
+
+#[test]
+fn headers_lookup_is_case_insensitive() {
+    let mut headers = Headers::new();
+    headers.append("Set-Cookie", "a=1");
+    headers.append("set-cookie", "b=2");
+    headers.set("Content-Type", "text/plain");
+    headers.set("content-type", "text/html");
+
+    assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+    assert_eq!(headers.get_all("Set-Cookie").count(), 2);
+    assert_eq!(headers.get("Content-Type"), Some("text/html"));
+    assert_eq!(headers.len(), 3);
+
+    headers.remove("SET-cookie");
+    assert!(!headers.contains("Set-Cookie"));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use web_server::{normalize_path, HttpServer, RequestTarget, Response, Router, WebServerError};
+
+#[test]
+fn targets_are_split_and_normalized() {
+    let target = RequestTarget::parse("/a//b/./c/../my%20file.html?x=%20&y#frag").unwrap();
+    assert_eq!(target.path(), "/a/b/my file.html");
+    assert_eq!(target.query(), Some("x=%20&y"));
+    assert_eq!(target.fragment(), Some("frag"));
+
+    let target = RequestTarget::parse("/docs").unwrap();
+    assert_eq!(target.query(), None);
+    assert_eq!(target.fragment(), None);
+
+    let target = RequestTarget::parse("http://example.com:8080/x%2Fy?q").unwrap();
+    assert_eq!(target.path(), "/x/y");
+    assert_eq!(target.query(), Some("q"));
+    assert_eq!(
+        RequestTarget::parse("HTTPS://example.com").unwrap().path(),
+        "/"
+    );
+}
+
+#[test]
+fn dot_segments_keep_the_directory_slash() {
+    for (path, normalized) in [
+        ("/", "/"),
+        ("//", "/"),
+        ("/a/", "/a/"),
+        ("/a/.", "/a/"),
+        ("/a/b/..", "/a/"),
+        ("/a/./b", "/a/b"),
+        ("/a/../..b", "/..b"),
+        ("/a/b/../../", "/"),
+    ] {
+        assert_eq!(
+            normalize_path(path).as_deref(),
+            Some(normalized),
+            "{}",
+            path
+        );
+    }
+    assert_eq!(normalize_path("/.."), None);
+    assert_eq!(normalize_path("/a/../../b"), None);
+}
+
+#[test]
+fn invalid_targets_are_rejected() {
+    for target in [
+        "/%zz",
+        "/%f",
+        "/%ff",
+        "/%+a",
+        "/%-0",
+        "*",
+        "relative/path",
+        "ftp://host/x",
+        "http://",
+    ] {
+        match RequestTarget::parse(target) {
+            Err(WebServerError::BadRequest(_)) => {}
+            other => panic!("{}: unexpected {:?}", target, other),
+        }
+    }
+    match RequestTarget::parse("/a/%2e%2e/%2E%2E/secret") {
+        Err(WebServerError::Forbidden(_)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn router_and_static_files_see_the_normalized_path() {
+    let content = common::temp_dir("request_target");
+    std::fs::create_dir_all(content.join("my dir")).unwrap();
+    std::fs::write(content.join("my file.html"), "spaced").unwrap();
+    std::fs::write(content.join("my dir/index.html"), "index").unwrap();
+
+    let mut router = Router::new();
+    router.get("/api/user name", |request| {
+        Response::text(&format!("{} {}", request.path(), request.target()))
+    });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(&content)
+            .router(router),
+    );
+
+    let response = common::send_raw(&address, "GET /my%20file.html HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nspaced"), "{}", response);
+
+    let response = common::send_raw(&address, "GET //api/./x/../user%20name HTTP/1.1\r\n\r\n");
+    assert!(
+        response.ends_with("\r\n\r\n/api/user name //api/./x/../user%20name"),
+        "{}",
+        response
+    );
+
+    // The redirect to the directory is encoded again.
+    let response = common::send_raw(&address, "GET /my%20dir?a=%20 HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 301 "), "{}", response);
+    assert!(
+        response.contains("\r\nLocation: /my%20dir/?a=%20\r\n"),
+        "{}",
+        response
+    );
+    let response = common::send_raw(&address, "GET /my%20dir/ HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nindex"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /api/%zz HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+    let response = common::send_raw(&address, "GET /../secret HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 403 "), "{}", response);
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use web_server::ServerConfig;
+
+#[test]
+fn oversized_response_headers_become_a_clean_500() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        debug_echo_path: Some("/echo".to_string()),
+        max_response_header_bytes: 40,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /echo HTTP/1.1\r\n\r\n");
+
+    assert_eq!(
+        response,
+        "HTTP/1.1 500 INTERNAL SERVER ERROR\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    );
+}
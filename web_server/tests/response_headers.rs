@@ -0,0 +1,123 @@
+mod common;
+
+use web_server::{canonical_header_name, parse_http_date, HeaderCasing, Response, ServerConfig};
+
+#[test]
+fn headers_are_written_in_canonical_casing() {
+    let mut response = Response::new(200);
+    response.set_header("content-type", "text/plain");
+    response.set_header("X-CUSTOM-header", "1");
+    response.set_header("etag", "\"abc\"");
+    response.set_header("www-authenticate", "Basic");
+    response.set_body(b"hi".to_vec());
+
+    let wire = String::from_utf8(response.to_bytes().unwrap()).unwrap();
+    assert_eq!(
+        wire,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain\r\n\
+         X-Custom-Header: 1\r\n\
+         ETag: \"abc\"\r\n\
+         WWW-Authenticate: Basic\r\n\
+         Content-Length: 2\r\n\
+         \r\n\
+         hi"
+    );
+}
+
+#[test]
+fn header_casing_can_be_preserved() {
+    let mut response = Response::new(404);
+    response.set_header("x-lower", "1");
+
+    let wire = String::from_utf8(response.serialize(HeaderCasing::AsSet).unwrap()).unwrap();
+    assert!(wire.contains("\r\nx-lower: 1\r\n"));
+}
+
+#[test]
+fn setting_a_header_twice_replaces_it() {
+    let mut response = Response::new(200);
+    response.set_header("Content-Type", "text/plain");
+    response.set_header("CONTENT-TYPE", "text/html");
+
+    assert_eq!(response.header("content-type"), Some("text/html"));
+    let wire = String::from_utf8(response.to_bytes().unwrap()).unwrap();
+    assert_eq!(wire.matches("Content-Type").count(), 1);
+}
+
+#[test]
+fn canonical_names() {
+    assert_eq!(canonical_header_name("content-length"), "Content-Length");
+    assert_eq!(canonical_header_name("CACHE-CONTROL"), "Cache-Control");
+    assert_eq!(
+        canonical_header_name("sec-websocket-accept"),
+        "Sec-WebSocket-Accept"
+    );
+    assert_eq!(canonical_header_name("Etag"), "ETag");
+}
+
+#[test]
+fn oversized_header_block_fails_to_serialize() {
+    let mut response = Response::new(200);
+    response.set_header("Set-Cookie", &"a".repeat(100));
+
+    assert!(response
+        .serialize_with_limit(HeaderCasing::Canonical, 64)
+        .is_err());
+    assert!(response
+        .serialize_with_limit(HeaderCasing::Canonical, 1024)
+        .is_ok());
+}
+
+#[test]
+fn constructors_build_complete_responses() {
+    let wire = String::from_utf8(Response::ok().to_bytes().unwrap()).unwrap();
+    assert_eq!(wire, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+
+    let response = Response::json("{\"ok\":true}");
+    assert_eq!(response.header("Content-Type"), Some("application/json"));
+    assert_eq!(response.body(), Some(&b"{\"ok\":true}"[..]));
+
+    let response = Response::not_found();
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.header("Content-Length"), Some("9"));
+
+    let wire = String::from_utf8(Response::internal_server_error().to_bytes().unwrap()).unwrap();
+    assert_eq!(
+        wire,
+        "HTTP/1.1 500 INTERNAL SERVER ERROR\r\nContent-Length: 0\r\n\r\n"
+    );
+}
+
+#[test]
+fn body_length_is_written_even_without_the_header() {
+    let mut response = Response::text("hello");
+    response.remove_header("Content-Length");
+
+    let wire = String::from_utf8(response.to_bytes().unwrap()).unwrap();
+    assert!(wire.contains("\r\nContent-Length: 5\r\n"), "{}", wire);
+}
+
+#[test]
+fn server_adds_date_and_server_headers() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /missing HTTP/1.1\r\n\r\n");
+
+    let date = response
+        .split("\r\n")
+        .find_map(|line| line.strip_prefix("Date: "))
+        .unwrap();
+    assert!(parse_http_date(date).is_some(), "{}", date);
+    assert!(
+        response.contains(&format!(
+            "\r\nServer: web_server/{}\r\n",
+            env!("CARGO_PKG_VERSION")
+        )),
+        "{}",
+        response
+    );
+}
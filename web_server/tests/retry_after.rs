@@ -0,0 +1,37 @@
+mod common;
+
+use web_server::ServerConfig;
+
+#[test]
+fn transient_errors_have_default_retry_after() {
+    let config = ServerConfig::default();
+    assert_eq!(config.retry_after_seconds(503), Some(10));
+    assert_eq!(config.retry_after_seconds(504), Some(5));
+    assert_eq!(config.retry_after_seconds(500), None);
+}
+
+#[test]
+fn retry_after_values_are_configurable() {
+    let config = ServerConfig {
+        retry_after: vec![(503, 120)],
+        ..ServerConfig::default()
+    };
+    assert_eq!(config.retry_after_seconds(503), Some(120));
+    assert_eq!(config.retry_after_seconds(504), None);
+}
+
+#[test]
+fn internal_server_error_has_no_retry_after() {
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        debug_echo_path: Some("/echo".to_string()),
+        // Forces the echo response to be replaced by a 500.
+        max_response_header_bytes: 40,
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /echo HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 500 "), "{}", response);
+    assert!(!response.contains("Retry-After"), "{}", response);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use web_server::{Response, Router, ServerConfig};
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        threads_count: 2,
+        ..ServerConfig::default()
+    }
+}
+
+#[test]
+fn registered_handler_answers_its_route() {
+    let mut router = Router::new();
+    router
+        .get("/api/status", |_| Response::text("ok"))
+        .get("/api/echo-header", |request| {
+            Response::text(request.header("x-name").unwrap_or("none"))
+        });
+    let (_server, address) = common::run_server_with_router(config(), router);
+
+    let response = common::send_raw(&address, "GET /api/status?verbose=1 HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+    assert!(response.contains("\r\nContent-Type: text/plain; charset=utf-8\r\n"));
+    assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /api/echo-header HTTP/1.1\r\nX-Name: router\r\n\r\n",
+    );
+    assert!(response.ends_with("\r\n\r\nrouter"), "{}", response);
+}
+
+#[test]
+fn unmatched_requests_fall_back_to_static_files() {
+    let mut router = Router::new();
+    router.get("/api/status", |_| Response::text("ok"));
+    let (_server, address) = common::run_server_with_router(config(), router);
+
+    let response = common::send_raw(&address, "GET /missing.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+}
+
+#[test]
+fn fallback_handler_can_be_replaced() {
+    let mut router = Router::new();
+    router.fallback(|request| Response::text(&format!("fallback for {}", request.path())));
+    let (_server, address) = common::run_server_with_router(config(), router);
+
+    let response = common::send_raw(&address, "GET /anything HTTP/1.1\r\n\r\n");
+    assert!(
+        response.ends_with("\r\n\r\nfallback for /anything"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn prefix_routes_match_the_longest_prefix() {
+    let mut router = Router::new();
+    router
+        .get("/files/*", |request| {
+            Response::text(&format!("files {}", request.path()))
+        })
+        .get("/files/private/*", |_| Response::text("private"))
+        .any("/hook/*", |request| {
+            Response::text(request.method().as_str())
+        });
+    let (_server, address) = common::run_server_with_router(config(), router);
+
+    let response = common::send_raw(&address, "GET /files/a/b.txt HTTP/1.1\r\n\r\n");
+    assert!(
+        response.ends_with("\r\n\r\nfiles /files/a/b.txt"),
+        "{}",
+        response
+    );
+    let response = common::send_raw(&address, "GET /files/private/key HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nprivate"), "{}", response);
+
+    let response = common::send_raw(&address, "DELETE /files/a HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 405 "), "{}", response);
+    assert!(
+        response.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(&address, "PUT /hook/x HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nPUT"), "{}", response);
+}
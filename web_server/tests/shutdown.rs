@@ -0,0 +1,84 @@
+mod common;
+
+use std::net::TcpStream;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use web_server::{HttpServer, Response, Router};
+
+fn join_with_timeout(server: Arc<std::sync::Mutex<HttpServer>>) {
+    let (done, joined) = mpsc::channel();
+    std::thread::spawn(move || {
+        done.send(web_server::join_server(server).is_ok()).unwrap();
+    });
+    assert_eq!(joined.recv_timeout(Duration::from_secs(5)), Ok(true));
+}
+
+#[test]
+fn shutdown_unblocks_accept_loop_and_join() {
+    let (server, address) = common::start_server(HttpServer::builder().threads(1));
+
+    server.lock().unwrap().shutdown().unwrap();
+    join_with_timeout(server);
+
+    assert!(TcpStream::connect(&address).is_err());
+}
+
+#[test]
+fn in_flight_requests_finish_during_shutdown() {
+    let (entered, handler_entered) = mpsc::channel();
+    let entered = std::sync::Mutex::new(entered);
+    let mut router = Router::new();
+    router.get("/slow", move |_| {
+        entered.lock().unwrap().send(()).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        Response::text("done")
+    });
+    let (server, address) = common::start_server(HttpServer::builder().threads(2).router(router));
+
+    let client =
+        std::thread::spawn(move || common::send_raw(&address, "GET /slow HTTP/1.1\r\n\r\n"));
+    handler_entered.recv().unwrap();
+
+    server.lock().unwrap().shutdown().unwrap();
+    join_with_timeout(server);
+
+    let response = client.join().unwrap();
+    assert!(response.ends_with("\r\n\r\ndone"), "{}", response);
+}
+
+#[test]
+fn shutdown_works_for_wildcard_bind() {
+    let server = HttpServer::builder()
+        .threads(1)
+        .bind("0.0.0.0:0")
+        .start()
+        .unwrap();
+
+    server.lock().unwrap().shutdown().unwrap();
+    join_with_timeout(server);
+}
+
+#[test]
+fn join_blocks_until_shutdown() {
+    let server = HttpServer::builder()
+        .threads(1)
+        .bind("127.0.0.1:0")
+        .start()
+        .unwrap();
+
+    let (done, joined) = mpsc::channel();
+    let joiner = {
+        let server = Arc::clone(&server);
+        std::thread::spawn(move || done.send(web_server::join_server(server).is_ok()).unwrap())
+    };
+    assert!(joined.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // join_server must not hold the lock while waiting.
+    server.lock().unwrap().shutdown().unwrap();
+    assert_eq!(joined.recv_timeout(Duration::from_secs(5)), Ok(true));
+    joiner.join().unwrap();
+
+    // Joining a stopped server returns immediately.
+    join_with_timeout(server);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use web_server::HttpServer;
+
+#[test]
+fn trickling_client_is_cut_off_by_the_request_deadline() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .read_timeout(Duration::from_secs(5))
+            .request_read_timeout(Duration::from_millis(300)),
+    );
+
+    let mut stream = common::connect(&address);
+    stream
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+    let started = Instant::now();
+    let mut response = Vec::new();
+    // Each byte arrives well within the read timeout, but the whole head
+    // never does.
+    for byte in b"GET / HTTP/1.1\r\nX-Slow: ".iter().cycle() {
+        if stream.write_all(&[*byte]).is_err() {
+            break;
+        }
+        let mut buffer = [0; 1024];
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => response.extend_from_slice(&buffer[..read]),
+            Err(_) => {}
+        }
+        if !response.is_empty() || started.elapsed() > Duration::from_secs(5) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(30));
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    assert!(
+        response.starts_with("HTTP/1.1 408 REQUEST TIMEOUT\r\n"),
+        "{}",
+        response
+    );
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn header_size_and_count_are_limited() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .max_headers(256, 3),
+    );
+
+    let response = common::send_raw(
+        &address,
+        "GET /hello.html HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    let response = common::send_raw(
+        &address,
+        "GET /hello.html HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        &format!("GET /hello.html HTTP/1.1\r\nA: {}\r\n\r\n", "x".repeat(300)),
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE\r\n"),
+        "{}",
+        response
+    );
+}
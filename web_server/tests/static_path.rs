@@ -0,0 +1,121 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use web_server::{resolve_static_path, PathResolution, ServerConfig};
+
+fn content_root(name: &str) -> PathBuf {
+    let root = common::temp_dir(name);
+    std::fs::create_dir_all(root.join("content/a")).unwrap();
+    std::fs::write(root.join("content/a/b.txt"), "b").unwrap();
+    std::fs::write(root.join("content/my file.html"), "file").unwrap();
+    std::fs::write(root.join("secret.txt"), "secret").unwrap();
+    root
+}
+
+fn found(root: &Path, relative: &str) -> PathResolution {
+    PathResolution::Found(root.join(relative).canonicalize().unwrap())
+}
+
+#[test]
+fn paths_inside_the_root_are_resolved() {
+    let root = content_root("static_path_inside");
+    let content = root.join("content");
+
+    assert_eq!(
+        resolve_static_path(&content, "/a/b.txt"),
+        found(&content, "a/b.txt")
+    );
+    assert_eq!(
+        resolve_static_path(&content, "/a/./b.txt"),
+        found(&content, "a/b.txt")
+    );
+    assert_eq!(
+        resolve_static_path(&content, "/a/../a//b.txt"),
+        found(&content, "a/b.txt")
+    );
+    assert_eq!(
+        resolve_static_path(&content, "/my%20file.html"),
+        found(&content, "my file.html")
+    );
+    assert_eq!(
+        resolve_static_path(&content, "/missing.txt"),
+        PathResolution::NotFound
+    );
+}
+
+#[test]
+fn traversal_outside_the_root_is_forbidden() {
+    let root = content_root("static_path_traversal");
+    let content = root.join("content");
+
+    for path in [
+        "/../secret.txt",
+        "/a/../../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/..%2fsecret.txt",
+        "/a%2f..%2f..%2fsecret.txt",
+        "/..%5csecret.txt",
+        "/a/b.txt%00.html",
+    ] {
+        assert_eq!(
+            resolve_static_path(&content, path),
+            PathResolution::Forbidden,
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn invalid_encoding_is_a_bad_request() {
+    let root = content_root("static_path_encoding");
+    let content = root.join("content");
+
+    assert_eq!(
+        resolve_static_path(&content, "/%zz"),
+        PathResolution::BadRequest
+    );
+    assert_eq!(
+        resolve_static_path(&content, "/%ff"),
+        PathResolution::BadRequest
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_leaving_the_root_are_forbidden() {
+    let root = content_root("static_path_symlink");
+    let content = root.join("content");
+    std::os::unix::fs::symlink(root.join("secret.txt"), content.join("link.txt")).unwrap();
+
+    assert_eq!(
+        resolve_static_path(&content, "/link.txt"),
+        PathResolution::Forbidden
+    );
+}
+
+#[test]
+fn server_answers_traversal_with_forbidden() {
+    let root = content_root("static_path_server");
+    let (_server, address) = common::run_server(ServerConfig {
+        threads_count: 1,
+        content_dir: root.join("content"),
+        ..ServerConfig::default()
+    });
+
+    let response = common::send_raw(&address, "GET /%2e%2e/secret.txt HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 403 FORBIDDEN\r\n"),
+        "{}",
+        response
+    );
+    assert!(!response.contains("secret\n"), "{}", response);
+
+    let response = common::send_raw(&address, "GET /%zz HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+
+    let response = common::send_raw(&address, "GET /a/b.txt HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nb"), "{}", response);
+}
@@ -0,0 +1,156 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use web_server::{PoolConfig, Priority, ThreadPool};
+
+/// Occupies the only worker of `pool` until the returned sender is dropped.
+fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+    let (release, blocked) = mpsc::channel::<()>();
+    let (started, wait_started) = mpsc::channel();
+    pool.execute(move || {
+        started.send(()).unwrap();
+        let _ = blocked.recv();
+    });
+    wait_started.recv().unwrap();
+    release
+}
+
+fn record(order: &Arc<Mutex<Vec<String>>>, name: String) -> impl FnOnce() + Send + 'static {
+    let order = Arc::clone(order);
+    move || order.lock().unwrap().push(name)
+}
+
+#[test]
+fn high_priority_job_jumps_ahead_of_queued_low_priority_jobs() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let pool = ThreadPool::new(1).unwrap();
+    let release = block_worker(&pool);
+
+    for i in 0..20 {
+        pool.execute_with_priority(record(&order, format!("low {}", i)), Priority::Low);
+    }
+    pool.execute_with_priority(record(&order, "high".to_string()), Priority::High);
+
+    drop(release);
+    drop(pool);
+
+    let order = order.lock().unwrap();
+    assert_eq!(order.len(), 21);
+    assert_eq!(order[0], "high");
+}
+
+#[test]
+fn low_priority_jobs_are_not_starved() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let pool = ThreadPool::new(1).unwrap();
+    let release = block_worker(&pool);
+
+    pool.execute_with_priority(record(&order, "low".to_string()), Priority::Low);
+    for i in 0..50 {
+        pool.execute_with_priority(record(&order, format!("high {}", i)), Priority::High);
+    }
+
+    drop(release);
+    drop(pool);
+
+    let order = order.lock().unwrap();
+    let low_position = order.iter().position(|name| name == "low").unwrap();
+    assert!(
+        low_position < 50,
+        "low job ran at position {}",
+        low_position
+    );
+}
+
+#[test]
+fn jobs_of_equal_priority_run_in_order() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let pool = ThreadPool::new(1).unwrap();
+    let release = block_worker(&pool);
+
+    for i in 0..5 {
+        pool.execute(record(&order, i.to_string()));
+    }
+
+    drop(release);
+    drop(pool);
+
+    assert_eq!(*order.lock().unwrap(), vec!["0", "1", "2", "3", "4"]);
+}
+
+#[test]
+fn panicking_job_does_not_kill_its_worker() {
+    let pool = ThreadPool::new(1).unwrap();
+    pool.execute(|| panic!("job failed"));
+
+    let (done, wait_done) = mpsc::channel();
+    pool.execute(move || done.send(()).unwrap());
+    wait_done.recv().unwrap();
+
+    assert_eq!(pool.panic_count(), 1);
+}
+
+#[test]
+fn bounded_pool_reports_a_full_queue() {
+    assert!(ThreadPool::bounded(1, 0).is_err());
+
+    let pool = ThreadPool::bounded(1, 2).unwrap();
+    let release = block_worker(&pool);
+
+    pool.execute(|| {});
+    assert!(!pool.is_full());
+    pool.execute(|| {});
+    assert!(pool.is_full());
+    assert_eq!(pool.queued_jobs(), 2);
+
+    drop(release);
+}
+
+#[test]
+fn pool_grows_under_load_and_shrinks_when_idle() {
+    let pool = ThreadPool::with_config(PoolConfig {
+        min_threads: 1,
+        max_threads: 3,
+        idle_timeout: Duration::from_millis(50),
+        queue_capacity: None,
+    })
+    .unwrap();
+    assert_eq!(pool.size(), 1);
+
+    // Each job only starts once a worker is free for it, so all three
+    // running at once proves the pool grew.
+    let releases: Vec<_> = (0..3).map(|_| block_worker(&pool)).collect();
+    assert_eq!(pool.size(), 3);
+
+    pool.execute(|| {});
+    assert_eq!(pool.size(), 3);
+    assert_eq!(pool.queued_jobs(), 1);
+
+    drop(releases);
+    let started = Instant::now();
+    while pool.size() > 1 {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "pool never shrank"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(pool.queued_jobs(), 0);
+
+    // The remaining worker still takes jobs.
+    let (done, wait_done) = mpsc::channel();
+    pool.execute(move || done.send(()).unwrap());
+    wait_done.recv().unwrap();
+}
+
+#[test]
+fn pool_config_is_validated() {
+    let config = PoolConfig {
+        min_threads: 4,
+        max_threads: 2,
+        ..PoolConfig::fixed(2)
+    };
+    assert!(ThreadPool::with_config(config).is_err());
+    assert!(ThreadPool::with_config(PoolConfig::fixed(0)).is_err());
+}
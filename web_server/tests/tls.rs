@@ -0,0 +1,122 @@
+// The certificates in `tests/tls` are a test-only CA and a leaf issued by it
+// for `localhost` and `127.0.0.1`.
+#![cfg(feature = "tls")]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use web_server::{HstsPolicy, HttpServer, ServerConfig, TlsConfig};
+
+fn tls_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tls")
+        .join(name)
+}
+
+fn server_tls() -> TlsConfig {
+    TlsConfig::from_pem_files(tls_file("cert.pem"), tls_file("key.pem")).unwrap()
+}
+
+/// Sends `request` over TLS, trusting only the test CA, and returns the
+/// whole response.
+fn send_tls(address: &str, request: &str) -> String {
+    let mut roots = rustls::RootCertStore::empty();
+    let ca = CertificateDer::from_pem_file(tls_file("ca.pem")).unwrap();
+    roots.add(ca).unwrap();
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let client_config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let session = rustls::ClientConnection::new(
+        Arc::new(client_config),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+
+    let mut stream = rustls::StreamOwned::new(session, common::connect(address));
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[test]
+fn https_serves_files_and_sends_hsts() {
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .config(ServerConfig {
+                hsts: Some(HstsPolicy {
+                    max_age: 600,
+                    include_subdomains: false,
+                    preload: false,
+                }),
+                ..ServerConfig::default()
+            })
+            .threads(1)
+            .content_dir(common::content_dir())
+            .tls(server_tls()),
+    );
+
+    let response = send_tls(
+        &address,
+        "GET /hello.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\nStrict-Transport-Security: max-age=600\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("<p>Hi from Rust</p>"), "{}", response);
+}
+
+#[test]
+fn plain_http_listener_redirects_to_https() {
+    let (server, https_address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .tls(server_tls())
+            .redirect_http_from("127.0.0.1:0"),
+    );
+    let http_address = server.lock().unwrap().companion_addrs()[0];
+    let https_port = https_address.rsplit(':').next().unwrap();
+
+    let response = common::send_raw(
+        &http_address.to_string(),
+        &format!(
+            "GET /hello.html?x=1 HTTP/1.1\r\nHost: localhost:{}\r\n\r\n",
+            http_address.port()
+        ),
+    );
+    assert!(response.starts_with("HTTP/1.1 301 "), "{}", response);
+    assert!(
+        response.contains(&format!(
+            "\r\nLocation: https://localhost:{}/hello.html?x=1\r\n",
+            https_port
+        )),
+        "{}",
+        response
+    );
+
+    // Stopping the HTTPS server stops the redirect as well.
+    server.lock().unwrap().shutdown().unwrap();
+    web_server::join_server(server).unwrap();
+    assert!(std::net::TcpStream::connect(http_address).is_err());
+}
+
+#[test]
+fn invalid_pem_is_rejected() {
+    assert!(TlsConfig::from_pem(b"not a certificate", b"not a key").is_err());
+    assert!(TlsConfig::from_pem_files(tls_file("missing.pem"), tls_file("key.pem")).is_err());
+}
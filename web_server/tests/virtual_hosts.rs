@@ -0,0 +1,81 @@
+mod common;
+
+use web_server::{HttpServer, Response, Router, VirtualHost};
+
+fn get(address: &str, host: &str, path: &str) -> String {
+    common::send_raw(
+        address,
+        &format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host),
+    )
+}
+
+#[test]
+fn requests_are_dispatched_by_host() {
+    let site = common::temp_dir("vhost_site");
+    std::fs::write(site.join("index.html"), "site a").unwrap();
+
+    let mut api = Router::new();
+    api.get("/hi", |_| Response::text("api"));
+    api.fallback(|_| Response::text("api fallback"));
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .virtual_host("site-a.test", VirtualHost::new().content_dir(&site))
+            .virtual_host("*.api.test", VirtualHost::new().router(api)),
+    );
+
+    // Host names match case-insensitively and without the port.
+    let response = get(&address, "Site-A.test:8080", "/");
+    assert!(response.ends_with("\r\n\r\nsite a"), "{}", response);
+    let response = get(&address, "site-a.test.", "/hello.html");
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+
+    let response = get(&address, "v1.api.test", "/hi");
+    assert!(response.ends_with("\r\n\r\napi"), "{}", response);
+    let response = get(&address, "v1.api.test", "/other");
+    assert!(response.ends_with("\r\n\r\napi fallback"), "{}", response);
+
+    // The wildcard does not cover the bare domain, which goes to the
+    // default site like any unknown host.
+    for host in ["api.test", "unknown.test"] {
+        let response = get(&address, host, "/hello.html");
+        assert!(response.contains("<p>Hi from Rust</p>"), "{}", response);
+    }
+    let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(response.contains("<p>Hi from Rust</p>"), "{}", response);
+}
+
+#[test]
+fn outer_middlewares_run_for_every_host() {
+    let mut inner = Router::new();
+    inner
+        .middleware(|request, next| {
+            let mut response = next(request);
+            response.append_header("X-Trace", "host");
+            response
+        })
+        .get("/", |_| Response::text("inner"));
+
+    let mut router = Router::new();
+    router
+        .middleware(|request, next| {
+            let mut response = next(request);
+            response.append_header("X-Trace", "server");
+            response
+        })
+        .virtual_host("inner.test", VirtualHost::new().router(inner));
+
+    let (_server, address) = common::start_server(HttpServer::builder().threads(1).router(router));
+
+    let response = get(&address, "inner.test", "/");
+    assert!(
+        response.contains("\r\nX-Trace: host\r\nX-Trace: server\r\n"),
+        "{}",
+        response
+    );
+    let response = get(&address, "other.test", "/");
+    assert!(response.contains("\r\nX-Trace: server\r\n"), "{}", response);
+    assert!(!response.contains("X-Trace: host"), "{}", response);
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use web_server::{HttpServer, Router, WebSocketMessage};
+
+/// The example key of RFC 6455, section 1.3, and its accept value.
+const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+const ACCEPT: &str = "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=";
+
+fn handshake(address: &str, path: &str) -> (TcpStream, String) {
+    let mut stream = common::connect(address);
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+         Connection: keep-alive, Upgrade\r\nSec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n",
+        path, KEY
+    )
+    .unwrap();
+
+    // Read byte by byte, so no frame after the head is consumed.
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0; 1];
+        if stream.read(&mut byte).unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    (stream, String::from_utf8(head).unwrap())
+}
+
+/// Writes one masked client frame.
+fn write_frame(stream: &mut TcpStream, first_byte: u8, payload: &[u8]) {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![first_byte];
+    if payload.len() < 126 {
+        frame.push(0x80 | payload.len() as u8);
+    } else {
+        frame.push(0x80 | 126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    stream.write_all(&frame).unwrap();
+}
+
+/// Reads one server frame as its first byte and payload.
+fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(header[1] & 0x80, 0, "server frames are not masked");
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).unwrap();
+            u16::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).unwrap();
+    (header[0], payload)
+}
+
+#[test]
+fn messages_are_exchanged_outside_the_pool() {
+    let mut router = Router::new();
+    router.websocket("/echo", |mut socket| {
+        let name = socket
+            .request()
+            .query()
+            .get("name")
+            .unwrap_or("")
+            .to_string();
+        socket
+            .send(WebSocketMessage::Text(format!("hello {}", name)))
+            .unwrap();
+        loop {
+            match socket.recv() {
+                Ok(WebSocketMessage::Text(text)) => {
+                    socket.send(WebSocketMessage::Text(text)).unwrap()
+                }
+                Ok(WebSocketMessage::Binary(data)) => {
+                    socket.send(WebSocketMessage::Binary(data)).unwrap()
+                }
+                Ok(WebSocketMessage::Close(_)) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+    });
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(1)
+            .content_dir(common::content_dir())
+            .router(router),
+    );
+
+    let (mut socket, head) = handshake(&address, "/echo?name=ws");
+    assert!(
+        head.starts_with("HTTP/1.1 101 SWITCHING PROTOCOLS\r\n"),
+        "{}",
+        head
+    );
+    assert!(head.contains("\r\nUpgrade: websocket\r\n"), "{}", head);
+    assert!(head.contains("\r\nConnection: Upgrade\r\n"), "{}", head);
+    assert!(
+        head.contains(&format!("\r\nSec-WebSocket-Accept: {}\r\n", ACCEPT)),
+        "{}",
+        head
+    );
+    assert_eq!(read_frame(&mut socket), (0x81, b"hello ws".to_vec()));
+
+    // The only worker is free again while the socket stays open.
+    let response = common::send_raw(&address, "GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    write_frame(&mut socket, 0x81, b"text");
+    assert_eq!(read_frame(&mut socket), (0x81, b"text".to_vec()));
+    let large = vec![7; 300];
+    write_frame(&mut socket, 0x82, &large);
+    assert_eq!(read_frame(&mut socket), (0x82, large));
+
+    // A fragmented message with a ping in the middle.
+    write_frame(&mut socket, 0x01, b"frag");
+    write_frame(&mut socket, 0x89, b"are you there");
+    assert_eq!(read_frame(&mut socket), (0x8A, b"are you there".to_vec()));
+    write_frame(&mut socket, 0x80, b"mented");
+    assert_eq!(read_frame(&mut socket), (0x81, b"fragmented".to_vec()));
+
+    write_frame(&mut socket, 0x88, &1000u16.to_be_bytes());
+    assert_eq!(
+        read_frame(&mut socket),
+        (0x88, 1000u16.to_be_bytes().to_vec())
+    );
+    let mut rest = Vec::new();
+    socket.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn invalid_handshakes_and_frames_are_refused() {
+    let mut router = Router::new();
+    router.websocket("/ws", |mut socket| while socket.recv().is_ok() {});
+
+    let (_server, address) = common::start_server(
+        HttpServer::builder()
+            .threads(2)
+            .max_websockets(1)
+            .max_body_size(64)
+            .router(router),
+    );
+
+    let response = common::send_raw(&address, "GET /ws HTTP/1.1\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 426 UPGRADE REQUIRED\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nUpgrade: websocket\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        &format!(
+            "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 8\r\n\r\n",
+            KEY
+        ),
+    );
+    assert!(response.starts_with("HTTP/1.1 426 "), "{}", response);
+    assert!(
+        response.contains("\r\nSec-WebSocket-Version: 13\r\n"),
+        "{}",
+        response
+    );
+
+    let response = common::send_raw(
+        &address,
+        "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: short\r\nSec-WebSocket-Version: 13\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+
+    // One socket at a time.
+    let (mut socket, head) = handshake(&address, "/ws");
+    assert!(head.starts_with("HTTP/1.1 101 "), "{}", head);
+    let (_, head) = handshake(&address, "/ws");
+    assert!(head.starts_with("HTTP/1.1 503 "), "{}", head);
+
+    // Too large a message closes the connection with 1009.
+    write_frame(&mut socket, 0x82, &[0; 65]);
+    assert_eq!(
+        read_frame(&mut socket),
+        (0x88, 1009u16.to_be_bytes().to_vec())
+    );
+
+    // So does an unmasked frame, with 1002, once the slot is free again.
+    let mut socket = loop {
+        let (socket, head) = handshake(&address, "/ws");
+        if head.starts_with("HTTP/1.1 101 ") {
+            break socket;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    };
+    socket.write_all(&[0x81, 0x02, b'h', b'i']).unwrap();
+    assert_eq!(
+        read_frame(&mut socket),
+        (0x88, 1002u16.to_be_bytes().to_vec())
+    );
+}